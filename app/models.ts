@@ -21,6 +21,22 @@ export type SiteModel = {
     online: boolean
     latest_message_timestamp: number
     latest_dump_timestamp: number
+    degraded: boolean
+    health: 'unknown' | 'up' | 'down'
+    apdex_t: number
+    tls_host: string
+    tls_port: number
+    cert_status:
+        | 'unknown'
+        | 'valid'
+        | 'expiring'
+        | 'expired'
+        | 'invalid'
+        | 'error'
+    cert_expires: number
+    badge_key: string | null
+    signing_key: string | null
+    signature_required: boolean
 }
 
 export type SiteMessageModel = {
@@ -31,6 +47,264 @@ export type SiteMessageModel = {
     tag: string
 }
 
+export type SiteMetricModel = {
+    id: number
+    site: number
+    name: string
+    kind: 'counter' | 'gauge' | 'timer' | 'set'
+    timestamp: number
+    value: number
+    count: number
+    min: number
+    max: number
+}
+
+export type SiteInstanceModel = {
+    site: number
+    instance: string
+    seq: number
+    version: string
+    latest_ping: number
+    latest_dump: number
+    total_requests: number
+    total_requests_time: number
+    requests_max_time: number
+    requests_min_time: number
+    status: { [k: string]: SiteStatusModel }
+}
+
+export type SiteSummaryModel = {
+    from: number
+    to: number
+    total: number
+    requests_per_day: number
+    requests_per_hour: number
+    error_rate_4xx: number
+    error_rate_5xx: number
+    mean_time: number
+    min_time: number
+    max_time: number
+    p50: number | null
+    p90: number | null
+    p95: number | null
+    p99: number | null
+}
+
+export type SiteForecastModel = {
+    timestamp: number
+    requests: number
+    lower: number
+    upper: number
+}
+
+export type SiteEventModel = {
+    id: number
+    site: number
+    timestamp: number
+    kind: 'traffic_drop' | 'error_spike' | 'latency_jump' | 'down' | 'up'
+    value: number
+    baseline: number
+    score: number
+    text: string
+}
+
+export type AlertRuleModel = {
+    id: number
+    site: number | null
+    name: string
+    metric:
+        | 'requests'
+        | 'client_errors'
+        | 'server_errors'
+        | 'mean_time'
+        | 'p95'
+        | 'since_ping'
+    comparator: 'gt' | 'ge' | 'lt' | 'le'
+    threshold: number
+    period: number
+    duration: number
+    severity: 'info' | 'warning' | 'critical'
+    enabled: boolean
+    timestamp: number
+}
+
+export type AlertModel = {
+    id: number
+    rule: number
+    site: number
+    state: 'pending' | 'firing' | 'resolved'
+    value: number
+    started: number
+    fired: number
+    resolved: number
+    updated: number
+}
+
+export type IncidentModel = {
+    id: number
+    site: number
+    cause: 'missed_pings' | 'probe_failure' | 'error_rate' | 'manual'
+    started: number
+    ended: number
+    text: string
+}
+
+export type IncidentNoteModel = {
+    id: number
+    incident: number
+    author: number | null
+    timestamp: number
+    text: string
+}
+
+export type StatusPageModel = {
+    id: number
+    slug: string
+    title: string
+    description: string
+    sites: number[]
+    timestamp: number
+}
+
+export type StatusLevel = 'operational' | 'degraded' | 'outage' | 'unknown'
+
+export type StatusDayModel = {
+    timestamp: number
+    uptime: number | null
+    downtime_minutes: number
+}
+
+export type StatusIncidentModel = {
+    cause: 'missed_pings' | 'probe_failure' | 'error_rate' | 'manual'
+    started: number
+    text: string
+    notes: Pick<IncidentNoteModel, 'timestamp' | 'text'>[]
+}
+
+export type StatusSiteModel = {
+    name: string
+    status: StatusLevel
+    uptime: number
+    days: StatusDayModel[]
+    incidents: StatusIncidentModel[]
+}
+
+export type StatusReportModel = {
+    slug: string
+    title: string
+    description: string
+    status: StatusLevel
+    sites: StatusSiteModel[]
+    timestamp: number
+}
+
+export type UptimeModel = {
+    site: number
+    from: number
+    to: number
+    uptime: number
+    downtime_minutes: number
+    incidents: number
+}
+
+export type SloModel = {
+    id: number
+    site: number
+    name: string
+    kind: 'availability' | 'latency'
+    target: number
+    threshold: number
+    days: number
+    burn_alert: number
+    burning: boolean
+    timestamp: number
+}
+
+export type SloReportModel = {
+    slo: SloModel
+    total: number
+    bad: number
+    compliance: number | null
+    budget_remaining: number | null
+    burn_1h: number | null
+    burn_6h: number | null
+}
+
+export type SiteApdexModel = {
+    timestamp: number
+    route: string
+    total: number
+    satisfied: number
+    tolerating: number
+    frustrated: number
+    score: number | null
+}
+
+export type ProbeModel = {
+    id: number
+    site: number
+    name: string
+    url: string
+    method: 'get' | 'head' | 'post' | 'put' | 'patch' | 'delete' | 'options'
+    status: number
+    body: string
+    timeout: number
+    interval: number
+    failures: number
+    enabled: boolean
+    streak: number
+    since: number
+    checked: number
+    timestamp: number
+}
+
+export type ProbeResultModel = {
+    id: number
+    probe: number
+    timestamp: number
+    ok: boolean
+    status: number
+    latency: number
+    error: string
+}
+
+export type SiteCertModel = {
+    site: number
+    host: string
+    port: number
+    timestamp: number
+    subject: string
+    issuer: string
+    sans: string[]
+    chain: string[]
+    not_before: number
+    not_after: number
+    error: string
+    warned: number
+}
+
+export type HeartbeatModel = {
+    id: number
+    site: number | null
+    name: string
+    token: string
+    period: number
+    grace: number
+    state: 'new' | 'up' | 'started' | 'late' | 'failed'
+    last_ping: number
+    last_start: number
+    timestamp: number
+}
+
+export type HeartbeatPingModel = {
+    id: number
+    heartbeat: number
+    timestamp: number
+    kind: 'success' | 'start' | 'fail'
+    duration: number
+    output: string
+}
+
 export type UserModel = {
     id: number
     phone: string
@@ -1,4 +1,4 @@
-import { SiteMessageModel, SiteModel } from 'models'
+import { SiteMessageModel, SiteModel, SiteSummaryModel } from 'models'
 import { fmt_timeago, fmt_timestamp, httpx } from 'shared'
 import { onMount, Show } from 'solid-js'
 import { createStore, produce } from 'solid-js/store'
@@ -31,6 +31,7 @@ export default () => {
     type State = {
         sites: { [id: string]: SiteModel }
         messages: { [id: string]: SiteMessageModel[] }
+        summaries: { [id: string]: SiteSummaryModel }
         timer: number
         interval: number
         online: boolean
@@ -41,6 +42,7 @@ export default () => {
     const [state, setState] = createStore<State>({
         sites: {},
         messages: {},
+        summaries: {},
         online: false,
         get status() {
             return this.online ? LINE_STATUS.online : LINE_STATUS.offline
@@ -94,9 +96,16 @@ export default () => {
                                 ) {
                                     load_messages(site.id)
                                 }
+                                if (
+                                    old.latest_dump_timestamp !=
+                                    site.latest_dump_timestamp
+                                ) {
+                                    load_summary(site.id)
+                                }
                             } else {
                                 s.sites[site.id] = site
                                 load_messages(site.id)
+                                load_summary(site.id)
                             }
                         })
                     })
@@ -122,6 +131,25 @@ export default () => {
         })
     }
 
+    function load_summary(site_id: number) {
+        httpx({
+            url: `/api/sites/${site_id}/summary/`,
+            method: 'GET',
+            onLoad(x) {
+                if (x.status != 200) return
+                setState(
+                    produce(s => {
+                        s.summaries[site_id] = x.response
+                    })
+                )
+            },
+        })
+    }
+
+    function pct(rate: number): string {
+        return (rate * 100).toFixed(2) + '%'
+    }
+
     return (
         <div class='dash-fnd'>
             <div class='status-bar'>
@@ -147,7 +175,7 @@ export default () => {
                         <div
                             class='site'
                             classList={{
-                                offline: state.now - site.latest_ping > 120,
+                                offline: site.health == 'down',
                             }}
                         >
                             <div class='site-info'>
@@ -200,6 +228,32 @@ export default () => {
                                         {site.total_requests.toLocaleString()}
                                     </span>
                                 </div>
+                                <Show when={state.summaries[site.id]}>
+                                    {sm => (
+                                        <>
+                                            <span>24h 4xx / 5xx:</span>
+                                            <div class='with-space'>
+                                                <span>
+                                                    {pct(sm().error_rate_4xx)}
+                                                </span>
+                                                <span class='spacer'>|</span>
+                                                <span>
+                                                    {pct(sm().error_rate_5xx)}
+                                                </span>
+                                            </div>
+                                            <span>24h count / p95:</span>
+                                            <div class='with-space'>
+                                                <span>
+                                                    {sm().total.toLocaleString()}
+                                                </span>
+                                                <span class='spacer'>|</span>
+                                                <span>
+                                                    {sm().p95 ?? '-'}ms
+                                                </span>
+                                            </div>
+                                        </>
+                                    )}
+                                </Show>
                             </div>
                             <div class='line' />
                             <table class='site-status-table'>
@@ -40,6 +40,10 @@ if [ ! -f main.db ]; then
     echo "$EG setup the database"
     cargo sqlx db setup
     echo $SPACER
+elif check_diff "migrations/*"; then
+    echo "$EG migrate the database"
+    cargo sqlx migrate run
+    echo $SPACER
 fi
 
 if check_diff "src/*"; then
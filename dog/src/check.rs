@@ -0,0 +1,130 @@
+use std::os::unix::fs::PermissionsExt;
+
+use crate::format::Format;
+use crate::input::{sock_path, Allow, Input, Source};
+use crate::privacy::Privacy;
+use crate::sender::{client_init, ping_body, API_PING};
+
+fn report(name: &str, result: Result<String, String>) -> bool {
+    match result {
+        Ok(v) => {
+            println!("ok    {name}: {v}");
+            true
+        }
+        Err(e) => {
+            println!("fail  {name}: {e}");
+            false
+        }
+    }
+}
+
+fn config() -> Result<String, String> {
+    for name in ["HEIMDALL_TOKEN", "HEIMDALL_SITE", "HEIMDALL_SERVICE"] {
+        std::env::var(name).map_err(|_| format!("{name} is not set"))?;
+    }
+
+    let token = std::env::var("HEIMDALL_TOKEN").unwrap_or_default();
+    let valid = token
+        .strip_prefix("site ")
+        .and_then(|v| v.split_once(':'))
+        .is_some_and(|(id, t)| id.parse::<i64>().is_ok() && !t.is_empty());
+    if !valid {
+        return Err("HEIMDALL_TOKEN must look like `site <id>:<token>`".into());
+    }
+
+    Allow::from_env()?;
+    Privacy::from_env()?;
+    let inputs = Input::from_env()?;
+    Ok(format!("{} input(s)", inputs.len()))
+}
+
+fn token() -> Result<String, String> {
+    let res = client_init()
+        .post(API_PING)
+        .json(&ping_body())
+        .send()
+        .map_err(|e| format!("could not reach heimdall: {e}"))?;
+
+    let status = res.status();
+    if status == reqwest::StatusCode::OK {
+        return Ok("accepted by heimdall".into());
+    }
+
+    let body = res.text().unwrap_or_default();
+    Err(format!("{status}: {body} (is the token right and the site online?)"))
+}
+
+fn socket() -> Result<String, String> {
+    let path = sock_path();
+    let dir = std::path::Path::new(&path).parent().ok_or("invalid path")?;
+    if !dir.is_dir() {
+        return Err(format!("{dir:?} does not exist"));
+    }
+
+    let Ok(meta) = std::fs::metadata(&path) else {
+        return Ok(format!("{path} will be created on start"));
+    };
+
+    let mode = meta.permissions().mode() & 0o777;
+    if mode != 0o777 {
+        return Err(format!("{path} has mode {mode:o}, nginx needs 777"));
+    }
+
+    Ok(format!("{path} is writable"))
+}
+
+fn nginx(formats: &[Format]) -> Result<String, String> {
+    let output = std::process::Command::new("nginx")
+        .arg("-T")
+        .output()
+        .map_err(|e| format!("could not run `nginx -T`: {e}"))?;
+    let config = String::from_utf8_lossy(&output.stdout);
+
+    for format in formats {
+        let name = match format {
+            Format::Heimdall => "heimdall",
+            Format::HeimdallFile => "heimdall_file",
+            _ => continue,
+        };
+
+        let found = config.lines().any(|l| {
+            let mut it = l.split_whitespace();
+            it.next() == Some("log_format") && it.next() == Some(name)
+        });
+        if !found {
+            return Err(format!(
+                "log_format {name} was not found, include config/format.conf"
+            ));
+        }
+    }
+
+    Ok("log formats are defined".into())
+}
+
+/// `dog check`: validate the setup of a new site
+pub fn check() -> std::io::Result<()> {
+    if !report("config", config()) {
+        std::process::exit(1);
+    }
+
+    let mut ok = report("token", token());
+
+    let inputs = Input::from_env().unwrap_or_default();
+    if inputs.iter().any(|i| matches!(i.source, Source::Syslog)) {
+        ok &= report("socket", socket());
+    }
+
+    let formats = inputs.iter().map(|i| i.format).collect::<Vec<_>>();
+    if formats
+        .iter()
+        .any(|f| matches!(f, Format::Heimdall | Format::HeimdallFile))
+    {
+        ok &= report("nginx", nginx(&formats));
+    }
+
+    if !ok {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use std::{
+    fs::Permissions,
+    io::{BufRead, BufReader, Read, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    time::Duration,
+};
+
+use crate::counters::COUNTERS;
+use crate::evar;
+
+/// the control socket of the running daemon. configured with
+/// `HEIMDALL_CONTROL`, defaults to a socket next to the syslog one
+pub fn path() -> String {
+    std::env::var("HEIMDALL_CONTROL").unwrap_or_else(|_| {
+        format!(
+            "/usr/share/nginx/socks/heimdall.dog.{}.ctl",
+            evar!("HEIMDALL_SITE")
+        )
+    })
+}
+
+/// answer `status` requests from `dog status`
+pub fn spawn() -> std::io::Result<()> {
+    let path = path();
+    let _ = std::fs::remove_file(&path);
+    let server = UnixListener::bind(&path)?;
+    std::fs::set_permissions(&path, Permissions::from_mode(0o600))?;
+
+    std::thread::spawn(move || {
+        for stream in server.incoming() {
+            let Ok(stream) = stream else { continue };
+            if let Err(e) = handle(stream) {
+                println!("control request error: {e}");
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn handle(stream: UnixStream) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut command = String::new();
+    BufReader::new(&stream).read_line(&mut command)?;
+
+    let response = match command.trim() {
+        "status" => COUNTERS.summary(),
+        cmd => format!("unknown command: {cmd}\n"),
+    };
+
+    (&stream).write_all(response.as_bytes())
+}
+
+/// `dog status`: print the live counters of the running daemon
+pub fn status() -> std::io::Result<()> {
+    let path = path();
+    let mut stream = UnixStream::connect(&path).map_err(|e| {
+        std::io::Error::new(
+            e.kind(),
+            format!("could not connect to {path}: {e}"),
+        )
+    })?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    stream.write_all(b"status\n")?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    print!("{response}");
+    Ok(())
+}
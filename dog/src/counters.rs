@@ -0,0 +1,212 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::dump::Record;
+
+/// upper bounds of the latency histogram buckets, in milliseconds
+pub const BUCKETS: [u64; 11] =
+    [5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+/// live counters of everything dog has seen since it started
+pub struct Counters {
+    pub status: Mutex<BTreeMap<u16, u64>>,
+    /// cumulative counts per bucket, the last one is `+Inf`
+    pub buckets: [AtomicU64; BUCKETS.len() + 1],
+    pub time_sum: AtomicU64,
+    pub malformed: AtomicU64,
+    pub samples: AtomicU64,
+    pub dumps_sent: AtomicU64,
+    pub send_failures: AtomicU64,
+    /// dumps the server refused, they are not sent again
+    pub dumps_rejected: AtomicU64,
+    pub backlog: AtomicU64,
+    /// unix timestamps
+    pub started: AtomicU64,
+    pub latest_sent: AtomicU64,
+}
+
+pub static COUNTERS: Counters = Counters {
+    status: Mutex::new(BTreeMap::new()),
+    buckets: [const { AtomicU64::new(0) }; BUCKETS.len() + 1],
+    time_sum: AtomicU64::new(0),
+    malformed: AtomicU64::new(0),
+    samples: AtomicU64::new(0),
+    dumps_sent: AtomicU64::new(0),
+    send_failures: AtomicU64::new(0),
+    dumps_rejected: AtomicU64::new(0),
+    backlog: AtomicU64::new(0),
+    started: AtomicU64::new(0),
+    latest_sent: AtomicU64::new(0),
+};
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+fn ago(timestamp: u64) -> String {
+    match timestamp {
+        0 => "never".to_string(),
+        t => format!("{}s ago", now().saturating_sub(t)),
+    }
+}
+
+impl Counters {
+    /// count a record that passed the privacy settings `weight` times, like
+    /// it is in the dumps
+    pub fn record(&self, record: &Record, weight: u64) {
+        if let Ok(mut status) = self.status.lock() {
+            *status.entry(record.status).or_default() += weight;
+        }
+
+        for (i, le) in BUCKETS.iter().enumerate() {
+            if record.time <= *le {
+                self.buckets[i].fetch_add(weight, Relaxed);
+            }
+        }
+        self.buckets[BUCKETS.len()].fetch_add(weight, Relaxed);
+        self.time_sum.fetch_add(record.time * weight, Relaxed);
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.buckets[BUCKETS.len()].load(Relaxed)
+    }
+
+    /// the short status line reported to systemd
+    pub fn status(&self) -> String {
+        format!(
+            "last dump {}, backlog {}",
+            ago(self.latest_sent.load(Relaxed)),
+            self.backlog.load(Relaxed)
+        )
+    }
+
+    /// the counters in a human readable form, for `dog status`
+    pub fn summary(&self) -> String {
+        let requests = self.requests();
+        let avg = self.time_sum.load(Relaxed).checked_div(requests);
+        let mut out = String::with_capacity(512);
+
+        let _ = writeln!(out, "started: {}", ago(self.started.load(Relaxed)));
+        let _ = writeln!(out, "requests: {requests}");
+        if let Ok(status) = self.status.lock() {
+            for (code, count) in status.iter() {
+                let _ = writeln!(out, "  {code}: {count}");
+            }
+        }
+        let _ = writeln!(out, "average time: {}ms", avg.unwrap_or_default());
+        let _ =
+            writeln!(out, "malformed lines: {}", self.malformed.load(Relaxed));
+        let _ = writeln!(out, "statsd samples: {}", self.samples.load(Relaxed));
+        let _ = writeln!(out, "dumps sent: {}", self.dumps_sent.load(Relaxed));
+        let _ = writeln!(
+            out,
+            "send failures: {}",
+            self.send_failures.load(Relaxed)
+        );
+        let _ = writeln!(
+            out,
+            "dumps rejected: {}",
+            self.dumps_rejected.load(Relaxed)
+        );
+        let _ = writeln!(out, "backlog: {}", self.backlog.load(Relaxed));
+        let _ =
+            writeln!(out, "last dump: {}", ago(self.latest_sent.load(Relaxed)));
+
+        out
+    }
+
+    /// the counters in the prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::with_capacity(2048);
+
+        out.push_str(
+            "# HELP dog_requests_total requests seen, by status code\n\
+            # TYPE dog_requests_total counter\n",
+        );
+        if let Ok(status) = self.status.lock() {
+            for (code, count) in status.iter() {
+                let _ = writeln!(
+                    out,
+                    "dog_requests_total{{status=\"{code}\"}} {count}"
+                );
+            }
+        }
+
+        out.push_str(
+            "# HELP dog_request_duration_seconds upstream response time\n\
+            # TYPE dog_request_duration_seconds histogram\n",
+        );
+        for (i, le) in BUCKETS.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "dog_request_duration_seconds_bucket{{le=\"{}\"}} {}",
+                *le as f64 / 1000.0,
+                self.buckets[i].load(Relaxed)
+            );
+        }
+        let _ = writeln!(
+            out,
+            "dog_request_duration_seconds_bucket{{le=\"+Inf\"}} {0}\n\
+            dog_request_duration_seconds_count {0}\n\
+            dog_request_duration_seconds_sum {1}",
+            self.requests(),
+            self.time_sum.load(Relaxed) as f64 / 1000.0
+        );
+
+        let simple = [
+            (
+                "malformed_lines_total",
+                "counter",
+                "unparsable log lines",
+                &self.malformed,
+            ),
+            (
+                "statsd_samples_total",
+                "counter",
+                "statsd samples received",
+                &self.samples,
+            ),
+            (
+                "dumps_sent_total",
+                "counter",
+                "dumps delivered to heimdall",
+                &self.dumps_sent,
+            ),
+            (
+                "send_failures_total",
+                "counter",
+                "failed dump requests",
+                &self.send_failures,
+            ),
+            (
+                "dumps_rejected_total",
+                "counter",
+                "dumps refused by heimdall and dropped",
+                &self.dumps_rejected,
+            ),
+            (
+                "spool_backlog",
+                "gauge",
+                "dumps waiting to be sent",
+                &self.backlog,
+            ),
+        ];
+        for (name, kind, help, value) in simple {
+            let _ = writeln!(
+                out,
+                "# HELP dog_{name} {help}\n# TYPE dog_{name} {kind}\n\
+                dog_{name} {}",
+                value.load(Relaxed)
+            );
+        }
+
+        out
+    }
+}
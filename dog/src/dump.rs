@@ -0,0 +1,229 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::counters::{now, BUCKETS};
+use crate::statsd::{Metric, MetricKind, Sample};
+
+/// a single request, as parsed from any of the supported log formats
+#[derive(Debug, Default)]
+pub struct Record {
+    pub status: u16,
+    /// response time in milliseconds
+    pub time: u64,
+    /// unix timestamp of the request, 0 when the input does not carry one
+    pub timestamp: i64,
+    /// client address, anonymized by `Privacy` before aggregation
+    pub ip: Option<String>,
+    /// request uri with the query string, when the format carries it
+    pub path: Option<String>,
+}
+
+/// what the inputs send to the aggregating loop
+#[derive(Debug)]
+pub enum Event {
+    Record(Record),
+    Metric(Sample),
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct Status {
+    code: u16,
+    count: u64,
+    max_time: u64,
+    min_time: u64,
+    total_time: u64,
+}
+
+/// requests of a route, see `route`
+#[derive(Serialize, Default, Debug)]
+pub struct Route {
+    total: u64,
+    total_time: u64,
+    max_time: u64,
+    /// 5xx responses
+    errors: u64,
+    latency: Vec<u64>,
+}
+
+/// most routes kept in a window, the others are counted under `OTHER`
+const ROUTES_MAX: usize = 200;
+const OTHER: &str = "other";
+
+/// the path of a request without its query string, with the segments that
+/// look like ids (numbers, hex and uuids, long tokens) replaced by `:id` so
+/// the routes stay few
+fn route(path: &str) -> String {
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    path.split('/')
+        .map(|segment| {
+            let id = segment.len() > 32
+                || (!segment.is_empty()
+                    && segment.chars().all(|c| c.is_ascii_digit()))
+                || (segment.len() >= 16
+                    && segment.chars().any(|c| c.is_ascii_digit())
+                    && segment
+                        .chars()
+                        .all(|c| c.is_ascii_hexdigit() || c == '-'));
+            if id {
+                ":id"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct Dump {
+    pub total: u64,
+    total_time: u64,
+    max_time: u64,
+    min_time: u64,
+    status: HashMap<String, Status>,
+    /// request count per latency bucket of `BUCKETS`, the last one is
+    /// everything slower
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    latency: Vec<u64>,
+    /// the dog that sent the dump, set by the sender when it is spooled
+    pub instance: String,
+    /// increases with every dump of an instance, so the server can drop
+    /// retried ones. set by the sender when it is spooled
+    pub seq: u64,
+    /// unix timestamps of the window this dump covers
+    pub start: i64,
+    pub end: i64,
+    /// requests per route, for the formats that carry the path
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    routes: HashMap<String, Route>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    metrics: HashMap<String, Metric>,
+    /// latest gauge values, kept across windows like statsd does
+    #[serde(skip)]
+    gauges: HashMap<String, f64>,
+    /// distinct client addresses of the window, as `Privacy` left them
+    visitors: u64,
+    #[serde(skip)]
+    addresses: HashSet<String>,
+}
+
+impl Dump {
+    pub fn new(start: i64) -> Self {
+        Self { start, ..Default::default() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total == 0 && self.metrics.is_empty()
+    }
+
+    /// close the current window and take it out, leaving an empty one
+    /// starting now behind
+    pub fn take(&mut self) -> Self {
+        let now = now() as i64;
+        self.end = now;
+
+        for (name, metric) in self.metrics.iter() {
+            if metric.kind() == MetricKind::Gauge {
+                self.gauges.insert(name.clone(), metric.value());
+            }
+        }
+
+        let gauges = std::mem::take(&mut self.gauges);
+        std::mem::replace(self, Self { gauges, ..Self::new(now) })
+    }
+
+    pub fn add_sample(&mut self, sample: &Sample) {
+        // a name reused with another type starts over
+        if self
+            .metrics
+            .get(&sample.name)
+            .is_some_and(|m| m.kind() != sample.kind)
+        {
+            self.metrics.remove(&sample.name);
+        }
+
+        let gauges = &self.gauges;
+        let metric =
+            self.metrics.entry(sample.name.clone()).or_insert_with(|| {
+                let mut metric = Metric::new(sample.kind);
+                if sample.kind == MetricKind::Gauge {
+                    if let Some(v) = gauges.get(&sample.name) {
+                        metric.set_value(*v);
+                    }
+                }
+                metric
+            });
+
+        metric.add(sample);
+    }
+
+    /// add a record counted `weight` times, more than once when sampling
+    pub fn add(&mut self, record: &Record, weight: u64) {
+        let time = record.time;
+        self.total += weight;
+        self.total_time += time * weight;
+        if self.max_time < time {
+            self.max_time = time;
+        }
+
+        if self.min_time > time || self.min_time == 0 {
+            self.min_time = time;
+        }
+
+        self.latency.resize(BUCKETS.len() + 1, 0);
+        let bucket = BUCKETS.iter().position(|le| time <= *le);
+        let bucket = bucket.unwrap_or(BUCKETS.len());
+        self.latency[bucket] += weight;
+
+        if let Some(ip) = record.ip.as_ref() {
+            if !self.addresses.contains(ip) {
+                self.addresses.insert(ip.clone());
+                self.visitors += 1;
+            }
+        }
+
+        if let Some(path) = record.path.as_deref() {
+            let mut name = route(path);
+            if self.routes.len() >= ROUTES_MAX
+                && !self.routes.contains_key(&name)
+            {
+                name = OTHER.to_string();
+            }
+
+            let route = self.routes.entry(name).or_default();
+            route.total += weight;
+            route.total_time += time * weight;
+            route.max_time = route.max_time.max(time);
+            if record.status >= 500 {
+                route.errors += weight;
+            }
+            route.latency.resize(BUCKETS.len() + 1, 0);
+            route.latency[bucket] += weight;
+        }
+
+        let sk = record.status.to_string();
+        if let Some(status) = self.status.get_mut(&sk) {
+            status.count += weight;
+            status.total_time += time * weight;
+
+            if status.min_time > time || status.min_time == 0 {
+                status.min_time = time;
+            }
+            if status.max_time < time {
+                status.max_time = time;
+            }
+        } else {
+            self.status.insert(
+                sk,
+                Status {
+                    code: record.status,
+                    total_time: time * weight,
+                    min_time: time,
+                    max_time: time,
+                    count: weight,
+                },
+            );
+        }
+    }
+}
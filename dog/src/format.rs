@@ -0,0 +1,439 @@
+use serde::Deserialize;
+
+use crate::dump::Record;
+
+/// the access log formats dog knows how to parse.
+/// selected per input with an `@format` suffix, e.g. `file=/x.log@caddy`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// nginx `heimdall` log format: `[$status,$upstream_response_time]`,
+    /// optionally followed by `,"$remote_addr","$request_uri"`
+    Heimdall,
+    /// nginx `heimdall_file` log format: `$msec [...]`, the rest is the same
+    /// as `heimdall`
+    HeimdallFile,
+    /// caddy json access logs
+    Caddy,
+    /// apache combined log format with `%D` appended
+    Apache,
+    /// haproxy `option httplog` format
+    Haproxy,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "heimdall" => Ok(Self::Heimdall),
+            "heimdall_file" => Ok(Self::HeimdallFile),
+            "caddy" => Ok(Self::Caddy),
+            "apache" => Ok(Self::Apache),
+            "haproxy" => Ok(Self::Haproxy),
+            _ => Err(format!("unknown log format: {s}")),
+        }
+    }
+}
+
+impl Format {
+    pub fn parse(&self, line: &[u8]) -> Option<Record> {
+        let line = line.trim_ascii();
+        match self {
+            Self::Heimdall => heimdall(line),
+            Self::HeimdallFile => heimdall_file(line),
+            Self::Caddy => caddy(line),
+            Self::Apache => apache(line),
+            Self::Haproxy => haproxy(line),
+        }
+    }
+}
+
+fn heimdall(line: &[u8]) -> Option<Record> {
+    if line.len() < 3 || line[0] != b'[' || line[line.len() - 1] != b']' {
+        return None;
+    }
+
+    let line = &line[1..line.len() - 1];
+    let (status, rest) = match line.iter().position(|v| *v == b',') {
+        Some(i) => (&line[..i], &line[i + 1..]),
+        None => (line, &line[line.len()..]),
+    };
+    let status = std::str::from_utf8(status).ok()?.parse().ok()?;
+
+    // the upstream time is not quoted and may hold several comma separated
+    // values, the optional fields after it are json strings
+    let (time, extra) = match rest.windows(2).position(|v| v == b",\"") {
+        Some(i) => (&rest[..i], &rest[i + 1..]),
+        None => (rest, &rest[rest.len()..]),
+    };
+    let time = std::str::from_utf8(time)
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .unwrap_or_default();
+
+    let mut extra = match extra.is_empty() {
+        true => Vec::new(),
+        false => {
+            serde_json::from_slice::<Vec<String>>(&[b"[", extra, b"]"].concat())
+                .ok()?
+        }
+    }
+    .into_iter()
+    .filter(|v| !v.is_empty() && v != "-");
+
+    Some(Record {
+        status,
+        time: (time * 1000.0) as u64,
+        ip: extra.next(),
+        path: extra.next(),
+        ..Default::default()
+    })
+}
+
+fn heimdall_file(line: &[u8]) -> Option<Record> {
+    let mut it = line.splitn(2, |v| *v == b' ');
+    let msec = std::str::from_utf8(it.next()?).ok()?.parse::<f64>().ok()?;
+    let mut record = heimdall(it.next()?)?;
+    record.timestamp = msec as i64;
+    Some(record)
+}
+
+fn caddy(line: &[u8]) -> Option<Record> {
+    #[derive(Deserialize, Default)]
+    struct Request {
+        #[serde(default)]
+        remote_ip: Option<String>,
+        #[serde(default)]
+        client_ip: Option<String>,
+        #[serde(default)]
+        uri: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct Log {
+        #[serde(default)]
+        ts: f64,
+        #[serde(default)]
+        request: Request,
+        status: u16,
+        /// seconds, unless caddy was configured with `duration_format`
+        #[serde(default)]
+        duration: f64,
+    }
+
+    let log = serde_json::from_slice::<Log>(line).ok()?;
+    Some(Record {
+        status: log.status,
+        time: (log.duration * 1000.0) as u64,
+        timestamp: log.ts as i64,
+        ip: log.request.client_ip.or(log.request.remote_ip),
+        path: log.request.uri,
+    })
+}
+
+/// `%h %l %u %t \"%r\" %>s %b \"%{Referer}i\" \"%{User-agent}i\" %D`
+fn apache(line: &[u8]) -> Option<Record> {
+    let ip = line.split(|v| *v == b' ').next().and_then(text);
+    let (timestamp, rest) = clf_date(line)?;
+
+    // the quoted request line may contain escaped quotes
+    let rest = rest.trim_ascii_start().strip_prefix(b"\"")?;
+    let mut i = 0;
+    while i < rest.len() && rest[i] != b'"' {
+        i += if rest[i] == b'\\' { 2 } else { 1 };
+    }
+    let path = request_path(rest.get(..i)?);
+    let rest = rest.get(i + 1..)?;
+
+    let mut fields = rest.split(|v| *v == b' ').filter(|v| !v.is_empty());
+    let status = std::str::from_utf8(fields.next()?).ok()?.parse().ok()?;
+
+    // %D is the request duration in microseconds, after the user agent
+    let time = rest
+        .rsplit(|v| *v == b' ')
+        .next()
+        .filter(|_| !rest.ends_with(b"\"") && rest.contains(&b'"'))
+        .and_then(|v| std::str::from_utf8(v).ok())
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(|v| v / 1000)
+        .unwrap_or_default();
+
+    Some(Record { status, time, timestamp, ip, path })
+}
+
+/// `... [06/Feb/2009:12:14:14.655] front back/srv Tq/Tw/Tc/Tr/Ta status ..`
+fn haproxy(line: &[u8]) -> Option<Record> {
+    let (timestamp, rest) = clf_date(line)?;
+
+    // `ip:port` is the last field before the accept date
+    let head = &line[..line.len() - rest.len()];
+    let head = &head[..head.iter().rposition(|v| *v == b'[')?];
+    let ip = head
+        .split(|v| *v == b' ')
+        .rfind(|v| !v.is_empty())
+        .and_then(|v| text(&v[..v.iter().rposition(|c| *c == b':')?]))
+        .map(|v| v.trim_matches(['[', ']']).to_string());
+
+    // the request line is the last quoted field
+    let path = rest.iter().rposition(|v| *v == b'"').and_then(|end| {
+        let start = rest[..end].iter().rposition(|v| *v == b'"')?;
+        request_path(&rest[start + 1..end])
+    });
+
+    let mut fields = rest.split(|v| *v == b' ').filter(|v| !v.is_empty());
+    let timers = std::str::from_utf8(fields.nth(2)?).ok()?;
+    let status = std::str::from_utf8(fields.next()?).ok()?.parse().ok()?;
+
+    // the last timer is the total active time, -1 when the request aborted
+    let time = timers
+        .rsplit('/')
+        .next()
+        .and_then(|v| v.trim_start_matches('+').parse::<i64>().ok())
+        .filter(|v| *v >= 0)
+        .unwrap_or_default() as u64;
+
+    Some(Record { status, time, timestamp, ip, path })
+}
+
+fn text(v: &[u8]) -> Option<String> {
+    std::str::from_utf8(v)
+        .ok()
+        .filter(|v| !v.is_empty() && *v != "-")
+        .map(Into::into)
+}
+
+/// the uri out of a `METHOD /uri HTTP/x` request line
+fn request_path(line: &[u8]) -> Option<String> {
+    line.split(|v| *v == b' ').nth(1).and_then(text)
+}
+
+/// find the first `[dd/Mon/yyyy:hh:mm:ss[.ms][ +zzzz]]` in the line and
+/// return it as a unix timestamp along with the rest of the line.
+/// dates without a zone are taken as utc
+fn clf_date(line: &[u8]) -> Option<(i64, &[u8])> {
+    const MONTHS: [&[u8]; 12] = [
+        b"Jan", b"Feb", b"Mar", b"Apr", b"May", b"Jun", b"Jul", b"Aug", b"Sep",
+        b"Oct", b"Nov", b"Dec",
+    ];
+
+    let num = |v: &[u8]| -> Option<i64> {
+        if v.is_empty() || !v.iter().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        std::str::from_utf8(v).ok()?.parse().ok()
+    };
+
+    let mut offset = 0;
+    while let Some(start) = line[offset..].iter().position(|v| *v == b'[') {
+        let start = offset + start + 1;
+        offset = start;
+        let end = line[start..].iter().position(|v| *v == b']')?;
+        let date = &line[start..start + end];
+        let rest = &line[start + end + 1..];
+
+        // 06/Feb/2009:12:14:14.655 or 10/Oct/2000:13:55:36 -0700
+        let (date, zone) = match date.iter().position(|v| *v == b' ') {
+            Some(i) => (&date[..i], Some(&date[i + 1..])),
+            None => (date, None),
+        };
+        if date.len() < 20 || date[2] != b'/' || date[6] != b'/' {
+            continue;
+        }
+
+        let Some(month) = MONTHS.iter().position(|m| *m == &date[3..6]) else {
+            continue;
+        };
+        let (Some(day), Some(year), Some(h), Some(m), Some(s)) = (
+            num(&date[0..2]),
+            num(&date[7..11]),
+            num(&date[12..14]),
+            num(&date[15..17]),
+            num(&date[18..20]),
+        ) else {
+            continue;
+        };
+
+        let zone = match zone {
+            Some(z) if z.len() == 5 => {
+                let sign = if z[0] == b'-' { -1 } else { 1 };
+                let Some(hh) = num(&z[1..3]) else { continue };
+                let Some(mm) = num(&z[3..5]) else { continue };
+                sign * (hh * 3600 + mm * 60)
+            }
+            _ => 0,
+        };
+
+        let days = days_from_civil(year, month as i64 + 1, day);
+        return Some((days * 86400 + h * 3600 + m * 60 + s - zone, rest));
+    }
+
+    None
+}
+
+/// days since 1970-01-01 for a proleptic gregorian date
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = (if y >= 0 { y } else { y - 399 }) / 400;
+    let yoe = y - era * 400;
+    let mp = (m + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const APACHE: &str = "203.0.113.9 - frank [10/Oct/2000:13:55:36 -0700] \
+        \"GET /apache_pb.gif?x=1 HTTP/1.0\" 200 2326 \
+        \"http://example.com/start.html\" \"Mozilla/4.08 [en] (Win98)\" 15300";
+    const HAPROXY: &str = "Feb  6 12:14:14 localhost haproxy[14389]: \
+        10.0.1.2:33317 [06/Feb/2009:12:14:14.655] http-in static/srv1 \
+        10/0/30/69/109 200 2750 - - ---- 1/1/1/1/0 0/0 {1wt.eu} {} \
+        \"GET /index.html HTTP/1.1\"";
+    const CADDY: &str = concat!(
+        r#"{"level":"info","ts":1646861401.5241024,"msg":"handled request","#,
+        r#""request":{"remote_ip":"127.0.0.1","client_ip":"203.0.113.5","#,
+        r#""method":"GET","uri":"/users/1?x=2","headers":{}},"#,
+        r#""duration":0.0150629,"size":12,"status":200}"#
+    );
+
+    fn parse(format: Format, line: &str) -> Record {
+        format.parse(line.as_bytes()).unwrap_or_else(|| panic!("{line}"))
+    }
+
+    fn check(r: &Record, want: (u16, u64, i64, Option<&str>, Option<&str>)) {
+        let got = (r.status, r.time, r.timestamp, r.ip.as_deref());
+        assert_eq!(got, (want.0, want.1, want.2, want.3));
+        assert_eq!(r.path.as_deref(), want.4);
+    }
+
+    #[test]
+    fn heimdall() {
+        let r = parse(Format::Heimdall, r#"[502,0.250,"10.0.0.1","/a/?b=c"]"#);
+        check(&r, (502, 250, 0, Some("10.0.0.1"), Some("/a/?b=c")));
+        let r = parse(Format::Heimdall, "[200,-]");
+        check(&r, (200, 0, 0, None, None));
+        let r =
+            parse(Format::HeimdallFile, r#"1700000000.123 [200,0.012,"-"]"#);
+        check(&r, (200, 12, 1700000000, None, None));
+    }
+
+    #[test]
+    fn apache() {
+        // %D is in microseconds
+        let r = parse(Format::Apache, APACHE);
+        let path = Some("/apache_pb.gif?x=1");
+        check(&r, (200, 15, 971211336, Some("203.0.113.9"), path));
+
+        // the plain combined format has no duration
+        let line = APACHE.trim_end_matches(" 15300");
+        check(
+            &parse(Format::Apache, line),
+            (200, 0, 971211336, r.ip.as_deref(), path),
+        );
+
+        let line = "::1 - - [01/Jan/2024:03:30:00 +0330] \
+            \"GET /search?q=\\\"ab\\\" HTTP/1.1\" 404 0 \"-\" \"curl\" 2000";
+        let r = parse(Format::Apache, line);
+        let path = Some("/search?q=\\\"ab\\\"");
+        check(&r, (404, 2, 1704067200, Some("::1"), path));
+    }
+
+    #[test]
+    fn haproxy() {
+        let r = parse(Format::Haproxy, HAPROXY);
+        let path = Some("/index.html");
+        check(&r, (200, 109, 1233922454, Some("10.0.1.2"), path));
+
+        // -1 timers are aborted requests, there is no total time
+        let line = HAPROXY.replace("10/0/30/69/109 200", "10/0/-1/-1/-1 503");
+        check(
+            &parse(Format::Haproxy, &line),
+            (503, 0, 1233922454, Some("10.0.1.2"), path),
+        );
+
+        let line = HAPROXY.replace("10.0.1.2:33317", "[2001:db8::1]:4444");
+        let r = parse(Format::Haproxy, &line);
+        assert_eq!(r.ip.as_deref(), Some("2001:db8::1"));
+    }
+
+    #[test]
+    fn caddy() {
+        let r = parse(Format::Caddy, CADDY);
+        check(
+            &r,
+            (200, 15, 1646861401, Some("203.0.113.5"), Some("/users/1?x=2")),
+        );
+
+        let line = r#"{"status":500,"duration":1.5,
+            "request":{"remote_ip":"10.0.0.7"}}"#;
+        check(
+            &parse(Format::Caddy, line),
+            (500, 1500, 0, Some("10.0.0.7"), None),
+        );
+    }
+
+    #[test]
+    fn dates() {
+        let date = |v: &str| clf_date(v.as_bytes()).map(|(t, _)| t);
+        assert_eq!(date("[10/Oct/2000:13:55:36 -0700]"), Some(971211336));
+        assert_eq!(date("[01/Jan/2024:03:30:00 +0330]"), Some(1704067200));
+        assert_eq!(date("[29/Feb/2024:23:59:59]"), Some(1709251199));
+        assert_eq!(
+            date("x [nope] [06/Feb/2009:12:14:14.655]"),
+            Some(1233922454)
+        );
+        assert_eq!(date("[06/Foo/2009:12:14:14]"), None);
+        assert_eq!(date("[06/Feb/2009:12:14]"), None);
+        assert_eq!(date("[06/Feb/2009:12:14:14"), None);
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+    }
+
+    #[test]
+    fn garbage() {
+        let formats = [
+            Format::Heimdall,
+            Format::HeimdallFile,
+            Format::Caddy,
+            Format::Apache,
+            Format::Haproxy,
+        ];
+        for format in formats {
+            for line in
+                ["", " ", "-", "[", "[]", "[abc]", "\"\"", "{}", "\u{fffd}[/]"]
+            {
+                assert!(
+                    format.parse(line.as_bytes()).is_none(),
+                    "{format:?} {line:?}"
+                );
+            }
+        }
+
+        // every cut of a good line must fail or parse, never panic
+        let lines = [
+            (Format::Heimdall, r#"[200,0.012,"10.0.0.1","/a/"]"#),
+            (
+                Format::HeimdallFile,
+                r#"1700000000.123 [200,0.012,"10.0.0.1","/a/"]"#,
+            ),
+            (Format::Caddy, CADDY),
+            (Format::Apache, APACHE),
+            (Format::Haproxy, HAPROXY),
+        ];
+        for (format, line) in lines {
+            for end in 0..line.len() {
+                format.parse(&line.as_bytes()[..end]);
+                format.parse(&line.as_bytes()[end..]);
+            }
+        }
+
+        let cut = &APACHE[..APACHE.find("\" 200").unwrap()];
+        assert!(Format::Apache.parse(cut.as_bytes()).is_none());
+        let cut = &HAPROXY[..HAPROXY.find(" 10/0").unwrap()];
+        assert!(Format::Haproxy.parse(cut.as_bytes()).is_none());
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Seek, SeekFrom},
+    os::unix::fs::MetadataExt,
+    path::PathBuf,
+    sync::{atomic::Ordering::Relaxed, mpsc},
+    time::Duration,
+};
+
+use crate::counters::COUNTERS;
+use crate::dump::Event;
+use crate::format::Format;
+
+/// follow an access log file like `tail -F`.
+/// when the file is rotated (a new inode shows up at the path) or
+/// truncated, it is read again from the start
+pub fn tail(path: PathBuf, format: Format, tx: mpsc::Sender<Event>) {
+    let mut from_start = false;
+
+    loop {
+        let file = match File::open(&path) {
+            Ok(f) => f,
+            Err(e) => {
+                println!("could not open {path:?}: {e}");
+                std::thread::sleep(Duration::from_secs(5));
+                from_start = true;
+                continue;
+            }
+        };
+
+        let ino = match file.metadata() {
+            Ok(m) => m.ino(),
+            Err(e) => {
+                println!("could not stat {path:?}: {e}");
+                std::thread::sleep(Duration::from_secs(5));
+                continue;
+            }
+        };
+        let mut reader = BufReader::new(file);
+        let mut pos = if from_start {
+            0
+        } else {
+            reader.seek(SeekFrom::End(0)).unwrap_or_default()
+        };
+        let mut line = Vec::with_capacity(512);
+        let mut rotated = false;
+
+        loop {
+            let size = match reader.read_until(b'\n', &mut line) {
+                Ok(s) => s,
+                Err(e) => {
+                    println!("could not read {path:?}: {e}");
+                    std::thread::sleep(Duration::from_secs(1));
+                    continue;
+                }
+            };
+            pos += size as u64;
+
+            if size != 0 {
+                // a partial line, wait for the rest of it
+                if line.last() != Some(&b'\n') {
+                    continue;
+                }
+
+                let record = format.parse(&line);
+                line.clear();
+                let Some(record) = record else {
+                    COUNTERS.malformed.fetch_add(1, Relaxed);
+                    continue;
+                };
+                if tx.send(Event::Record(record)).is_err() {
+                    return;
+                }
+                continue;
+            }
+
+            // the lines written to the old file before the writer moved on
+            // are read to its end before the new one is opened
+            if rotated {
+                break;
+            }
+
+            std::thread::sleep(Duration::from_millis(250));
+            match std::fs::metadata(&path) {
+                Ok(m) if m.ino() != ino => rotated = true,
+                Ok(m) if m.len() < pos => {
+                    pos = reader.seek(SeekFrom::Start(0)).unwrap_or_default();
+                    line.clear();
+                }
+                _ => {}
+            }
+        }
+
+        from_start = true;
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    net::{TcpListener, UdpSocket},
+    path::PathBuf,
+    sync::{mpsc, Arc},
+};
+
+use crate::dump::Event;
+use crate::format::Format;
+
+mod file;
+mod syslog;
+
+pub use syslog::{sock_path, Allow};
+
+#[derive(Debug, Clone)]
+pub enum Source {
+    Syslog,
+    Udp(String),
+    Tcp(String),
+    File(PathBuf),
+}
+
+/// where dog reads the access log lines from and how they are parsed.
+/// configured with `HEIMDALL_INPUTS`, a comma separated list of
+/// `syslog`, `udp=<addr>`, `tcp=<addr>` and `file=/path/to/access.log`,
+/// each optionally followed by `@<format>`. defaults to `syslog`
+#[derive(Debug, Clone)]
+pub struct Input {
+    pub source: Source,
+    pub format: Format,
+}
+
+impl Input {
+    pub fn from_env() -> Result<Vec<Self>, String> {
+        let value = std::env::var("HEIMDALL_INPUTS")
+            .unwrap_or_else(|_| "syslog".to_string());
+
+        value
+            .split(',')
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .map(|v| {
+                let (v, format) = match v.rsplit_once('@') {
+                    Some((v, f)) => (v, Some(f.parse::<Format>()?)),
+                    None => (v, None),
+                };
+
+                let mut it = v.splitn(2, '=');
+                let source = match (it.next(), it.next()) {
+                    (Some("syslog"), None) => Source::Syslog,
+                    (Some("udp"), Some(addr)) => Source::Udp(addr.to_string()),
+                    (Some("tcp"), Some(addr)) => Source::Tcp(addr.to_string()),
+                    (Some("file"), Some(path)) => Source::File(path.into()),
+                    _ => return Err(format!("invalid input: {v}")),
+                };
+
+                let format = match (format, &source) {
+                    (Some(f), _) => f,
+                    (None, Source::File(_)) => Format::HeimdallFile,
+                    (None, _) => Format::Heimdall,
+                };
+
+                Ok(Input { source, format })
+            })
+            .collect()
+    }
+
+    pub fn spawn(
+        self, allow: Arc<Allow>, tx: mpsc::Sender<Event>,
+    ) -> std::io::Result<()> {
+        let format = self.format;
+        match self.source {
+            Source::Syslog => {
+                let server = syslog::unix_bind()?;
+                std::thread::spawn(move || syslog::unix(server, format, tx));
+            }
+            Source::Udp(addr) => {
+                let server = UdpSocket::bind(addr)?;
+                std::thread::spawn(move || {
+                    syslog::udp(server, format, allow, tx)
+                });
+            }
+            Source::Tcp(addr) => {
+                let server = TcpListener::bind(addr)?;
+                std::thread::spawn(move || {
+                    syslog::tcp(server, format, allow, tx)
+                });
+            }
+            Source::File(path) => {
+                std::thread::spawn(move || file::tail(path, format, tx));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,369 @@
+use std::{
+    fs::Permissions,
+    io::{BufRead, BufReader, Read},
+    net::{IpAddr, SocketAddr, TcpListener, TcpStream, UdpSocket},
+    os::unix::{fs::PermissionsExt, net::UnixDatagram},
+    sync::{
+        atomic::{AtomicUsize, Ordering::Relaxed},
+        mpsc, Arc,
+    },
+    time::Duration,
+};
+
+use crate::counters::COUNTERS;
+use crate::dump::Event;
+use crate::evar;
+use crate::format::Format;
+
+/// frames bigger than this are dropped
+const MAX_FRAME: usize = 8 * 1024;
+/// tcp connections past this are closed right away
+const MAX_CONNECTIONS: usize = 64;
+/// tcp connections that send nothing for this long are closed, so idle
+/// peers do not hold on to the connection slots
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+static CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// strip the rfc 3164 header nginx puts before the log line:
+/// `<190>Oct 19 12:00:00 [hostname ]tag: [...]`. lines without the `<pri>`
+/// have no header and are left as they are
+pub fn payload(buf: &[u8]) -> &[u8] {
+    let buf = buf.trim_ascii_end();
+    let pri = buf
+        .strip_prefix(b"<")
+        .and_then(|v| v.iter().position(|v| *v == b'>'))
+        .filter(|i| (1..=3).contains(i))
+        .filter(|i| buf[1..=*i].iter().all(u8::is_ascii_digit));
+    let Some(i) = pri else { return buf };
+    let buf = &buf[i + 2..];
+
+    // the timestamp is always 16 bytes: `Mmm dd hh:mm:ss `
+    let Some(rest) = buf.get(16..) else { return buf };
+    match rest.windows(2).position(|v| v == b": ") {
+        Some(i) => &rest[i + 2..],
+        None => rest,
+    }
+}
+
+/// source addresses that are allowed to send logs over the network.
+/// configured with `HEIMDALL_ALLOW`, a comma separated list of ips or
+/// cidr ranges (e.g. `10.0.0.0/24,192.168.1.7`), only loopback when unset
+#[derive(Debug, Default)]
+pub struct Allow(Vec<(IpAddr, u8)>);
+
+impl Allow {
+    pub fn from_env() -> Result<Self, String> {
+        let Ok(value) = std::env::var("HEIMDALL_ALLOW") else {
+            return Ok(Self::default());
+        };
+
+        let mut allow = Self::default();
+        for v in value.split(',').map(|v| v.trim()).filter(|v| !v.is_empty()) {
+            let mut it = v.splitn(2, '/');
+            let ip: IpAddr = it
+                .next()
+                .and_then(|v| v.parse().ok())
+                .ok_or_else(|| format!("invalid ip in allow: {v}"))?;
+            let max = if ip.is_ipv4() { 32 } else { 128 };
+            let prefix = match it.next() {
+                Some(p) => p
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|p| *p <= max)
+                    .ok_or_else(|| format!("invalid cidr in allow: {v}"))?,
+                None => max,
+            };
+            allow.0.push((ip, prefix));
+        }
+
+        Ok(allow)
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v) => v.to_canonical(),
+            v => v,
+        };
+
+        if ip.is_loopback() {
+            return true;
+        }
+
+        self.0.iter().any(|(net, prefix)| match (net, ip) {
+            (IpAddr::V4(n), IpAddr::V4(a)) => {
+                let mask = u32::MAX.checked_shl(32 - *prefix as u32);
+                let mask = mask.unwrap_or(0);
+                u32::from(*n) & mask == u32::from(a) & mask
+            }
+            (IpAddr::V6(n), IpAddr::V6(a)) => {
+                let mask = u128::MAX.checked_shl(128 - *prefix as u32);
+                let mask = mask.unwrap_or(0);
+                u128::from(*n) & mask == u128::from(a) & mask
+            }
+            _ => false,
+        })
+    }
+}
+
+/// the unix socket nginx sends its logs to
+pub fn sock_path() -> String {
+    // "/tmp/heimdall.dog.sock"
+    format!(
+        "/usr/share/nginx/socks/heimdall.dog.{}.sock",
+        evar!("HEIMDALL_SITE")
+    )
+}
+
+pub fn unix_bind() -> std::io::Result<UnixDatagram> {
+    let path = sock_path();
+    let _ = std::fs::remove_file(&path);
+    let server = UnixDatagram::bind(&path)?;
+    std::fs::set_permissions(&path, Permissions::from_mode(0o777))?;
+    Ok(server)
+}
+
+pub fn unix(server: UnixDatagram, format: Format, tx: mpsc::Sender<Event>) {
+    let mut buf = vec![0u8; MAX_FRAME];
+    loop {
+        let size = match server.recv(buf.as_mut_slice()) {
+            Ok(s) => s,
+            Err(e) => {
+                println!("server recv error: {e}");
+                continue;
+            }
+        };
+
+        let Some(record) = format.parse(payload(&buf[..size])) else {
+            COUNTERS.malformed.fetch_add(1, Relaxed);
+            continue;
+        };
+
+        if tx.send(Event::Record(record)).is_err() {
+            return;
+        }
+    }
+}
+
+pub fn udp(
+    server: UdpSocket, format: Format, allow: Arc<Allow>,
+    tx: mpsc::Sender<Event>,
+) {
+    let mut buf = vec![0u8; MAX_FRAME];
+    loop {
+        let (size, addr) = match server.recv_from(buf.as_mut_slice()) {
+            Ok(s) => s,
+            Err(e) => {
+                println!("udp recv error: {e}");
+                continue;
+            }
+        };
+
+        if !allow.contains(addr.ip()) {
+            continue;
+        }
+
+        let Some(record) = format.parse(payload(&buf[..size])) else {
+            COUNTERS.malformed.fetch_add(1, Relaxed);
+            continue;
+        };
+
+        if tx.send(Event::Record(record)).is_err() {
+            return;
+        }
+    }
+}
+
+pub fn tcp(
+    server: TcpListener, format: Format, allow: Arc<Allow>,
+    tx: mpsc::Sender<Event>,
+) {
+    for stream in server.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                println!("tcp accept error: {e}");
+                continue;
+            }
+        };
+
+        let Ok(addr) = stream.peer_addr() else { continue };
+        if !allow.contains(addr.ip()) {
+            println!("tcp connection from {addr} is not allowed");
+            continue;
+        }
+
+        if CONNECTIONS.fetch_add(1, Relaxed) >= MAX_CONNECTIONS {
+            CONNECTIONS.fetch_sub(1, Relaxed);
+            println!("tcp connection from {addr} is over the limit");
+            continue;
+        }
+
+        let tx = tx.clone();
+        std::thread::spawn(move || {
+            tcp_stream(stream, addr, format, tx);
+            CONNECTIONS.fetch_sub(1, Relaxed);
+        });
+    }
+}
+
+/// rfc 6587 octet counting frames start with `<len> `, anything else is
+/// read up to the newline
+fn octet_counted(buf: &[u8]) -> bool {
+    let digits = buf.iter().take_while(|v| v.is_ascii_digit()).count();
+    digits > 0 && buf.get(digits) == Some(&b' ')
+}
+
+/// throw away the rest of a line that was too long to be a frame
+fn skip_line(reader: &mut impl BufRead) -> std::io::Result<()> {
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(());
+        }
+        match buf.iter().position(|v| *v == b'\n') {
+            Some(i) => {
+                reader.consume(i + 1);
+                return Ok(());
+            }
+            None => {
+                let len = buf.len();
+                reader.consume(len);
+            }
+        }
+    }
+}
+
+/// read frames from a syslog tcp connection. both octet counting
+/// (`<len> <msg>`, rfc 6587) and newline delimited framing are accepted
+fn tcp_stream(
+    stream: TcpStream, addr: SocketAddr, format: Format,
+    tx: mpsc::Sender<Event>,
+) {
+    if let Err(e) = stream.set_read_timeout(Some(IDLE_TIMEOUT)) {
+        println!("tcp timeout error from {addr}: {e}");
+        return;
+    }
+
+    let mut reader = BufReader::new(stream);
+    let mut frame = Vec::with_capacity(512);
+
+    loop {
+        frame.clear();
+        let octet_counted = match reader.fill_buf() {
+            Ok([]) => return,
+            Ok(buf) => octet_counted(buf),
+            Err(e) => {
+                println!("tcp read error from {addr}: {e}");
+                return;
+            }
+        };
+
+        let result = if octet_counted {
+            let mut len = Vec::with_capacity(8);
+            reader.read_until(b' ', &mut len).and_then(|_| {
+                let len = std::str::from_utf8(len.trim_ascii())
+                    .ok()
+                    .and_then(|v| v.parse::<usize>().ok())
+                    .filter(|v| *v <= MAX_FRAME)
+                    .ok_or(std::io::ErrorKind::InvalidData)?;
+                frame.resize(len, 0);
+                reader.read_exact(&mut frame)
+            })
+        } else {
+            let mut limited = (&mut reader).take(MAX_FRAME as u64);
+            limited.read_until(b'\n', &mut frame).and_then(|_| {
+                if frame.len() < MAX_FRAME || frame.last() == Some(&b'\n') {
+                    return Ok(());
+                }
+                // the frame was cut off, the rest of it is not a new frame
+                frame.clear();
+                COUNTERS.malformed.fetch_add(1, Relaxed);
+                skip_line(&mut reader)
+            })
+        };
+
+        if let Err(e) = result {
+            println!("tcp read error from {addr}: {e}");
+            return;
+        }
+
+        if frame.is_empty() {
+            continue;
+        }
+
+        let Some(record) = format.parse(payload(&frame)) else {
+            COUNTERS.malformed.fetch_add(1, Relaxed);
+            continue;
+        };
+
+        if tx.send(Event::Record(record)).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allow(nets: &[(&str, u8)]) -> Allow {
+        Allow(nets.iter().map(|(ip, p)| (ip.parse().unwrap(), *p)).collect())
+    }
+
+    #[test]
+    fn allow_contains() {
+        let ip = |v: &str| v.parse::<IpAddr>().unwrap();
+        let lan = allow(&[("10.0.0.0", 24), ("192.168.1.7", 32)]);
+        assert!(lan.contains(ip("10.0.0.200")));
+        assert!(!lan.contains(ip("10.0.1.1")));
+        assert!(lan.contains(ip("192.168.1.7")));
+        assert!(!lan.contains(ip("192.168.1.8")));
+        // ipv4 mapped addresses are checked as ipv4
+        assert!(lan.contains(ip("::ffff:10.0.0.5")));
+        assert!(!lan.contains(ip("2001:db8::1")));
+
+        let empty = Allow::default();
+        assert!(empty.contains(ip("127.0.0.1")));
+        assert!(empty.contains(ip("::1")));
+        assert!(!empty.contains(ip("10.0.0.1")));
+
+        let v6 = allow(&[("2001:db8::", 32), ("0.0.0.0", 0)]);
+        assert!(v6.contains(ip("2001:db8:ffff::1")));
+        assert!(!v6.contains(ip("2001:db9::1")));
+        assert!(v6.contains(ip("8.8.8.8")));
+    }
+
+    #[test]
+    fn payload_header() {
+        let line = b"<190>Oct 19 12:00:00 web nginx: [200,12,\"/\"]\n";
+        assert_eq!(payload(line), b"[200,12,\"/\"]");
+        let line = b"<190>Oct  9 12:00:00 nginx: [200,12,\"/\"]";
+        assert_eq!(payload(line), b"[200,12,\"/\"]");
+        assert_eq!(payload(b"[200,12,\"/\"]"), b"[200,12,\"/\"]");
+        // no `<pri>`, no header: nothing is cut off
+        let line = b"203.0.113.7 - - [19/Oct/2026:12:00:00 +0000] \"GET /\"";
+        assert_eq!(payload(line), line);
+        let line = b"<html> is not a priority, this line is kept";
+        assert_eq!(payload(line), line);
+    }
+
+    #[test]
+    fn octet_counting() {
+        assert!(octet_counted(b"12 [200,12,\"/\"]"));
+        assert!(!octet_counted(b"200,12,\"/\"\n"));
+        assert!(!octet_counted(b"2026-10-19 12:00:00 GET /\n"));
+        assert!(!octet_counted(b" 12 [200]"));
+        assert!(!octet_counted(b"<190>Oct 19"));
+    }
+
+    #[test]
+    fn skip_long_line() {
+        let mut reader =
+            BufReader::with_capacity(4, &b"xxxxxxxxxx\nnext\n"[..]);
+        skip_line(&mut reader).unwrap();
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "next\n");
+    }
+}
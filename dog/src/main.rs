@@ -1,73 +1,66 @@
 use std::{
-    collections::HashMap,
     env,
-    fs::Permissions,
-    os::unix::{fs::PermissionsExt, net::UnixDatagram},
+    sync::{atomic::Ordering::Relaxed, mpsc, Arc},
     time::{Duration, Instant},
 };
 
-use serde::Serialize;
-
-const API_DUMP: &str = "https://heimdall.00-team.org/api/sites/dump/";
-const API_PING: &str = "https://heimdall.00-team.org/api/sites/ping/";
-
-#[derive(Debug, Default)]
-struct Message {
-    status: u16,
-    upstream_response_time: f64,
-}
-
-impl From<&[u8]> for Message {
-    fn from(value: &[u8]) -> Self {
-        let mut msg = Self::default();
-        if value.len() < 3 {
-            return msg;
-        }
-
-        let mut it = value[1..value.len() - 1].splitn(2, |v| *v == b',');
-
-        if let Some(v) = it.next().and_then(|v| std::str::from_utf8(v).ok()) {
-            msg.status = v.parse().unwrap_or_default();
-        }
-
-        if let Some(v) = it.next().and_then(|v| std::str::from_utf8(v).ok()) {
-            msg.upstream_response_time = v.parse().unwrap_or_default();
-        }
-
-        msg
-    }
-}
-
-#[derive(Serialize, Default, Debug)]
-struct Status {
-    code: u16,
-    count: u64,
-    max_time: u64,
-    min_time: u64,
-    total_time: u64,
-}
-
-#[derive(Serialize, Default, Debug)]
-struct Dump {
-    total: u64,
-    total_time: u64,
-    max_time: u64,
-    min_time: u64,
-    status: HashMap<String, Status>,
-}
+use counters::COUNTERS;
+use dump::{Dump, Event};
+use input::{Allow, Input};
+use privacy::Privacy;
+use sender::{client_init, ping_body, Sender, API_PING};
+
+mod check;
+mod control;
+mod counters;
+mod dump;
+mod format;
+mod input;
+mod privacy;
+mod prometheus;
+mod replay;
+mod rng;
+mod sender;
+mod simulate;
+mod statsd;
+mod systemd;
 
 macro_rules! evar {
     ($name:literal) => {
-        env::var($name).expect(concat!($name, " was not found in env"))
+        std::env::var($name).expect(concat!($name, " was not found in env"))
     };
 }
+pub(crate) use evar;
 
 fn main() -> std::io::Result<()> {
     #[cfg(debug_assertions)]
     dotenvy::from_path(".env").expect("could not read .env file");
 
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if !args.is_empty() {
+        // commands are run by hand, outside of the systemd environment
+        let _ = dotenvy::from_path(".env");
+    }
+
+    match args.first().map(|v| v.as_str()) {
+        None => {}
+        Some("replay") => return replay::replay(&args[1..]),
+        Some("check") => return check::check(),
+        Some("simulate") => return simulate::simulate(&args[1..]),
+        Some("status") => return control::status(),
+        Some(cmd) => {
+            println!(
+                "unknown command: {cmd}\n\
+                usage: dog [check | status | simulate [<options>] | \
+                replay [-f <format>] <files>...]"
+            );
+            std::process::exit(1);
+        }
+    }
+
     std::thread::spawn(|| {
         let client = client_init();
+        let body = ping_body();
 
         loop {
             std::thread::sleep(Duration::from_secs(60));
@@ -80,7 +73,7 @@ fn main() -> std::io::Result<()> {
             };
 
             if output.stdout == b"active\n" {
-                if let Err(e) = client.post(API_PING).body("").send() {
+                if let Err(e) = client.post(API_PING).json(&body).send() {
                     println!("could not send ping: {e:#?}");
                 };
             }
@@ -89,91 +82,54 @@ fn main() -> std::io::Result<()> {
         }
     });
 
-    // let sock_path = "/tmp/heimdall.dog.sock";
-    let sock_path = format!(
-        "/usr/share/nginx/socks/heimdall.dog.{}.sock",
-        evar!("HEIMDALL_SITE")
-    );
-
-    let _ = std::fs::remove_file(&sock_path);
-    let server = UnixDatagram::bind(&sock_path)?;
-    std::fs::set_permissions(&sock_path, Permissions::from_mode(0o777))?;
-    // server.set_nonblocking(true)?;
-    let mut buf = vec![0u8; 512];
-    let mut dump = Dump::default();
-    let mut latest_request = Instant::now();
+    let (tx, rx) = mpsc::channel();
+    COUNTERS.started.store(counters::now(), Relaxed);
+    let allow = Arc::new(Allow::from_env().map_err(std::io::Error::other)?);
+    let mut privacy = Privacy::from_env().map_err(std::io::Error::other)?;
+    for input in Input::from_env().map_err(std::io::Error::other)? {
+        input.spawn(allow.clone(), tx.clone())?;
+    }
+    statsd::spawn(tx.clone())?;
+    prometheus::spawn()?;
+    control::spawn()?;
+    systemd::notify("READY=1");
+    drop(tx);
 
-    let client = client_init();
+    let mut dump = Dump::new(counters::now() as i64);
+    let mut latest_request = Instant::now();
+    let mut sender = Sender::new(sender::instance());
+    let mut watchdog = systemd::Watchdog::from_env();
 
     loop {
-        if latest_request.elapsed().as_secs() >= 10 && dump.total != 0 {
-            let res = match client.post(API_DUMP).json(&dump).send() {
-                Ok(v) => v,
-                Err(e) => {
-                    println!("could not send dump request: {e:#?}");
-                    continue;
-                }
-            };
-            if res.status() != reqwest::StatusCode::OK {
-                println!("err: {:?}", res.json::<serde_json::Value>());
+        watchdog.poll(|| COUNTERS.status());
+
+        if latest_request.elapsed().as_secs() >= 10 {
+            if !dump.is_empty() {
+                sender.push(dump.take());
+            }
+            if sender.backlog() != 0 {
+                // the watchdog is pinged between the batches
+                sender.flush(|| watchdog.poll(|| COUNTERS.status()));
             }
-            dump = Dump::default();
             latest_request = Instant::now();
         }
 
-        let size = match server.recv(buf.as_mut_slice()) {
-            Ok(s) => s,
-            Err(e) => {
-                println!("server recv error: {e}");
-                continue;
+        match rx.recv_timeout(Duration::from_secs(1)) {
+            Ok(Event::Record(mut record)) => {
+                if let Some(weight) = privacy.apply(&mut record) {
+                    COUNTERS.record(&record, weight);
+                    dump.add(&record, weight);
+                }
             }
-        };
-        let msg = Message::from(&buf[24..size]);
-        let time = (msg.upstream_response_time * 1000.0) as u64;
-        dump.total += 1;
-        dump.total_time += time;
-        if dump.max_time < time {
-            dump.max_time = time;
-        }
-
-        if dump.min_time > time || dump.min_time == 0 {
-            dump.min_time = time;
-        }
-
-        let sk = msg.status.to_string();
-        if let Some(status) = dump.status.get_mut(&sk) {
-            status.count += 1;
-            status.total_time += time;
-
-            if status.min_time > time || status.min_time == 0 {
-                status.min_time = time;
+            Ok(Event::Metric(sample)) => {
+                COUNTERS.samples.fetch_add(1, Relaxed);
+                dump.add_sample(&sample);
             }
-            if status.max_time < time {
-                status.max_time = time;
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                println!("all inputs are closed");
+                return Ok(());
             }
-        } else {
-            dump.status.insert(
-                sk,
-                Status {
-                    code: msg.status,
-                    total_time: time,
-                    min_time: time,
-                    max_time: time,
-                    count: 1,
-                },
-            );
         }
     }
 }
-
-fn client_init() -> reqwest::blocking::Client {
-    let mut headers = reqwest::header::HeaderMap::new();
-    headers.insert(
-        "authorization",
-        evar!("HEIMDALL_TOKEN").parse().expect("bad token"),
-    );
-    reqwest::blocking::ClientBuilder::new()
-        .default_headers(headers)
-        .build()
-        .expect("could not build the client")
-}
@@ -0,0 +1,260 @@
+use std::{
+    hash::{BuildHasher, RandomState},
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+use crate::dump::Record;
+use crate::rng::Rng;
+
+/// how client addresses are kept before anything leaves the host
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IpMode {
+    Keep,
+    /// zero the host part: /24 for ipv4, /48 for ipv6
+    Truncate,
+    /// keyed hash with a salt that only lives in memory and is rotated
+    Hash,
+    Drop,
+}
+
+impl std::str::FromStr for IpMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Self::Keep),
+            "truncate" => Ok(Self::Truncate),
+            "hash" => Ok(Self::Hash),
+            "drop" => Ok(Self::Drop),
+            _ => Err(format!("unknown ip mode: {s}")),
+        }
+    }
+}
+
+/// per-site privacy settings, applied to every record before it is
+/// aggregated into a dump:
+/// - `HEIMDALL_IP`: keep, truncate (default), hash or drop
+/// - `HEIMDALL_IP_SALT_HOURS`: how often the hash salt rotates, default 24
+/// - `HEIMDALL_EXCLUDE`: comma separated path prefixes left out of the stats
+/// - `HEIMDALL_SAMPLE`: keep 1 in N records, counts are scaled back by N
+/// - `HEIMDALL_REDACT`: comma separated query parameters whose values are
+///   replaced
+pub struct Privacy {
+    ip: IpMode,
+    salt: RandomState,
+    salt_period: Duration,
+    salt_created: Instant,
+    exclude: Vec<String>,
+    sample: u64,
+    redact: Vec<String>,
+    rng: Rng,
+}
+
+fn list(name: &str) -> Vec<String> {
+    std::env::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+impl Privacy {
+    pub fn from_env() -> Result<Self, String> {
+        let ip = match std::env::var("HEIMDALL_IP") {
+            Ok(v) => v.trim().parse()?,
+            Err(_) => IpMode::Truncate,
+        };
+
+        let hours = match std::env::var("HEIMDALL_IP_SALT_HOURS") {
+            Ok(v) => v
+                .trim()
+                .parse::<u64>()
+                .ok()
+                .filter(|v| *v > 0)
+                .ok_or(format!("invalid HEIMDALL_IP_SALT_HOURS: {v}"))?,
+            Err(_) => 24,
+        };
+
+        let sample = match std::env::var("HEIMDALL_SAMPLE") {
+            Ok(v) => v
+                .trim()
+                .parse::<u64>()
+                .ok()
+                .filter(|v| *v > 0)
+                .ok_or(format!("invalid HEIMDALL_SAMPLE: {v}"))?,
+            Err(_) => 1,
+        };
+
+        Ok(Self {
+            ip,
+            salt: RandomState::new(),
+            salt_period: Duration::from_secs(hours * 3600),
+            salt_created: Instant::now(),
+            exclude: list("HEIMDALL_EXCLUDE"),
+            sample,
+            redact: list("HEIMDALL_REDACT"),
+            rng: Rng::new(),
+        })
+    }
+
+    /// clean the record up in place, before anything else reads it. returns
+    /// the weight it should be counted with, or `None` when it must be left
+    /// out
+    pub fn apply(&mut self, record: &mut Record) -> Option<u64> {
+        record.ip = record.ip.take().and_then(|v| self.ip(&v));
+        if let Some(path) = record.path.as_mut() {
+            self.redact(path);
+        }
+
+        if let Some(path) = record.path.as_deref() {
+            let path = path.split('?').next().unwrap_or_default();
+            if self.exclude.iter().any(|p| path.starts_with(p.as_str())) {
+                return None;
+            }
+        }
+
+        if self.sample > 1 && self.rng.next(self.sample) != 0 {
+            return None;
+        }
+
+        Some(self.sample)
+    }
+
+    fn ip(&mut self, ip: &str) -> Option<String> {
+        let ip = ip.trim().parse::<IpAddr>().ok()?;
+        match self.ip {
+            IpMode::Keep => Some(ip.to_string()),
+            IpMode::Drop => None,
+            IpMode::Truncate => Some(match ip {
+                IpAddr::V4(v) => {
+                    let [a, b, c, _] = v.octets();
+                    format!("{a}.{b}.{c}.0")
+                }
+                IpAddr::V6(v) => {
+                    let s = v.segments();
+                    format!("{:x}:{:x}:{:x}::", s[0], s[1], s[2])
+                }
+            }),
+            IpMode::Hash => {
+                if self.salt_created.elapsed() >= self.salt_period {
+                    self.salt = RandomState::new();
+                    self.salt_created = Instant::now();
+                }
+                Some(format!("{:016x}", self.salt.hash_one(ip)))
+            }
+        }
+    }
+
+    /// replace the values of the redacted query parameters
+    fn redact(&self, path: &mut String) {
+        let Some((base, query)) = path.split_once('?') else { return };
+        if self.redact.is_empty() {
+            return;
+        }
+
+        let query = query
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((k, _)) if self.redact.iter().any(|r| r == k) => {
+                    format!("{k}=redacted")
+                }
+                _ => pair.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+
+        *path = format!("{base}?{query}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn privacy(exclude: &[&str], sample: u64) -> Privacy {
+        Privacy {
+            ip: IpMode::Truncate,
+            salt: RandomState::new(),
+            salt_period: Duration::from_secs(3600),
+            salt_created: Instant::now(),
+            exclude: exclude.iter().map(|v| v.to_string()).collect(),
+            sample,
+            redact: vec!["token".into(), "email".into()],
+            rng: Rng::new(),
+        }
+    }
+
+    fn record(path: &str) -> Record {
+        Record { status: 200, path: Some(path.into()), ..Default::default() }
+    }
+
+    #[test]
+    fn exclude() {
+        let mut p = privacy(&["/health", "/admin/"], 1);
+        assert_eq!(p.apply(&mut record("/health")), None);
+        assert_eq!(p.apply(&mut record("/healthz?full=1")), None);
+        assert_eq!(p.apply(&mut record("/admin/users/")), None);
+        assert_eq!(p.apply(&mut record("/api/?next=/health")), Some(1));
+        assert_eq!(p.apply(&mut record("/admin")), Some(1));
+        assert_eq!(p.apply(&mut Record::default()), Some(1));
+    }
+
+    #[test]
+    fn ip_modes() {
+        let mut p = privacy(&[], 1);
+        let ip = |p: &mut Privacy, mode, ip: &str| {
+            p.ip = mode;
+            let mut r = Record { ip: Some(ip.into()), ..Default::default() };
+            p.apply(&mut r);
+            r.ip
+        };
+
+        let v4 = ip(&mut p, IpMode::Truncate, "203.0.113.77");
+        assert_eq!(v4.as_deref(), Some("203.0.113.0"));
+        let v6 = ip(&mut p, IpMode::Truncate, "2001:db8:85a3:8d3::7334");
+        assert_eq!(v6.as_deref(), Some("2001:db8:85a3::"));
+        let kept = ip(&mut p, IpMode::Keep, " 203.0.113.77");
+        assert_eq!(kept.as_deref(), Some("203.0.113.77"));
+        assert_eq!(ip(&mut p, IpMode::Drop, "203.0.113.77"), None);
+        assert_eq!(ip(&mut p, IpMode::Truncate, "not an ip"), None);
+
+        let a = ip(&mut p, IpMode::Hash, "203.0.113.77").unwrap();
+        assert_eq!(a.len(), 16);
+        assert!(!a.contains("203"));
+        assert_eq!(ip(&mut p, IpMode::Hash, "203.0.113.77").unwrap(), a);
+        assert_ne!(ip(&mut p, IpMode::Hash, "203.0.113.78").unwrap(), a);
+
+        // a new salt gives the same address another hash
+        p.salt_created = Instant::now() - Duration::from_secs(3600);
+        assert_ne!(ip(&mut p, IpMode::Hash, "203.0.113.77").unwrap(), a);
+    }
+
+    #[test]
+    fn redact() {
+        let mut p = privacy(&[], 1);
+        let mut r = record("/login/?token=abc&next=/home&email=a@b.c&token");
+        p.apply(&mut r);
+        assert_eq!(
+            r.path.as_deref(),
+            Some("/login/?token=redacted&next=/home&email=redacted&token")
+        );
+
+        let mut r = record("/search/?q=rust");
+        p.apply(&mut r);
+        assert_eq!(r.path.as_deref(), Some("/search/?q=rust"));
+    }
+
+    #[test]
+    fn sample() {
+        let mut p = privacy(&[], 10);
+        let weights = (0..10_000)
+            .filter_map(|_| p.apply(&mut record("/")))
+            .collect::<Vec<_>>();
+        assert!(weights.iter().all(|w| *w == 10));
+        // kept records scaled by their weight add up to about the total
+        let total = weights.iter().sum::<u64>();
+        assert!((7_000..13_000).contains(&total), "{total}");
+    }
+}
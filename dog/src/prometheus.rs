@@ -0,0 +1,65 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+
+use crate::counters::COUNTERS;
+
+/// serve the live counters on `/metrics`. configured with
+/// `HEIMDALL_PROMETHEUS`, the address to listen on (e.g. `127.0.0.1:9150`)
+pub fn spawn() -> std::io::Result<()> {
+    let Ok(addr) = std::env::var("HEIMDALL_PROMETHEUS") else {
+        return Ok(());
+    };
+
+    let server = TcpListener::bind(addr)?;
+    std::thread::spawn(move || {
+        for stream in server.incoming() {
+            match stream {
+                Ok(s) => {
+                    if let Err(e) = handle(s) {
+                        println!("prometheus request error: {e}");
+                    }
+                }
+                Err(e) => println!("prometheus accept error: {e}"),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn handle(stream: TcpStream) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(5)))?;
+
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+
+    // drain the headers
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let mut parts = request.split(' ');
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => (
+            "200 OK",
+            "text/plain; version=0.0.4; charset=utf-8",
+            COUNTERS.render(),
+        ),
+        _ => ("404 Not Found", "text/plain", "not found\n".to_string()),
+    };
+
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\ncontent-type: {content_type}\r\n\
+        content-length: {}\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
@@ -0,0 +1,87 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    time::Duration,
+};
+
+use flate2::read::MultiGzDecoder;
+
+use crate::dump::Dump;
+use crate::format::Format;
+use crate::privacy::Privacy;
+use crate::sender::{self, Sender};
+
+/// back-filled requests are grouped into windows of this many seconds
+const WINDOW: i64 = 60;
+
+/// read historical access logs (plain or gzip) and submit one back-dated
+/// dump per window. the format defaults to `heimdall_file` and can be set
+/// with `-f <format>`. files must be given oldest first,
+/// e.g. `access.log.3.gz access.log.2.gz`
+pub fn replay(args: &[String]) -> std::io::Result<()> {
+    let (format, paths) = match args {
+        [flag, format, paths @ ..] if flag == "-f" => {
+            (format.parse::<Format>().map_err(std::io::Error::other)?, paths)
+        }
+        paths => (Format::HeimdallFile, paths),
+    };
+
+    // a separate instance, its sequence must not get ahead of the daemon's
+    let mut sender = Sender::new(format!("{}:replay", sender::instance()));
+    let mut privacy = Privacy::from_env().map_err(std::io::Error::other)?;
+
+    for path in paths {
+        let file = File::open(path)?;
+        let reader: Box<dyn Read> = if path.ends_with(".gz") {
+            Box::new(MultiGzDecoder::new(file))
+        } else {
+            Box::new(file)
+        };
+
+        let mut dump = Dump::default();
+        let mut lines = 0u64;
+        let mut skipped = 0u64;
+
+        for line in BufReader::new(reader).split(b'\n') {
+            let line = line?;
+            lines += 1;
+            let Some(mut record) =
+                format.parse(&line).filter(|r| r.timestamp != 0)
+            else {
+                skipped += 1;
+                continue;
+            };
+
+            let window = record.timestamp - record.timestamp % WINDOW;
+            if dump.start != window {
+                if dump.total != 0 {
+                    sender.push(std::mem::take(&mut dump));
+                    send(&mut sender);
+                }
+                dump = Dump::new(window);
+                dump.end = window + WINDOW;
+            }
+
+            if let Some(weight) = privacy.apply(&mut record) {
+                dump.add(&record, weight);
+            }
+        }
+
+        if dump.total != 0 {
+            sender.push(dump);
+            send(&mut sender);
+        }
+
+        println!("replayed {path}: {lines} lines, {skipped} skipped");
+    }
+
+    Ok(())
+}
+
+/// keep retrying until every spooled dump is delivered
+fn send(sender: &mut Sender) {
+    while !sender.flush(|| {}) {
+        println!("retrying in 5s, backlog: {}", sender.backlog());
+        std::thread::sleep(Duration::from_secs(5));
+    }
+}
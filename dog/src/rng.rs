@@ -0,0 +1,22 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// a tiny xorshift, good enough for synthetic traffic and sampling. it is
+/// seeded from the clock and predictable, nothing secret comes from it
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0x2545f4914f6cdd1d);
+        Self(seed | 1)
+    }
+
+    pub fn next(&mut self, max: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % max.max(1)
+    }
+}
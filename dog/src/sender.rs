@@ -0,0 +1,235 @@
+use std::{
+    collections::VecDeque,
+    io::{Read, Write},
+    sync::atomic::Ordering::Relaxed,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use flate2::{write::GzEncoder, Compression};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::counters::{now, COUNTERS};
+use crate::dump::Dump;
+use crate::evar;
+
+pub const API_DUMP: &str = "https://heimdall.00-team.org/api/sites/dump/";
+pub const API_PING: &str = "https://heimdall.00-team.org/api/sites/ping/";
+
+/// dumps that could not be delivered are kept here until the next flush
+const SPOOL_MAX: usize = 1024;
+/// most spooled dumps that are sent in one request
+const BATCH_MAX: usize = 32;
+
+/// body encoding, set with `HEIMDALL_COMPRESS`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Identity,
+    Gzip,
+    Zstd,
+}
+
+pub struct Sender {
+    client: reqwest::blocking::Client,
+    spool: VecDeque<Dump>,
+    encoding: Encoding,
+    /// `HEIMDALL_SIGNING_KEY`, dumps are signed when it is set
+    key: Option<String>,
+    instance: String,
+    seq: u64,
+}
+
+/// the name of this dog in its dumps: `HEIMDALL_INSTANCE` or the hostname
+pub fn instance() -> String {
+    std::env::var("HEIMDALL_INSTANCE")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| "dog".to_string())
+}
+
+impl Sender {
+    pub fn new(instance: String) -> Self {
+        let encoding = match std::env::var("HEIMDALL_COMPRESS").as_deref() {
+            Ok("gzip") => Encoding::Gzip,
+            Ok("zstd") => Encoding::Zstd,
+            _ => Encoding::Identity,
+        };
+
+        // a key of its own: the token is sent with every request
+        let key = std::env::var("HEIMDALL_SIGNING_KEY")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+
+        Self {
+            client: client_init(),
+            spool: VecDeque::new(),
+            encoding,
+            key,
+            instance,
+            // dumps are at least seconds apart, so starting from the time in
+            // milliseconds keeps the sequence increasing across restarts
+            seq: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+        }
+    }
+
+    pub fn backlog(&self) -> usize {
+        self.spool.len()
+    }
+
+    pub fn push(&mut self, mut dump: Dump) {
+        self.seq += 1;
+        dump.seq = self.seq;
+        dump.instance = self.instance.clone();
+        if self.spool.len() >= SPOOL_MAX {
+            println!("spool is full, dropping the oldest dump");
+            self.spool.pop_front();
+        }
+        self.spool.push_back(dump);
+        COUNTERS.backlog.store(self.spool.len() as u64, Relaxed);
+    }
+
+    /// build the dump request for a json body: it is signed first, then
+    /// compressed
+    fn request(
+        &mut self, body: Vec<u8>,
+    ) -> std::io::Result<reqwest::blocking::RequestBuilder> {
+        let mut request = self
+            .client
+            .post(API_DUMP)
+            .header("content-type", "application/json");
+
+        if let Some(key) = &self.key {
+            let timestamp = now();
+            let nonce = nonce()?;
+            let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes())
+                .map_err(std::io::Error::other)?;
+            mac.update(format!("{timestamp}\n{nonce}\n").as_bytes());
+            mac.update(&body);
+            let signature = mac
+                .finalize()
+                .into_bytes()
+                .iter()
+                .map(|v| format!("{v:02x}"))
+                .collect::<String>();
+
+            request = request
+                .header("x-heimdall-timestamp", timestamp)
+                .header("x-heimdall-nonce", nonce)
+                .header("x-heimdall-signature", signature);
+        }
+
+        let body = match self.encoding {
+            Encoding::Identity => body,
+            Encoding::Gzip => {
+                request = request.header("content-encoding", "gzip");
+                let mut encoder =
+                    GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&body)?;
+                encoder.finish()?
+            }
+            Encoding::Zstd => {
+                request = request.header("content-encoding", "zstd");
+                zstd::encode_all(body.as_slice(), 0)?
+            }
+        };
+
+        Ok(request.body(body))
+    }
+
+    /// send the spooled dumps in order, in batches of up to `BATCH_MAX`.
+    /// stops at the first failure and keeps the spool. only the dumps the
+    /// server rejected on their own are dropped. `tick` runs before every
+    /// request, a slow server can keep the loop here for a while
+    pub fn flush(&mut self, mut tick: impl FnMut()) -> bool {
+        // the dumps of a rejected batch are sent one by one to find the bad
+        // ones
+        let mut single = 0;
+        while !self.spool.is_empty() {
+            tick();
+            let count = match single {
+                0 => self.spool.len().min(BATCH_MAX),
+                _ => 1,
+            };
+            let batch = self.spool.iter().take(count).collect::<Vec<_>>();
+            let request = serde_json::to_vec(&batch)
+                .map_err(std::io::Error::from)
+                .and_then(|body| self.request(body));
+            let res = match request.map(|r| r.send()) {
+                Ok(Ok(v)) => v,
+                Ok(Err(e)) => {
+                    println!("could not send dump request: {e:#?}");
+                    COUNTERS.send_failures.fetch_add(1, Relaxed);
+                    return false;
+                }
+                Err(e) => {
+                    println!("could not build dump request: {e}");
+                    return false;
+                }
+            };
+
+            // a rotated token or key, a removed site or throttling: the
+            // dumps are fine and are sent once it is sorted out
+            let status = res.status();
+            let retry = matches!(status.as_u16(), 401 | 403 | 404 | 408 | 429);
+            if status.is_server_error() || retry {
+                println!("could not send dumps: {status}");
+                COUNTERS.send_failures.fetch_add(1, Relaxed);
+                return false;
+            }
+
+            if status.is_success() {
+                COUNTERS.dumps_sent.fetch_add(count as u64, Relaxed);
+            } else if count > 1 {
+                println!("batch rejected with {status}, sending it one by one");
+                single = count;
+                continue;
+            } else {
+                let error = res.text().unwrap_or_default();
+                println!("dump rejected with {status}: {error}");
+                COUNTERS.dumps_rejected.fetch_add(1, Relaxed);
+            }
+
+            single = single.saturating_sub(1);
+            self.spool.drain(..count);
+            COUNTERS.latest_sent.store(now(), Relaxed);
+            COUNTERS.backlog.store(self.spool.len() as u64, Relaxed);
+        }
+
+        true
+    }
+}
+
+/// a signature nonce from the os random source, the nonces of a clock
+/// seeded generator could be guessed ahead of time
+fn nonce() -> std::io::Result<String> {
+    let mut bytes = [0u8; 16];
+    std::fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes.iter().map(|v| format!("{v:02x}")).collect())
+}
+
+/// pings name this instance and its version
+pub fn ping_body() -> serde_json::Value {
+    serde_json::json!({
+        "instance": instance(),
+        "version": env!("CARGO_PKG_VERSION"),
+    })
+}
+
+pub fn client_init() -> reqwest::blocking::Client {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(
+        "authorization",
+        evar!("HEIMDALL_TOKEN").parse().expect("bad token"),
+    );
+    reqwest::blocking::ClientBuilder::new()
+        .default_headers(headers)
+        .timeout(Duration::from_secs(30))
+        .build()
+        .expect("could not build the client")
+}
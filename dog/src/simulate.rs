@@ -0,0 +1,158 @@
+use std::{
+    io::Write,
+    net::{TcpStream, UdpSocket},
+    os::unix::net::UnixDatagram,
+    time::Duration,
+};
+
+use crate::input::sock_path;
+use crate::rng::Rng;
+
+const USAGE: &str = "usage: dog simulate [-n <count>] \
+    [-s <status>=<weight>,...] [-l <min ms>-<max ms>] \
+    [-t unix=<path>|udp=<addr>|tcp=<addr>] [-r <per second>]";
+
+type Send = Box<dyn Fn(&[u8]) -> std::io::Result<()>>;
+
+struct Options {
+    count: u64,
+    status: Vec<(u16, u64)>,
+    latency: (u64, u64),
+    /// the unix socket of dog when missing
+    target: Option<String>,
+    /// lines per second, 0 sends them as fast as it can
+    rate: u64,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut opts = Self {
+            count: 100,
+            status: vec![(200, 90), (404, 5), (500, 5)],
+            latency: (5, 200),
+            target: None,
+            rate: 0,
+        };
+
+        let mut it = args.iter();
+        while let Some(flag) = it.next() {
+            let value =
+                it.next().ok_or_else(|| format!("{flag} needs a value"))?;
+            let bad = || format!("invalid value for {flag}: {value}");
+            match flag.as_str() {
+                "-n" => opts.count = value.parse().map_err(|_| bad())?,
+                "-r" => {
+                    opts.rate = value
+                        .parse()
+                        .ok()
+                        .filter(|v| (1..=1_000_000).contains(v))
+                        .ok_or_else(bad)?;
+                }
+                "-t" => opts.target = Some(value.clone()),
+                "-l" => {
+                    let (min, max) = value.split_once('-').ok_or_else(bad)?;
+                    let min = min.parse::<u64>().map_err(|_| bad())?;
+                    let max = max.parse::<u64>().map_err(|_| bad())?;
+                    if min > max || max == u64::MAX {
+                        return Err(bad());
+                    }
+                    opts.latency = (min, max);
+                }
+                "-s" => {
+                    opts.status = value
+                        .split(',')
+                        .map(|v| {
+                            let (code, weight) = v.split_once('=')?;
+                            Some((code.parse().ok()?, weight.parse().ok()?))
+                        })
+                        .collect::<Option<Vec<_>>>()
+                        .filter(|v| {
+                            v.iter()
+                                .try_fold(0u64, |sum, (_, w)| {
+                                    sum.checked_add(*w)
+                                })
+                                .is_some_and(|sum| sum > 0)
+                        })
+                        .ok_or_else(bad)?;
+                }
+                _ => return Err(format!("unknown option: {flag}")),
+            }
+        }
+
+        Ok(opts)
+    }
+}
+
+/// `dog simulate`: send synthetic nginx log lines to a dog input with the
+/// chosen status and latency mix
+pub fn simulate(args: &[String]) -> std::io::Result<()> {
+    let opts = Options::parse(args).map_err(|e| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{e}\n{USAGE}"),
+        )
+    })?;
+
+    let target = match opts.target {
+        Some(target) => target,
+        None => format!("unix={}", sock_path()),
+    };
+    let send: Send = match target.split_once('=') {
+        Some(("unix", path)) => {
+            let sock = UnixDatagram::unbound()?;
+            let path = path.to_string();
+            Box::new(move |v| sock.send_to(v, &path).map(|_| ()))
+        }
+        Some(("udp", addr)) => {
+            let sock = UdpSocket::bind("0.0.0.0:0")?;
+            sock.connect(addr)?;
+            Box::new(move |v| sock.send(v).map(|_| ()))
+        }
+        Some(("tcp", addr)) => {
+            let stream = TcpStream::connect(addr)?;
+            Box::new(move |v| {
+                let mut stream = &stream;
+                stream.write_all(v)?;
+                stream.write_all(b"\n")
+            })
+        }
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("invalid target: {target}\n{USAGE}"),
+            ))
+        }
+    };
+
+    let total_weight = opts.status.iter().map(|(_, w)| w).sum::<u64>();
+    let (min, max) = opts.latency;
+    let mut rng = Rng::new();
+
+    for _ in 0..opts.count {
+        let mut pick = rng.next(total_weight);
+        let mut status = opts.status[0].0;
+        for (code, weight) in opts.status.iter() {
+            if pick < *weight {
+                status = *code;
+                break;
+            }
+            pick -= weight;
+        }
+
+        let time = min + rng.next(max - min + 1);
+        // the syslog header is only skipped by dog, the date is not used
+        let line = format!(
+            "<190>Jan  1 00:00:00 H: [{status},{}.{:03}]",
+            time / 1000,
+            time % 1000
+        );
+        send(line.as_bytes())?;
+
+        if opts.rate != 0 {
+            std::thread::sleep(Duration::from_secs(1) / opts.rate as u32);
+        }
+    }
+
+    println!("sent {} lines to {target}", opts.count);
+    Ok(())
+}
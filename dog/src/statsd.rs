@@ -0,0 +1,262 @@
+use std::{
+    collections::HashSet,
+    fs::Permissions,
+    net::UdpSocket,
+    os::unix::{fs::PermissionsExt, net::UnixDatagram},
+    sync::mpsc,
+};
+
+use serde::Serialize;
+
+use crate::dump::Event;
+
+const MAX_PACKET: usize = 8 * 1024;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+    Timer,
+    Set,
+}
+
+/// a single statsd line: `name:value|type[|@rate][|#tags]`
+#[derive(Debug)]
+pub struct Sample {
+    pub name: String,
+    pub kind: MetricKind,
+    pub value: f64,
+    /// `+`/`-` prefixed gauges change the current value
+    pub relative: bool,
+    /// the raw value, used for sets
+    pub member: String,
+    pub rate: f64,
+}
+
+impl Sample {
+    pub fn parse(line: &str) -> Option<Self> {
+        let (name, rest) = line.trim().split_once(':')?;
+        if name.is_empty() || name.len() > 255 {
+            return None;
+        }
+
+        let mut parts = rest.split('|');
+        let raw = parts.next()?;
+        let kind = match parts.next()? {
+            "c" => MetricKind::Counter,
+            "g" => MetricKind::Gauge,
+            "ms" | "h" | "d" => MetricKind::Timer,
+            "s" => MetricKind::Set,
+            _ => return None,
+        };
+
+        let mut rate = 1.0;
+        for part in parts {
+            if let Some(r) = part.strip_prefix('@') {
+                rate = r.parse::<f64>().ok().filter(|r| *r > 0.0)?;
+            }
+        }
+
+        let value = match kind {
+            MetricKind::Set => 0.0,
+            _ => raw.parse::<f64>().ok().filter(|v| v.is_finite())?,
+        };
+
+        Some(Self {
+            name: name.to_string(),
+            kind,
+            value,
+            relative: raw.starts_with(['+', '-']),
+            member: raw.to_string(),
+            rate,
+        })
+    }
+}
+
+/// a metric aggregated over one flush window.
+/// `value` is the sum for counters, the latest value for gauges, the sum
+/// for timers and the number of unique members for sets
+#[derive(Serialize, Debug)]
+pub struct Metric {
+    kind: MetricKind,
+    value: f64,
+    count: u64,
+    min: f64,
+    max: f64,
+    #[serde(skip)]
+    members: HashSet<String>,
+}
+
+impl Metric {
+    pub fn new(kind: MetricKind) -> Self {
+        Self {
+            kind,
+            value: 0.0,
+            count: 0,
+            min: 0.0,
+            max: 0.0,
+            members: HashSet::new(),
+        }
+    }
+
+    pub fn kind(&self) -> MetricKind {
+        self.kind
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    pub fn set_value(&mut self, value: f64) {
+        self.value = value;
+    }
+
+    pub fn add(&mut self, sample: &Sample) {
+        match self.kind {
+            MetricKind::Counter => {
+                self.value += sample.value / sample.rate;
+                self.count += (1.0 / sample.rate).round() as u64;
+            }
+            MetricKind::Gauge => {
+                if sample.relative {
+                    self.value += sample.value;
+                } else {
+                    self.value = sample.value;
+                }
+                self.count += 1;
+            }
+            MetricKind::Timer => {
+                if self.count == 0 || sample.value < self.min {
+                    self.min = sample.value;
+                }
+                if self.count == 0 || sample.value > self.max {
+                    self.max = sample.value;
+                }
+                let count = (1.0 / sample.rate).round().max(1.0);
+                self.value += sample.value * count;
+                self.count += count as u64;
+            }
+            MetricKind::Set => {
+                self.members.insert(sample.member.clone());
+                self.value = self.members.len() as f64;
+                self.count += 1;
+            }
+        }
+    }
+}
+
+/// where apps send their statsd metrics. configured with `HEIMDALL_STATSD`,
+/// either a udp address (`127.0.0.1:8125`) or a unix socket path
+pub fn spawn(tx: mpsc::Sender<Event>) -> std::io::Result<()> {
+    let Ok(addr) = std::env::var("HEIMDALL_STATSD") else {
+        return Ok(());
+    };
+
+    if addr.starts_with('/') {
+        let _ = std::fs::remove_file(&addr);
+        let server = UnixDatagram::bind(&addr)?;
+        std::fs::set_permissions(&addr, Permissions::from_mode(0o777))?;
+        std::thread::spawn(move || {
+            listen(|buf| server.recv(buf).map_err(|e| e.to_string()), tx)
+        });
+    } else {
+        let server = UdpSocket::bind(&addr)?;
+        std::thread::spawn(move || {
+            listen(|buf| server.recv(buf).map_err(|e| e.to_string()), tx)
+        });
+    }
+
+    Ok(())
+}
+
+fn listen(
+    recv: impl Fn(&mut [u8]) -> Result<usize, String>, tx: mpsc::Sender<Event>,
+) {
+    let mut buf = vec![0u8; MAX_PACKET];
+    loop {
+        let size = match recv(buf.as_mut_slice()) {
+            Ok(s) => s,
+            Err(e) => {
+                println!("statsd recv error: {e}");
+                continue;
+            }
+        };
+
+        let Ok(packet) = std::str::from_utf8(&buf[..size]) else { continue };
+        for sample in packet.lines().filter_map(Sample::parse) {
+            if tx.send(Event::Metric(sample)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let s = Sample::parse("api.hits:3|c|@0.5|#env:prod").unwrap();
+        assert_eq!(s.name, "api.hits");
+        assert_eq!(s.kind, MetricKind::Counter);
+        assert_eq!(s.value, 3.0);
+        assert_eq!(s.rate, 0.5);
+
+        let s = Sample::parse("queue:-2|g\n").unwrap();
+        assert_eq!(s.kind, MetricKind::Gauge);
+        assert_eq!(s.value, -2.0);
+        assert!(s.relative);
+
+        let s = Sample::parse("users:alice|s").unwrap();
+        assert_eq!(s.kind, MetricKind::Set);
+        assert_eq!(s.member, "alice");
+
+        assert_eq!(Sample::parse("db:12.5|h").unwrap().kind, MetricKind::Timer);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for line in [
+            "",
+            "no-value",
+            ":1|c",
+            "hits:1",
+            "hits:1|x",
+            "hits:abc|c",
+            "hits:inf|ms",
+            "hits:1|c|@0",
+            "hits:1|c|@-1",
+        ] {
+            assert!(Sample::parse(line).is_none(), "{line:?}");
+        }
+        let long = format!("{}:1|c", "a".repeat(256));
+        assert!(Sample::parse(&long).is_none());
+    }
+
+    #[test]
+    fn aggregate() {
+        let add = |kind, lines: &[&str]| {
+            let mut metric = Metric::new(kind);
+            for line in lines {
+                metric.add(&Sample::parse(line).unwrap());
+            }
+            metric
+        };
+
+        let m = add(MetricKind::Counter, &["a:1|c", "a:2|c|@0.5"]);
+        assert_eq!((m.value, m.count), (5.0, 3));
+
+        let m = add(MetricKind::Gauge, &["a:10|g", "a:+5|g", "a:-3|g"]);
+        assert_eq!(m.value, 12.0);
+        let m = add(MetricKind::Gauge, &["a:10|g", "a:4|g"]);
+        assert_eq!(m.value, 4.0);
+
+        let m = add(MetricKind::Timer, &["a:30|ms", "a:10|ms", "a:20|ms"]);
+        assert_eq!((m.value, m.count, m.min, m.max), (60.0, 3, 10.0, 30.0));
+
+        let m = add(MetricKind::Set, &["a:x|s", "a:y|s", "a:x|s"]);
+        assert_eq!(m.value, 2.0);
+    }
+}
@@ -0,0 +1,64 @@
+use std::{
+    os::{
+        linux::net::SocketAddrExt,
+        unix::net::{SocketAddr, UnixDatagram},
+    },
+    time::{Duration, Instant},
+};
+
+/// send a state string (`READY=1`, `STATUS=...`, `WATCHDOG=1`) to systemd.
+/// does nothing when dog was not started by a `Type=notify` unit
+pub fn notify(state: &str) {
+    let Ok(path) = std::env::var("NOTIFY_SOCKET") else { return };
+
+    let addr = match path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name),
+        None => SocketAddr::from_pathname(&path),
+    };
+
+    let result = UnixDatagram::unbound().and_then(|sock| {
+        addr.and_then(|a| sock.send_to_addr(state.as_bytes(), &a))
+    });
+    if let Err(e) = result {
+        println!("could not notify systemd: {e}");
+    }
+}
+
+/// reports the status to systemd every 10 seconds and pings the watchdog
+/// at half of `WatchdogSec` when it is set. it has to be polled from the
+/// main loop, so a stuck loop stops the pings
+pub struct Watchdog {
+    interval: Duration,
+    watchdog: bool,
+    latest: Instant,
+}
+
+impl Watchdog {
+    pub fn from_env() -> Self {
+        let status = Duration::from_secs(10);
+        let watchdog = std::env::var("WATCHDOG_USEC")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .map(|v| Duration::from_micros(v / 2));
+
+        Self {
+            interval: watchdog.unwrap_or(status).min(status),
+            watchdog: watchdog.is_some(),
+            latest: Instant::now(),
+        }
+    }
+
+    pub fn poll(&mut self, status: impl FnOnce() -> String) {
+        if self.latest.elapsed() < self.interval {
+            return;
+        }
+
+        let status = status();
+        if self.watchdog {
+            notify(&format!("WATCHDOG=1\nSTATUS={status}"));
+        } else {
+            notify(&format!("STATUS={status}"));
+        }
+        self.latest = Instant::now();
+    }
+}
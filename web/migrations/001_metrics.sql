@@ -0,0 +1,14 @@
+create table if not exists sites_metrics (
+    id integer primary key not null,
+    site integer not null references sites(id) on delete cascade,
+    name text not null,
+    kind integer not null default 0, -- counter, gauge, timer, set
+    timestamp integer not null,
+    value real not null default 0,
+    count integer not null default 0,
+    min real not null default 0,
+    max real not null default 0
+);
+
+create index if not exists sites_metrics_site_name_timestamp
+on sites_metrics(site, name, timestamp);
@@ -0,0 +1,6 @@
+create table if not exists sites_instances (
+    site integer not null references sites(id) on delete cascade,
+    instance text not null,
+    seq integer not null default 0, -- latest applied dump
+    primary key (site, instance)
+);
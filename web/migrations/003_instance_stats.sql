@@ -0,0 +1,11 @@
+alter table sites_instances add column version text not null default '';
+alter table sites_instances add column latest_ping integer not null default 0;
+alter table sites_instances add column latest_dump integer not null default 0;
+alter table sites_instances add column total_requests integer not null default 0;
+alter table sites_instances add column total_requests_time integer not null default 0;
+alter table sites_instances add column requests_max_time integer not null default 0;
+alter table sites_instances add column requests_min_time integer not null default 0;
+alter table sites_instances add column status text not null default "{}";
+
+-- one of the instances of the site went silent
+alter table sites add column degraded boolean not null default false;
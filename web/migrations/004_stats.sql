@@ -0,0 +1,17 @@
+-- requests of each site bucketed by time. step is 60 for the minutes
+-- written by the dumps, 3600 and 86400 for the hours and days rolled up
+-- from them by the stats job
+create table if not exists sites_stats (
+    site integer not null references sites(id) on delete cascade,
+    step integer not null,
+    timestamp integer not null, -- bucket start
+    total integer not null default 0,
+    total_time integer not null default 0,
+    max_time integer not null default 0,
+    min_time integer not null default 0,
+    status text not null default "{}",
+    rolled boolean not null default false, -- included in the next step
+    primary key (site, step, timestamp)
+);
+
+create index if not exists sites_stats_step_rolled on sites_stats(step, rolled);
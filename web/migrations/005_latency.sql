@@ -0,0 +1,2 @@
+-- request count per latency bucket, see Config::LATENCY_BUCKETS
+alter table sites_stats add column latency text not null default "[]";
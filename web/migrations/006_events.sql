@@ -0,0 +1,14 @@
+-- things that happened to a site, e.g. anomalies found in its stats
+create table if not exists sites_events (
+    id integer primary key not null,
+    site integer not null references sites(id) on delete cascade,
+    timestamp integer not null,
+    kind integer not null default 0, -- traffic_drop, error_spike, ...
+    value real not null default 0,
+    baseline real not null default 0,
+    score real not null default 0,
+    text text not null default ""
+);
+
+create unique index if not exists sites_events_site_kind_timestamp
+on sites_events(site, kind, timestamp);
@@ -0,0 +1,28 @@
+-- conditions on the stats and pings of a site, evaluated every minute
+create table if not exists alert_rules (
+    id integer primary key not null,
+    site integer references sites(id) on delete cascade, -- null for all sites
+    name text not null,
+    metric integer not null default 0, -- requests, client_errors, ...
+    comparator integer not null default 0, -- gt, ge, lt, le
+    threshold real not null default 0,
+    period integer not null default 300, -- seconds of stats evaluated
+    duration integer not null default 0, -- seconds pending before firing
+    severity integer not null default 0, -- info, warning, critical
+    enabled boolean not null default true,
+    timestamp integer not null default 0
+);
+
+create table if not exists alerts (
+    id integer primary key not null,
+    rule integer not null references alert_rules(id) on delete cascade,
+    site integer not null references sites(id) on delete cascade,
+    state integer not null default 0, -- pending, firing, resolved
+    value real not null default 0,
+    started integer not null default 0,
+    fired integer not null default 0,
+    resolved integer not null default 0,
+    updated integer not null default 0
+);
+
+create index if not exists alerts_rule_site_state on alerts(rule, site, state);
@@ -0,0 +1,2 @@
+-- unknown, up, down. down when nothing arrived within OFFLINE_AFTER
+alter table sites add column health integer not null default 0;
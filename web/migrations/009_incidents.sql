@@ -0,0 +1,19 @@
+-- periods a site was down or failing, counted against its uptime
+create table if not exists incidents (
+    id integer primary key not null,
+    site integer not null references sites(id) on delete cascade,
+    cause integer not null default 0, -- missed_pings, probe_failure, ...
+    started integer not null,
+    ended integer not null default 0, -- 0 while it is open
+    text text not null default ""
+);
+
+create index if not exists incidents_site_started on incidents(site, started);
+
+create table if not exists incidents_notes (
+    id integer primary key not null,
+    incident integer not null references incidents(id) on delete cascade,
+    author integer references users(id) on delete set null,
+    timestamp integer not null,
+    text text not null
+);
@@ -0,0 +1,13 @@
+-- objectives on the share of good requests of a site
+create table if not exists slos (
+    id integer primary key not null,
+    site integer not null references sites(id) on delete cascade,
+    name text not null,
+    kind integer not null default 0, -- availability, latency
+    target real not null default 0.995, -- share of good requests, 0 to 1
+    threshold integer not null default 0, -- ms, for latency
+    days integer not null default 30,
+    burn_alert real not null default 14.4, -- 0 never alerts
+    burning boolean not null default false,
+    timestamp integer not null default 0
+);
@@ -0,0 +1,15 @@
+-- apdex threshold of the site in ms
+alter table sites add column apdex_t integer not null default 500;
+
+-- requests per route of a site in hour buckets, kept like the hour stats
+create table if not exists sites_routes (
+    site integer not null references sites(id) on delete cascade,
+    timestamp integer not null,
+    route text not null,
+    total integer not null default 0,
+    total_time integer not null default 0,
+    max_time integer not null default 0,
+    errors integer not null default 0, -- 5xx
+    latency text not null default "[]",
+    primary key (site, timestamp, route)
+);
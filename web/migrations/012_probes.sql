@@ -0,0 +1,31 @@
+-- http checks the web service runs against a site from the outside
+create table if not exists probes (
+    id integer primary key not null,
+    site integer not null references sites(id) on delete cascade,
+    name text not null,
+    url text not null,
+    method integer not null default 0, -- get, head, post, ...
+    status integer not null default 200, -- expected, 0 for any 2xx
+    body text not null default "", -- expected in the body, empty skips it
+    timeout integer not null default 10000, -- ms
+    interval integer not null default 60, -- seconds between the checks
+    failures integer not null default 3, -- in a row before an incident
+    enabled boolean not null default true,
+    streak integer not null default 0, -- current failures in a row
+    since integer not null default 0, -- first failure of the streak
+    checked integer not null default 0,
+    timestamp integer not null default 0
+);
+
+create table if not exists probes_results (
+    id integer primary key not null,
+    probe integer not null references probes(id) on delete cascade,
+    timestamp integer not null,
+    ok boolean not null,
+    status integer not null default 0, -- 0 without a response
+    latency integer not null default 0, -- ms
+    error text not null default ""
+);
+
+create index if not exists probes_results_probe on probes_results(probe, timestamp);
+create index if not exists probes_results_timestamp on probes_results(timestamp);
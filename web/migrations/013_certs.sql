@@ -0,0 +1,21 @@
+-- host and port the certificate of a site is read from, empty skips it
+alter table sites add column tls_host text not null default "";
+alter table sites add column tls_port integer not null default 443;
+alter table sites add column cert_status integer not null default 0;
+alter table sites add column cert_expires integer not null default 0;
+
+-- the latest certificate served for a site
+create table if not exists sites_certs (
+    site integer primary key not null references sites(id) on delete cascade,
+    host text not null,
+    port integer not null,
+    timestamp integer not null, -- when it was checked
+    subject text not null default "",
+    issuer text not null default "",
+    sans text not null default "[]",
+    chain text not null default "[]", -- subjects from the leaf up
+    not_before integer not null default 0,
+    not_after integer not null default 0,
+    error text not null default "", -- connection or verification error
+    warned integer not null default 0 -- smallest warning sent, in days
+);
@@ -0,0 +1,24 @@
+-- jobs that ping heimdall when they run, late or failed ones are reported
+create table if not exists heartbeats (
+    id integer primary key not null,
+    site integer references sites(id) on delete cascade, -- null for none
+    name text not null,
+    token text not null unique,
+    period integer not null default 3600, -- seconds between the runs
+    grace integer not null default 300, -- seconds a run may be late
+    state integer not null default 0, -- new, up, started, late, failed
+    last_ping integer not null default 0, -- the latest success or fail
+    last_start integer not null default 0,
+    timestamp integer not null default 0
+);
+
+create table if not exists heartbeats_pings (
+    id integer primary key not null,
+    heartbeat integer not null references heartbeats(id) on delete cascade,
+    timestamp integer not null,
+    kind integer not null default 0, -- success, start, fail
+    duration integer not null default 0, -- seconds since the start, 0 for none
+    output text not null default ""
+);
+
+create index if not exists heartbeats_pings_heartbeat on heartbeats_pings(heartbeat, id);
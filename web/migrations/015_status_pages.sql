@@ -0,0 +1,9 @@
+-- public pages with the health and uptime of a group of sites
+create table if not exists status_pages (
+    id integer primary key not null,
+    slug text not null unique, -- served at /status/{slug}/
+    title text not null,
+    description text not null default "",
+    sites text not null default "[]", -- ids in the order they are listed
+    timestamp integer not null default 0
+);
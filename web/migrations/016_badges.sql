@@ -0,0 +1,2 @@
+-- public key of the svg badges of a site, null while they are off
+alter table sites add column badge_key text;
@@ -0,0 +1,4 @@
+-- secret dog signs dumps with, unlike the token it is never sent
+alter table sites add column signing_key text;
+-- dumps without a valid signature are rejected when set
+alter table sites add column signature_required boolean not null default false;
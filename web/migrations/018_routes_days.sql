@@ -0,0 +1,39 @@
+-- the routes are kept per day like the day stats as well as per hour, a
+-- replayed log older than the hours still has its routes
+create table if not exists sites_routes_steps (
+    site integer not null references sites(id) on delete cascade,
+    step integer not null default 3600,
+    timestamp integer not null,
+    route text not null,
+    total integer not null default 0,
+    total_time integer not null default 0,
+    max_time integer not null default 0,
+    errors integer not null default 0, -- 5xx
+    latency text not null default "[]",
+    primary key (site, step, timestamp, route)
+);
+
+insert into sites_routes_steps(
+    site, step, timestamp, route, total, total_time, max_time, errors, latency
+)
+select site, 3600, timestamp, route, total, total_time, max_time, errors,
+    latency
+from sites_routes;
+
+insert into sites_routes_steps(
+    site, step, timestamp, route, total, total_time, max_time, errors, latency
+)
+select r.site, 86400, r.timestamp - r.timestamp % 86400 as day, r.route,
+    sum(r.total), sum(r.total_time), max(r.max_time), sum(r.errors), (
+        select json_group_array(n) from (
+            select sum(j.value) as n
+            from sites_routes h, json_each(h.latency) j
+            where h.site = r.site and h.route = r.route and
+            h.timestamp - h.timestamp % 86400 = r.timestamp - r.timestamp % 86400
+            group by j.key order by j.key
+        )
+    )
+from sites_routes r group by r.site, day, r.route;
+
+drop table sites_routes;
+alter table sites_routes_steps rename to sites_routes;
@@ -0,0 +1,3 @@
+-- distinct client addresses of each dump window as dog anonymized them,
+-- summed over the bucket
+alter table sites_stats add column visitors integer not null default 0;
@@ -0,0 +1,240 @@
+use actix_web::web::{Data, Json, Query};
+use actix_web::{delete, get, patch, post, HttpResponse, Scope};
+use serde::Deserialize;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::docs::UpdatePaths;
+use crate::models::alert::{
+    Alert, AlertComparator, AlertMetric, AlertRule, AlertSeverity, AlertState,
+};
+use crate::models::user::Admin;
+use crate::models::{bad_request, AppErr, ListInput, Response};
+use crate::utils::CutOff;
+use crate::{utils, AppState};
+
+#[derive(OpenApi)]
+#[openapi(
+    tags((name = "admin::alerts")),
+    paths(rule_list, rule_add, rule_update, rule_delete, alert_list),
+    components(schemas(
+        AlertRule, AlertRuleBody, Alert, AlertMetric, AlertComparator,
+        AlertSeverity, AlertState
+    )),
+    servers((url = "/alerts")),
+    modifiers(&UpdatePaths)
+)]
+pub struct ApiDoc;
+
+#[utoipa::path(
+    get,
+    params(ListInput),
+    responses((status = 200, body = Vec<AlertRule>))
+)]
+/// Rule List
+#[get("/rules/")]
+async fn rule_list(
+    _: Admin, q: Query<ListInput>, state: Data<AppState>,
+) -> Response<Vec<AlertRule>> {
+    let offset = q.page * 32;
+    let rules = sqlx::query_as! {
+        AlertRule,
+        "select * from alert_rules order by id desc limit 32 offset ?",
+        offset
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    Ok(Json(rules))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct AlertRuleBody {
+    /// leave empty for a rule on every online site
+    site: Option<i64>,
+    name: String,
+    metric: AlertMetric,
+    comparator: AlertComparator,
+    threshold: f64,
+    #[schema(example = 300)]
+    period: i64,
+    #[schema(example = 120)]
+    duration: i64,
+    severity: AlertSeverity,
+    enabled: bool,
+}
+
+impl AlertRuleBody {
+    async fn verify(&mut self, state: &AppState) -> Result<(), AppErr> {
+        self.name.cut_off(255);
+        if self.name.is_empty() {
+            return Err(bad_request!("name is empty"));
+        }
+
+        if !self.threshold.is_finite() {
+            return Err(bad_request!("invalid threshold"));
+        }
+
+        if self.period < 60 || self.period > 30 * 86400 {
+            return Err(bad_request!("period must be between 1m and 30d"));
+        }
+
+        if self.duration < 0 || self.duration > 7 * 86400 {
+            return Err(bad_request!("duration must be between 0 and 7d"));
+        }
+
+        if let Some(site) = self.site {
+            if !state.sites.lock().await.contains_key(&site) {
+                return Err(bad_request!("site was not found"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[utoipa::path(
+    post,
+    request_body = AlertRuleBody,
+    responses((status = 200, body = AlertRule))
+)]
+/// Rule Add
+#[post("/rules/")]
+async fn rule_add(
+    _: Admin, body: Json<AlertRuleBody>, state: Data<AppState>,
+) -> Response<AlertRule> {
+    let mut body = body;
+    body.verify(&state).await?;
+
+    let mut rule = AlertRule {
+        id: 0,
+        site: body.site,
+        name: body.name.clone(),
+        metric: body.metric.clone(),
+        comparator: body.comparator.clone(),
+        threshold: body.threshold,
+        period: body.period,
+        duration: body.duration,
+        severity: body.severity.clone(),
+        enabled: body.enabled,
+        timestamp: utils::now(),
+    };
+
+    let result = sqlx::query! {
+        "insert into alert_rules(
+            site, name, metric, comparator, threshold, period, duration,
+            severity, enabled, timestamp
+        ) values(?,?,?,?,?,?,?,?,?,?)",
+        rule.site, rule.name, rule.metric, rule.comparator, rule.threshold,
+        rule.period, rule.duration, rule.severity, rule.enabled,
+        rule.timestamp
+    }
+    .execute(&state.sql)
+    .await?;
+
+    rule.id = result.last_insert_rowid();
+    Ok(Json(rule))
+}
+
+#[utoipa::path(
+    patch,
+    params(("rule_id" = i64, Path, example = 1)),
+    request_body = AlertRuleBody,
+    responses((status = 200, body = AlertRule))
+)]
+/// Rule Update
+///
+/// the alerts of the rule are kept and evaluated with the new conditions
+#[patch("/rules/{rule_id}/")]
+async fn rule_update(
+    _: Admin, rule: AlertRule, body: Json<AlertRuleBody>, state: Data<AppState>,
+) -> Response<AlertRule> {
+    let mut body = body;
+    body.verify(&state).await?;
+
+    let mut rule = rule;
+    rule.site = body.site;
+    rule.name = body.name.clone();
+    rule.metric = body.metric.clone();
+    rule.comparator = body.comparator.clone();
+    rule.threshold = body.threshold;
+    rule.period = body.period;
+    rule.duration = body.duration;
+    rule.severity = body.severity.clone();
+    rule.enabled = body.enabled;
+
+    sqlx::query! {
+        "update alert_rules set site = ?, name = ?, metric = ?,
+        comparator = ?, threshold = ?, period = ?, duration = ?,
+        severity = ?, enabled = ? where id = ?",
+        rule.site, rule.name, rule.metric, rule.comparator, rule.threshold,
+        rule.period, rule.duration, rule.severity, rule.enabled, rule.id
+    }
+    .execute(&state.sql)
+    .await?;
+
+    Ok(Json(rule))
+}
+
+#[utoipa::path(
+    delete,
+    params(("rule_id" = i64, Path, example = 1)),
+    responses((status = 200))
+)]
+/// Rule Delete
+///
+/// the alerts of the rule are deleted with it
+#[delete("/rules/{rule_id}/")]
+async fn rule_delete(
+    _: Admin, rule: AlertRule, state: Data<AppState>,
+) -> Result<HttpResponse, AppErr> {
+    sqlx::query!("delete from alerts where rule = ?", rule.id)
+        .execute(&state.sql)
+        .await?;
+    sqlx::query!("delete from alert_rules where id = ?", rule.id)
+        .execute(&state.sql)
+        .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Deserialize, IntoParams)]
+struct AlertListQuery {
+    #[param(example = 0)]
+    page: u32,
+    state: Option<AlertState>,
+    site: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    params(AlertListQuery),
+    responses((status = 200, body = Vec<Alert>))
+)]
+/// Alert List
+///
+/// newest first, optionally only the alerts of a state or site
+#[get("/")]
+async fn alert_list(
+    _: Admin, q: Query<AlertListQuery>, state: Data<AppState>,
+) -> Response<Vec<Alert>> {
+    let offset = q.page * 32;
+    let alerts = sqlx::query_as! {
+        Alert,
+        "select * from alerts where (? is null or state = ?) and
+        (? is null or site = ?) order by id desc limit 32 offset ?",
+        q.state, q.state, q.site, q.site, offset
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    Ok(Json(alerts))
+}
+
+pub fn router() -> Scope {
+    Scope::new("/alerts")
+        .service(rule_list)
+        .service(rule_add)
+        .service(rule_update)
+        .service(rule_delete)
+        .service(alert_list)
+}
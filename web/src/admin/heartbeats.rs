@@ -0,0 +1,226 @@
+use actix_web::web::{Data, Json, Query};
+use actix_web::{delete, get, patch, post, HttpResponse, Scope};
+use serde::Deserialize;
+use utoipa::{OpenApi, ToSchema};
+
+use crate::config::Config;
+use crate::docs::UpdatePaths;
+use crate::models::heartbeat::{
+    Heartbeat, HeartbeatKind, HeartbeatPing, HeartbeatState,
+};
+use crate::models::user::Admin;
+use crate::models::{bad_request, AppErr, ListInput, Response};
+use crate::utils::CutOff;
+use crate::{utils, AppState};
+
+#[derive(OpenApi)]
+#[openapi(
+    tags((name = "admin::heartbeats")),
+    paths(list, add, update, del, ping_list),
+    components(schemas(
+        Heartbeat, HeartbeatState, HeartbeatPing, HeartbeatKind,
+        HeartbeatBody
+    )),
+    servers((url = "/heartbeats")),
+    modifiers(&UpdatePaths)
+)]
+pub struct ApiDoc;
+
+#[utoipa::path(
+    get,
+    params(ListInput),
+    responses((status = 200, body = Vec<Heartbeat>))
+)]
+/// List
+#[get("/")]
+async fn list(
+    _: Admin, q: Query<ListInput>, state: Data<AppState>,
+) -> Response<Vec<Heartbeat>> {
+    let offset = q.page * 32;
+    let heartbeats = sqlx::query_as! {
+        Heartbeat,
+        "select * from heartbeats order by id desc limit 32 offset ?",
+        offset
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    Ok(Json(heartbeats))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct HeartbeatBody {
+    /// the site the job belongs to, if any
+    site: Option<i64>,
+    name: String,
+    /// seconds between the runs
+    #[schema(example = 3600)]
+    period: i64,
+    /// seconds a ping may be late, and a started run may take
+    #[schema(example = 300)]
+    grace: i64,
+    /// a new ping url, only for updates
+    #[serde(default)]
+    token: bool,
+}
+
+impl HeartbeatBody {
+    async fn verify(&mut self, state: &AppState) -> Result<(), AppErr> {
+        self.name.cut_off(255);
+        if self.name.is_empty() {
+            return Err(bad_request!("name is empty"));
+        }
+
+        if self.period < 60 || self.period > 30 * 86400 {
+            return Err(bad_request!("period must be between 1m and 30d"));
+        }
+
+        if self.grace < 10 || self.grace > 7 * 86400 {
+            return Err(bad_request!("grace must be between 10s and 7d"));
+        }
+
+        if let Some(site) = self.site {
+            if !state.sites.lock().await.contains_key(&site) {
+                return Err(bad_request!("site was not found"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn new_token() -> String {
+    utils::get_random_string(Config::SITE_TOKEN_ABC, 32)
+}
+
+#[utoipa::path(
+    post,
+    request_body = HeartbeatBody,
+    responses((status = 200, body = Heartbeat))
+)]
+/// Add
+///
+/// the job pings `/api/heartbeats/{token}/` with the returned token
+#[post("/")]
+async fn add(
+    _: Admin, body: Json<HeartbeatBody>, state: Data<AppState>,
+) -> Response<Heartbeat> {
+    let mut body = body;
+    body.verify(&state).await?;
+
+    let mut heartbeat = Heartbeat {
+        id: 0,
+        site: body.site,
+        name: body.name.clone(),
+        token: new_token(),
+        period: body.period,
+        grace: body.grace,
+        state: HeartbeatState::New,
+        last_ping: 0,
+        last_start: 0,
+        timestamp: utils::now(),
+    };
+
+    let result = sqlx::query! {
+        "insert into heartbeats(site, name, token, period, grace, timestamp)
+        values(?,?,?,?,?,?)",
+        heartbeat.site, heartbeat.name, heartbeat.token, heartbeat.period,
+        heartbeat.grace, heartbeat.timestamp
+    }
+    .execute(&state.sql)
+    .await?;
+
+    heartbeat.id = result.last_insert_rowid();
+    Ok(Json(heartbeat))
+}
+
+#[utoipa::path(
+    patch,
+    params(("heartbeat_id" = i64, Path, example = 1)),
+    request_body = HeartbeatBody,
+    responses((status = 200, body = Heartbeat))
+)]
+/// Update
+#[patch("/{heartbeat_id}/")]
+async fn update(
+    _: Admin, heartbeat: Heartbeat, body: Json<HeartbeatBody>,
+    state: Data<AppState>,
+) -> Response<Heartbeat> {
+    let mut body = body;
+    body.verify(&state).await?;
+
+    let mut heartbeat = heartbeat;
+    heartbeat.site = body.site;
+    heartbeat.name = body.name.clone();
+    heartbeat.period = body.period;
+    heartbeat.grace = body.grace;
+    if body.token {
+        heartbeat.token = new_token();
+    }
+
+    sqlx::query! {
+        "update heartbeats set site = ?, name = ?, token = ?, period = ?,
+        grace = ? where id = ?",
+        heartbeat.site, heartbeat.name, heartbeat.token, heartbeat.period,
+        heartbeat.grace, heartbeat.id
+    }
+    .execute(&state.sql)
+    .await?;
+
+    Ok(Json(heartbeat))
+}
+
+#[utoipa::path(
+    delete,
+    params(("heartbeat_id" = i64, Path, example = 1)),
+    responses((status = 200))
+)]
+/// Delete
+#[delete("/{heartbeat_id}/")]
+async fn del(
+    _: Admin, heartbeat: Heartbeat, state: Data<AppState>,
+) -> Result<HttpResponse, AppErr> {
+    sqlx::query!(
+        "delete from heartbeats_pings where heartbeat = ?",
+        heartbeat.id
+    )
+    .execute(&state.sql)
+    .await?;
+    sqlx::query!("delete from heartbeats where id = ?", heartbeat.id)
+        .execute(&state.sql)
+        .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[utoipa::path(
+    get,
+    params(("heartbeat_id" = i64, Path, example = 1), ListInput),
+    responses((status = 200, body = Vec<HeartbeatPing>))
+)]
+/// Ping List
+#[get("/{heartbeat_id}/pings/")]
+async fn ping_list(
+    _: Admin, heartbeat: Heartbeat, q: Query<ListInput>, state: Data<AppState>,
+) -> Response<Vec<HeartbeatPing>> {
+    let offset = q.page * 32;
+    let pings = sqlx::query_as! {
+        HeartbeatPing,
+        "select * from heartbeats_pings where heartbeat = ?
+        order by id desc limit 32 offset ?",
+        heartbeat.id, offset
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    Ok(Json(pings))
+}
+
+pub fn router() -> Scope {
+    Scope::new("/heartbeats")
+        .service(list)
+        .service(add)
+        .service(update)
+        .service(del)
+        .service(ping_list)
+}
@@ -0,0 +1,216 @@
+use actix_web::web::{Data, Json, Query};
+use actix_web::{delete, get, patch, post, HttpResponse, Scope};
+use serde::Deserialize;
+use utoipa::{OpenApi, ToSchema};
+
+use crate::docs::UpdatePaths;
+use crate::models::incident::{Incident, IncidentCause, IncidentNote};
+use crate::models::user::Admin;
+use crate::models::{bad_request, AppErr, ListInput, Response};
+use crate::utils::CutOff;
+use crate::{utils, AppState};
+
+#[derive(OpenApi)]
+#[openapi(
+    tags((name = "admin::incidents")),
+    paths(add, update, del, note_list, note_add),
+    components(schemas(
+        Incident, IncidentCause, IncidentNote, IncidentAddBody,
+        IncidentUpdateBody, IncidentNoteAddBody
+    )),
+    servers((url = "/incidents")),
+    modifiers(&UpdatePaths)
+)]
+pub struct ApiDoc;
+
+fn verify_range(started: i64, ended: i64) -> Result<(), AppErr> {
+    if started <= 0 || started > utils::now() {
+        return Err(bad_request!("invalid started"));
+    }
+
+    if ended != 0 && ended < started {
+        return Err(bad_request!("ended must be after started or 0"));
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize, ToSchema)]
+struct IncidentAddBody {
+    site: i64,
+    started: i64,
+    /// 0 for an incident that is still going on
+    ended: i64,
+    text: String,
+}
+
+#[utoipa::path(
+    post,
+    request_body = IncidentAddBody,
+    responses((status = 200, body = Incident))
+)]
+/// Add
+///
+/// a manual incident, it is counted against the uptime like the others
+#[post("/")]
+async fn add(
+    _: Admin, body: Json<IncidentAddBody>, state: Data<AppState>,
+) -> Response<Incident> {
+    let mut body = body;
+    body.text.cut_off(1024);
+    verify_range(body.started, body.ended)?;
+    if !state.sites.lock().await.contains_key(&body.site) {
+        return Err(bad_request!("site was not found"));
+    }
+
+    let cause = IncidentCause::Manual;
+    let result = sqlx::query! {
+        "insert into incidents(site, cause, started, ended, text)
+        values(?,?,?,?,?)",
+        body.site, cause, body.started, body.ended, body.text
+    }
+    .execute(&state.sql)
+    .await?;
+
+    Ok(Json(Incident {
+        id: result.last_insert_rowid(),
+        site: body.site,
+        cause,
+        started: body.started,
+        ended: body.ended,
+        text: body.text.clone(),
+    }))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct IncidentUpdateBody {
+    started: i64,
+    ended: i64,
+    text: String,
+}
+
+#[utoipa::path(
+    patch,
+    params(("incident_id" = i64, Path, example = 1)),
+    request_body = IncidentUpdateBody,
+    responses((status = 200, body = Incident))
+)]
+/// Update
+///
+/// correct the range of an incident or close it
+#[patch("/{incident_id}/")]
+async fn update(
+    _: Admin, incident: Incident, body: Json<IncidentUpdateBody>,
+    state: Data<AppState>,
+) -> Response<Incident> {
+    let mut incident = incident;
+    verify_range(body.started, body.ended)?;
+    incident.started = body.started;
+    incident.ended = body.ended;
+    incident.text = body.text.clone();
+    incident.text.cut_off(1024);
+
+    sqlx::query! {
+        "update incidents set started = ?, ended = ?, text = ? where id = ?",
+        incident.started, incident.ended, incident.text, incident.id
+    }
+    .execute(&state.sql)
+    .await?;
+
+    Ok(Json(incident))
+}
+
+#[utoipa::path(
+    delete,
+    params(("incident_id" = i64, Path, example = 1)),
+    responses((status = 200))
+)]
+/// Delete
+///
+/// for false alarms, the downtime is no longer counted
+#[delete("/{incident_id}/")]
+async fn del(
+    _: Admin, incident: Incident, state: Data<AppState>,
+) -> Result<HttpResponse, AppErr> {
+    sqlx::query!("delete from incidents_notes where incident = ?", incident.id)
+        .execute(&state.sql)
+        .await?;
+    sqlx::query!("delete from incidents where id = ?", incident.id)
+        .execute(&state.sql)
+        .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[utoipa::path(
+    get,
+    params(("incident_id" = i64, Path, example = 1), ListInput),
+    responses((status = 200, body = Vec<IncidentNote>))
+)]
+/// Note List
+#[get("/{incident_id}/notes/")]
+async fn note_list(
+    _: Admin, incident: Incident, q: Query<ListInput>, state: Data<AppState>,
+) -> Response<Vec<IncidentNote>> {
+    let offset = q.page * 32;
+    let notes = sqlx::query_as! {
+        IncidentNote,
+        "select * from incidents_notes where incident = ?
+        order by id desc limit 32 offset ?",
+        incident.id, offset
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    Ok(Json(notes))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct IncidentNoteAddBody {
+    text: String,
+}
+
+#[utoipa::path(
+    post,
+    params(("incident_id" = i64, Path, example = 1)),
+    request_body = IncidentNoteAddBody,
+    responses((status = 200, body = IncidentNote))
+)]
+/// Note Add
+#[post("/{incident_id}/notes/")]
+async fn note_add(
+    admin: Admin, incident: Incident, body: Json<IncidentNoteAddBody>,
+    state: Data<AppState>,
+) -> Response<IncidentNote> {
+    let mut note = IncidentNote {
+        id: 0,
+        incident: incident.id,
+        author: Some(admin.id),
+        timestamp: utils::now(),
+        text: body.text.clone(),
+    };
+    note.text.cut_off(2048);
+    if note.text.is_empty() {
+        return Err(bad_request!("text is empty"));
+    }
+
+    let result = sqlx::query! {
+        "insert into incidents_notes(incident, author, timestamp, text)
+        values(?,?,?,?)",
+        note.incident, note.author, note.timestamp, note.text
+    }
+    .execute(&state.sql)
+    .await?;
+
+    note.id = result.last_insert_rowid();
+    Ok(Json(note))
+}
+
+pub fn router() -> Scope {
+    Scope::new("/incidents")
+        .service(add)
+        .service(update)
+        .service(del)
+        .service(note_list)
+        .service(note_add)
+}
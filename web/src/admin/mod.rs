@@ -1,4 +1,10 @@
 // pub mod product;
 // pub mod product_tag;
 // pub mod users;
+pub mod alerts;
+pub mod heartbeats;
+pub mod incidents;
+pub mod probes;
 pub mod sites;
+pub mod slos;
+pub mod status;
@@ -0,0 +1,249 @@
+use actix_web::web::{Data, Json, Query};
+use actix_web::{delete, get, patch, post, HttpResponse, Scope};
+use serde::Deserialize;
+use utoipa::{OpenApi, ToSchema};
+
+use crate::docs::UpdatePaths;
+use crate::models::probe::{Probe, ProbeMethod, ProbeResult};
+use crate::models::user::Admin;
+use crate::models::{bad_request, AppErr, ListInput, Response};
+use crate::utils::CutOff;
+use crate::{probe, utils, AppState};
+
+#[derive(OpenApi)]
+#[openapi(
+    tags((name = "admin::probes")),
+    paths(list, add, update, del, run),
+    components(schemas(Probe, ProbeMethod, ProbeResult, ProbeBody)),
+    servers((url = "/probes")),
+    modifiers(&UpdatePaths)
+)]
+pub struct ApiDoc;
+
+#[utoipa::path(
+    get,
+    params(ListInput),
+    responses((status = 200, body = Vec<Probe>))
+)]
+/// List
+#[get("/")]
+async fn list(
+    _: Admin, q: Query<ListInput>, state: Data<AppState>,
+) -> Response<Vec<Probe>> {
+    let offset = q.page * 32;
+    let probes = sqlx::query_as! {
+        Probe,
+        "select * from probes order by id desc limit 32 offset ?",
+        offset
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    Ok(Json(probes))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ProbeBody {
+    site: i64,
+    name: String,
+    #[schema(example = "https://example.com/health/")]
+    url: String,
+    method: ProbeMethod,
+    /// 0 accepts any 2xx
+    #[schema(example = 200)]
+    status: i64,
+    /// text the body has to contain, empty skips the check
+    body: String,
+    /// ms
+    #[schema(example = 10000)]
+    timeout: i64,
+    /// seconds
+    #[schema(example = 60)]
+    interval: i64,
+    #[schema(example = 3)]
+    failures: i64,
+    enabled: bool,
+}
+
+impl ProbeBody {
+    async fn verify(&mut self, state: &AppState) -> Result<(), AppErr> {
+        self.name.cut_off(255);
+        if self.name.is_empty() {
+            return Err(bad_request!("name is empty"));
+        }
+
+        // plain http is allowed for probes of local and internal services
+        let url = self.url.to_lowercase();
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(bad_request!("url must be http or https"));
+        }
+        if self.url.len() > 2048 {
+            return Err(bad_request!("url is too long"));
+        }
+
+        if self.status != 0 && !(100..600).contains(&self.status) {
+            return Err(bad_request!("invalid status"));
+        }
+        self.body.cut_off(1024);
+
+        if self.timeout < 100 || self.timeout > 60_000 {
+            return Err(bad_request!("timeout must be between 100ms and 60s"));
+        }
+
+        if self.interval < 10 || self.interval > 86400 {
+            return Err(bad_request!("interval must be between 10s and 1d"));
+        }
+
+        if self.failures < 1 || self.failures > 100 {
+            return Err(bad_request!("failures must be between 1 and 100"));
+        }
+
+        if !state.sites.lock().await.contains_key(&self.site) {
+            return Err(bad_request!("site was not found"));
+        }
+
+        Ok(())
+    }
+}
+
+#[utoipa::path(
+    post,
+    request_body = ProbeBody,
+    responses((status = 200, body = Probe))
+)]
+/// Add
+#[post("/")]
+async fn add(
+    _: Admin, body: Json<ProbeBody>, state: Data<AppState>,
+) -> Response<Probe> {
+    let mut body = body;
+    body.verify(&state).await?;
+
+    let mut probe = Probe {
+        id: 0,
+        site: body.site,
+        name: body.name.clone(),
+        url: body.url.clone(),
+        method: body.method.clone(),
+        status: body.status,
+        body: body.body.clone(),
+        timeout: body.timeout,
+        interval: body.interval,
+        failures: body.failures,
+        enabled: body.enabled,
+        streak: 0,
+        since: 0,
+        checked: 0,
+        timestamp: utils::now(),
+    };
+
+    let result = sqlx::query! {
+        "insert into probes(
+            site, name, url, method, status, body, timeout, interval,
+            failures, enabled, timestamp
+        ) values(?,?,?,?,?,?,?,?,?,?,?)",
+        probe.site, probe.name, probe.url, probe.method, probe.status,
+        probe.body, probe.timeout, probe.interval, probe.failures,
+        probe.enabled, probe.timestamp
+    }
+    .execute(&state.sql)
+    .await?;
+
+    probe.id = result.last_insert_rowid();
+    Ok(Json(probe))
+}
+
+#[utoipa::path(
+    patch,
+    params(("probe_id" = i64, Path, example = 1)),
+    request_body = ProbeBody,
+    responses((status = 200, body = Probe))
+)]
+/// Update
+///
+/// the failure streak is kept, the incident of the site follows the change
+#[patch("/{probe_id}/")]
+async fn update(
+    _: Admin, probe: Probe, body: Json<ProbeBody>, state: Data<AppState>,
+) -> Response<Probe> {
+    let mut body = body;
+    body.verify(&state).await?;
+
+    let mut probe = probe;
+    let old_site = probe.site;
+    probe.site = body.site;
+    probe.name = body.name.clone();
+    probe.url = body.url.clone();
+    probe.method = body.method.clone();
+    probe.status = body.status;
+    probe.body = body.body.clone();
+    probe.timeout = body.timeout;
+    probe.interval = body.interval;
+    probe.failures = body.failures;
+    probe.enabled = body.enabled;
+
+    sqlx::query! {
+        "update probes set site = ?, name = ?, url = ?, method = ?,
+        status = ?, body = ?, timeout = ?, interval = ?, failures = ?,
+        enabled = ? where id = ?",
+        probe.site, probe.name, probe.url, probe.method, probe.status,
+        probe.body, probe.timeout, probe.interval, probe.failures,
+        probe.enabled, probe.id
+    }
+    .execute(&state.sql)
+    .await?;
+
+    let now = utils::now();
+    probe::settle(&state.sql, old_site, now).await?;
+    probe::settle(&state.sql, probe.site, now).await?;
+
+    Ok(Json(probe))
+}
+
+#[utoipa::path(
+    delete,
+    params(("probe_id" = i64, Path, example = 1)),
+    responses((status = 200))
+)]
+/// Delete
+#[delete("/{probe_id}/")]
+async fn del(
+    _: Admin, probe: Probe, state: Data<AppState>,
+) -> Result<HttpResponse, AppErr> {
+    sqlx::query!("delete from probes_results where probe = ?", probe.id)
+        .execute(&state.sql)
+        .await?;
+    sqlx::query!("delete from probes where id = ?", probe.id)
+        .execute(&state.sql)
+        .await?;
+
+    probe::settle(&state.sql, probe.site, utils::now()).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[utoipa::path(
+    post,
+    params(("probe_id" = i64, Path, example = 1)),
+    responses((status = 200, body = ProbeResult))
+)]
+/// Run
+///
+/// run the probe now, the result is stored like a scheduled one
+#[post("/{probe_id}/run/")]
+async fn run(
+    _: Admin, probe: Probe, state: Data<AppState>,
+) -> Response<ProbeResult> {
+    let mut result = probe::run(&probe).await;
+    probe::record(&state, &probe, &mut result).await?;
+    Ok(Json(result))
+}
+
+pub fn router() -> Scope {
+    Scope::new("/probes")
+        .service(list)
+        .service(add)
+        .service(update)
+        .service(del)
+        .service(run)
+}
@@ -1,20 +1,23 @@
-use actix_web::web::{Data, Json};
+use actix_web::web::{Data, Json, Path};
 use actix_web::{delete, patch, post, HttpResponse, Scope};
 use serde::Deserialize;
 use utoipa::{OpenApi, ToSchema};
 
 use crate::config::Config;
 use crate::docs::UpdatePaths;
+use crate::models::site::{CertStatus, SiteCert};
 use crate::models::user::Admin;
-use crate::models::AppErr;
+use crate::models::{bad_request, not_found, AppErr};
 use crate::models::{site::Site, Response};
-use crate::{utils, AppState};
+use crate::{cert, utils, AppState};
 
 #[derive(OpenApi)]
 #[openapi(
     tags((name = "admin::sites")),
-    paths(add, update, reset, del),
-    components(schemas(Site, SitesAddBody, SitesUpdateBody)),
+    paths(add, update, reset, del, cert_check, instance_del),
+    components(schemas(
+        Site, SitesAddBody, SitesUpdateBody, SiteCert, CertStatus
+    )),
     servers((url = "/sites")),
     modifiers(&UpdatePaths)
 )]
@@ -49,6 +52,8 @@ async fn add(
         id: result.last_insert_rowid(),
         name: body.name.clone(),
         timestamp: now,
+        apdex_t: Config::APDEX_T,
+        tls_port: Config::TLS_PORT,
         ..Default::default()
     };
     let mut sites = state.sites.lock().await;
@@ -62,6 +67,23 @@ struct SitesUpdateBody {
     name: String,
     token: bool,
     online: bool,
+    /// apdex threshold in ms, unchanged when missing
+    apdex_t: Option<i64>,
+    /// host the certificate is read from, empty skips the check.
+    /// unchanged when missing
+    #[schema(example = "example.com")]
+    tls_host: Option<String>,
+    /// unchanged when missing
+    #[schema(example = 443)]
+    tls_port: Option<i64>,
+    /// true turns the badges on with a new key, false turns them off.
+    /// unchanged when missing
+    badges: Option<bool>,
+    /// true makes a new key for signing dumps, false removes it.
+    /// unchanged when missing
+    signing_key: Option<bool>,
+    /// reject unsigned dumps, needs a signing key. unchanged when missing
+    signature_required: Option<bool>,
 }
 
 #[utoipa::path(
@@ -80,13 +102,64 @@ async fn update(
 
     site.online = body.online;
     site.name = body.name.clone();
+    if let Some(t) = body.apdex_t {
+        if !(1..=60_000).contains(&t) {
+            return Err(bad_request!("apdex_t must be between 1 and 60000"));
+        }
+        site.apdex_t = t;
+    }
+    match body.signing_key {
+        Some(true) => {
+            let key = utils::get_random_string(Config::SITE_TOKEN_ABC, 41);
+            site.signing_key = Some(key);
+        }
+        Some(false) => site.signing_key = None,
+        None => {}
+    }
+    if let Some(required) = body.signature_required {
+        site.signature_required = required;
+    }
+    if site.signature_required && site.signing_key.is_none() {
+        return Err(bad_request!("signature_required needs a signing_key"));
+    }
+    let tls = (site.tls_host.clone(), site.tls_port);
+    if let Some(host) = &body.tls_host {
+        Site::verify_host(host)?;
+        site.tls_host = host.clone();
+    }
+    if let Some(port) = body.tls_port {
+        if !(1..=65535).contains(&port) {
+            return Err(bad_request!("tls_port must be between 1 and 65535"));
+        }
+        site.tls_port = port;
+    }
+    // the certificate of another host is not this one
+    if tls != (site.tls_host.clone(), site.tls_port) {
+        site.cert_status = CertStatus::Unknown;
+        site.cert_expires = 0;
+        sqlx::query!("delete from sites_certs where site = ?", site.id)
+            .execute(&state.sql)
+            .await?;
+    }
     if body.token {
         site.token = Some(utils::get_random_string(Config::SITE_TOKEN_ABC, 41));
     }
+    match body.badges {
+        Some(true) => {
+            let key = utils::get_random_string(Config::SITE_TOKEN_ABC, 24);
+            site.badge_key = Some(key);
+        }
+        Some(false) => site.badge_key = None,
+        None => {}
+    }
 
     sqlx::query! {
-        "update sites set name = ?, token = ?, online = ? where id = ?",
-        site.name, site.token, site.online, site.id
+        "update sites set name = ?, token = ?, online = ?, apdex_t = ?,
+        tls_host = ?, tls_port = ?, cert_status = ?, cert_expires = ?,
+        badge_key = ?, signing_key = ?, signature_required = ? where id = ?",
+        site.name, site.token, site.online, site.apdex_t, site.tls_host,
+        site.tls_port, site.cert_status, site.cert_expires, site.badge_key,
+        site.signing_key, site.signature_required, site.id
     }
     .execute(&state.sql)
     .await?;
@@ -96,6 +169,14 @@ async fn update(
     state_site.name = site.name.clone();
     state_site.token = site.token.clone();
     state_site.online = site.online;
+    state_site.apdex_t = site.apdex_t;
+    state_site.tls_host = site.tls_host.clone();
+    state_site.tls_port = site.tls_port;
+    state_site.cert_status = site.cert_status.clone();
+    state_site.cert_expires = site.cert_expires;
+    state_site.badge_key = site.badge_key.clone();
+    state_site.signing_key = site.signing_key.clone();
+    state_site.signature_required = site.signature_required;
 
     Ok(Json(site))
 }
@@ -156,10 +237,63 @@ async fn del(
     Ok(HttpResponse::Ok().finish())
 }
 
+#[utoipa::path(
+    post,
+    params(("site_id" = i64, Path, example = 1)),
+    responses((status = 200, body = SiteCert))
+)]
+/// Cert Check
+///
+/// read the certificate of the site now instead of waiting for the hourly
+/// check
+#[post("/{site_id}/cert/")]
+async fn cert_check(
+    _: Admin, site: Site, state: Data<AppState>,
+) -> Response<SiteCert> {
+    if site.tls_host.is_empty() {
+        return Err(bad_request!("site has no tls_host"));
+    }
+
+    Ok(Json(cert::check(&state, &site).await?))
+}
+
+#[utoipa::path(
+    delete,
+    params(
+        ("site_id" = i64, Path, example = 1),
+        ("instance" = String, Path, example = "web-1"),
+    ),
+    responses((status = 200))
+)]
+/// Instance Delete
+///
+/// forget an instance that was shut down for good, a silent one keeps the
+/// site degraded until then
+#[delete("/{site_id}/instances/{instance}/")]
+async fn instance_del(
+    _: Admin, site: Site, path: Path<(i64, String)>, state: Data<AppState>,
+) -> Result<HttpResponse, AppErr> {
+    let instance = &path.1;
+    let result = sqlx::query! {
+        "delete from sites_instances where site = ? and instance = ?",
+        site.id, instance
+    }
+    .execute(&state.sql)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(not_found!("no instance was found"));
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
 pub fn router() -> Scope {
     Scope::new("/sites")
         .service(add)
         .service(update)
         .service(reset)
         .service(del)
+        .service(cert_check)
+        .service(instance_del)
 }
@@ -0,0 +1,165 @@
+use actix_web::web::{Data, Json};
+use actix_web::{delete, patch, post, HttpResponse, Scope};
+use serde::Deserialize;
+use utoipa::{OpenApi, ToSchema};
+
+use crate::docs::UpdatePaths;
+use crate::models::slo::{Slo, SloKind};
+use crate::models::user::Admin;
+use crate::models::{bad_request, AppErr, Response};
+use crate::utils::CutOff;
+use crate::{utils, AppState};
+
+#[derive(OpenApi)]
+#[openapi(
+    tags((name = "admin::slos")),
+    paths(add, update, del),
+    components(schemas(Slo, SloKind, SloBody)),
+    servers((url = "/slos")),
+    modifiers(&UpdatePaths)
+)]
+pub struct ApiDoc;
+
+#[derive(Deserialize, ToSchema)]
+struct SloBody {
+    site: i64,
+    name: String,
+    kind: SloKind,
+    #[schema(example = 0.995)]
+    target: f64,
+    /// ms, only for latency
+    #[schema(example = 300)]
+    threshold: i64,
+    #[schema(example = 30)]
+    days: i64,
+    /// 0 never reports a fast burn
+    #[schema(example = 14.4)]
+    burn_alert: f64,
+}
+
+impl SloBody {
+    async fn verify(&mut self, state: &AppState) -> Result<(), AppErr> {
+        self.name.cut_off(255);
+        if self.name.is_empty() {
+            return Err(bad_request!("name is empty"));
+        }
+
+        if !(self.target > 0.0 && self.target < 1.0) {
+            return Err(bad_request!("target must be between 0 and 1"));
+        }
+
+        if self.kind == SloKind::Latency && self.threshold <= 0 {
+            return Err(bad_request!("latency slos need a threshold"));
+        }
+
+        if self.days < 1 || self.days > 365 {
+            return Err(bad_request!("days must be between 1 and 365"));
+        }
+
+        if !(self.burn_alert >= 0.0 && self.burn_alert.is_finite()) {
+            return Err(bad_request!("invalid burn_alert"));
+        }
+
+        if !state.sites.lock().await.contains_key(&self.site) {
+            return Err(bad_request!("site was not found"));
+        }
+
+        Ok(())
+    }
+}
+
+#[utoipa::path(
+    post,
+    request_body = SloBody,
+    responses((status = 200, body = Slo))
+)]
+/// Add
+#[post("/")]
+async fn add(
+    _: Admin, body: Json<SloBody>, state: Data<AppState>,
+) -> Response<Slo> {
+    let mut body = body;
+    body.verify(&state).await?;
+
+    let mut slo = Slo {
+        id: 0,
+        site: body.site,
+        name: body.name.clone(),
+        kind: body.kind.clone(),
+        target: body.target,
+        threshold: body.threshold,
+        days: body.days,
+        burn_alert: body.burn_alert,
+        burning: false,
+        timestamp: utils::now(),
+    };
+
+    let result = sqlx::query! {
+        "insert into slos(
+            site, name, kind, target, threshold, days, burn_alert, timestamp
+        ) values(?,?,?,?,?,?,?,?)",
+        slo.site, slo.name, slo.kind, slo.target, slo.threshold, slo.days,
+        slo.burn_alert, slo.timestamp
+    }
+    .execute(&state.sql)
+    .await?;
+
+    slo.id = result.last_insert_rowid();
+    Ok(Json(slo))
+}
+
+#[utoipa::path(
+    patch,
+    params(("slo_id" = i64, Path, example = 1)),
+    request_body = SloBody,
+    responses((status = 200, body = Slo))
+)]
+/// Update
+#[patch("/{slo_id}/")]
+async fn update(
+    _: Admin, slo: Slo, body: Json<SloBody>, state: Data<AppState>,
+) -> Response<Slo> {
+    let mut body = body;
+    body.verify(&state).await?;
+
+    let mut slo = slo;
+    slo.site = body.site;
+    slo.name = body.name.clone();
+    slo.kind = body.kind.clone();
+    slo.target = body.target;
+    slo.threshold = body.threshold;
+    slo.days = body.days;
+    slo.burn_alert = body.burn_alert;
+
+    sqlx::query! {
+        "update slos set site = ?, name = ?, kind = ?, target = ?,
+        threshold = ?, days = ?, burn_alert = ? where id = ?",
+        slo.site, slo.name, slo.kind, slo.target, slo.threshold, slo.days,
+        slo.burn_alert, slo.id
+    }
+    .execute(&state.sql)
+    .await?;
+
+    Ok(Json(slo))
+}
+
+#[utoipa::path(
+    delete,
+    params(("slo_id" = i64, Path, example = 1)),
+    responses((status = 200))
+)]
+/// Delete
+#[delete("/{slo_id}/")]
+async fn del(
+    _: Admin, slo: Slo, state: Data<AppState>,
+) -> Result<HttpResponse, AppErr> {
+    sqlx::query!("delete from slos where id = ?", slo.id)
+        .execute(&state.sql)
+        .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+pub fn router() -> Scope {
+    Scope::new("/slos").service(add).service(update).service(del)
+}
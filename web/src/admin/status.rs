@@ -0,0 +1,197 @@
+use actix_web::web::{Data, Json, Query};
+use actix_web::{delete, get, patch, post, HttpResponse, Scope};
+use serde::Deserialize;
+use utoipa::{OpenApi, ToSchema};
+
+use crate::docs::UpdatePaths;
+use crate::models::status::StatusPage;
+use crate::models::user::Admin;
+use crate::models::{bad_request, AppErr, JsonStr, ListInput, Response};
+use crate::utils::CutOff;
+use crate::{utils, AppState};
+
+#[derive(OpenApi)]
+#[openapi(
+    tags((name = "admin::status")),
+    paths(list, add, update, del),
+    components(schemas(StatusPage, StatusPageBody)),
+    servers((url = "/status")),
+    modifiers(&UpdatePaths)
+)]
+pub struct ApiDoc;
+
+#[utoipa::path(
+    get,
+    params(ListInput),
+    responses((status = 200, body = Vec<StatusPage>))
+)]
+/// List
+#[get("/")]
+async fn list(
+    _: Admin, q: Query<ListInput>, state: Data<AppState>,
+) -> Response<Vec<StatusPage>> {
+    let offset = q.page * 32;
+    let pages = sqlx::query_as! {
+        StatusPage,
+        "select * from status_pages order by id desc limit 32 offset ?",
+        offset
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    Ok(Json(pages))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct StatusPageBody {
+    /// the page is served at `/status/{slug}/`
+    #[schema(example = "main")]
+    slug: String,
+    title: String,
+    description: String,
+    /// ids of the sites in the order they are listed
+    sites: Vec<i64>,
+}
+
+impl StatusPageBody {
+    async fn verify(&mut self, state: &AppState) -> Result<(), AppErr> {
+        if self.slug.is_empty() || self.slug.len() > 64 {
+            return Err(bad_request!("invalid slug length > 0 && <= 64"));
+        }
+
+        let valid = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit();
+        if !self.slug.chars().all(|c| valid(c) || c == '-') {
+            return Err(bad_request!("slug must be lowercase, digits or -"));
+        }
+
+        self.title.cut_off(255);
+        if self.title.is_empty() {
+            return Err(bad_request!("title is empty"));
+        }
+        self.description.cut_off(2048);
+
+        let mut seen = std::collections::HashSet::new();
+        self.sites.retain(|id| seen.insert(*id));
+        if self.sites.len() > 64 {
+            return Err(bad_request!("a page can list up to 64 sites"));
+        }
+
+        let sites = state.sites.lock().await;
+        if self.sites.iter().any(|id| !sites.contains_key(id)) {
+            return Err(bad_request!("site was not found"));
+        }
+
+        Ok(())
+    }
+}
+
+async fn slug_taken(
+    state: &AppState, slug: &str, id: i64,
+) -> Result<(), AppErr> {
+    let taken = sqlx::query! {
+        "select id from status_pages where slug = ? and id != ?",
+        slug, id
+    }
+    .fetch_optional(&state.sql)
+    .await?;
+
+    match taken {
+        Some(_) => Err(bad_request!("slug is taken")),
+        None => Ok(()),
+    }
+}
+
+#[utoipa::path(
+    post,
+    request_body = StatusPageBody,
+    responses((status = 200, body = StatusPage))
+)]
+/// Add
+#[post("/")]
+async fn add(
+    _: Admin, body: Json<StatusPageBody>, state: Data<AppState>,
+) -> Response<StatusPage> {
+    let mut body = body;
+    body.verify(&state).await?;
+    slug_taken(&state, &body.slug, 0).await?;
+
+    let mut page = StatusPage {
+        id: 0,
+        slug: body.slug.clone(),
+        title: body.title.clone(),
+        description: body.description.clone(),
+        sites: JsonStr(body.sites.clone()),
+        timestamp: utils::now(),
+    };
+
+    let result = sqlx::query! {
+        "insert into status_pages(slug, title, description, sites, timestamp)
+        values(?,?,?,?,?)",
+        page.slug, page.title, page.description, page.sites, page.timestamp
+    }
+    .execute(&state.sql)
+    .await?;
+
+    page.id = result.last_insert_rowid();
+    Ok(Json(page))
+}
+
+#[utoipa::path(
+    patch,
+    params(("page_id" = i64, Path, example = 1)),
+    request_body = StatusPageBody,
+    responses((status = 200, body = StatusPage))
+)]
+/// Update
+#[patch("/{page_id}/")]
+async fn update(
+    _: Admin, page: StatusPage, body: Json<StatusPageBody>,
+    state: Data<AppState>,
+) -> Response<StatusPage> {
+    let mut body = body;
+    body.verify(&state).await?;
+    slug_taken(&state, &body.slug, page.id).await?;
+
+    state.status.lock().await.remove(&page.slug);
+    let mut page = page;
+    page.slug = body.slug.clone();
+    page.title = body.title.clone();
+    page.description = body.description.clone();
+    page.sites = JsonStr(body.sites.clone());
+
+    sqlx::query! {
+        "update status_pages set slug = ?, title = ?, description = ?,
+        sites = ? where id = ?",
+        page.slug, page.title, page.description, page.sites, page.id
+    }
+    .execute(&state.sql)
+    .await?;
+
+    Ok(Json(page))
+}
+
+#[utoipa::path(
+    delete,
+    params(("page_id" = i64, Path, example = 1)),
+    responses((status = 200))
+)]
+/// Delete
+#[delete("/{page_id}/")]
+async fn del(
+    _: Admin, page: StatusPage, state: Data<AppState>,
+) -> Result<HttpResponse, AppErr> {
+    sqlx::query!("delete from status_pages where id = ?", page.id)
+        .execute(&state.sql)
+        .await?;
+
+    state.status.lock().await.remove(&page.slug);
+    Ok(HttpResponse::Ok().finish())
+}
+
+pub fn router() -> Scope {
+    Scope::new("/status")
+        .service(list)
+        .service(add)
+        .service(update)
+        .service(del)
+}
@@ -0,0 +1,396 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use crate::models::alert::{
+    Alert, AlertMetric, AlertRule, AlertSeverity, AlertState,
+};
+use crate::models::incident::IncidentCause;
+use crate::models::site::{Site, SiteSummary};
+use crate::{incident, stats, utils, AppState};
+
+fn metric(metric: &AlertMetric, summary: &SiteSummary) -> Option<f64> {
+    match metric {
+        AlertMetric::Requests => Some(summary.total as f64),
+        AlertMetric::ClientErrors => Some(summary.error_rate_4xx),
+        AlertMetric::ServerErrors => Some(summary.error_rate_5xx),
+        AlertMetric::MeanTime => Some(summary.mean_time),
+        AlertMetric::P95 => summary.p95.map(|v| v as f64),
+        AlertMetric::SincePing => None,
+    }
+}
+
+fn message(rule: &AlertRule, site: &Site, value: f64, state: &str) -> String {
+    let value = (value * 1000.0).round() / 1000.0;
+    format!(
+        "[{}] site {}: {} is {state}, {value} {} {}",
+        format!("{:?}", rule.severity).to_lowercase(),
+        site.name,
+        rule.name,
+        rule.comparator.symbol(),
+        rule.threshold
+    )
+}
+
+/// move the alert of a rule and site along with the latest value of the
+/// metric. returns the notification for firing and resolved alerts
+async fn transition(
+    state: &AppState, rule: &AlertRule, site: &Site, alert: Option<Alert>,
+    value: f64, now: i64,
+) -> Result<Option<String>, sqlx::Error> {
+    let holds = rule.comparator.check(value, rule.threshold);
+    let Some(alert) = alert else {
+        if !holds {
+            return Ok(None);
+        }
+
+        let firing = rule.duration <= 0;
+        let (alert_state, fired) = match firing {
+            true => (AlertState::Firing, now),
+            false => (AlertState::Pending, 0),
+        };
+        sqlx::query! {
+            "insert into alerts(
+                rule, site, state, value, started, fired, updated
+            ) values(?,?,?,?,?,?,?)",
+            rule.id, site.id, alert_state, value, now, fired, now
+        }
+        .execute(&state.sql)
+        .await?;
+
+        return Ok(firing.then(|| message(rule, site, value, "firing")));
+    };
+
+    if holds {
+        let fire = alert.state == AlertState::Pending
+            && now - alert.started >= rule.duration;
+        let (alert_state, fired) = match fire {
+            true => (AlertState::Firing, now),
+            false => (alert.state, alert.fired),
+        };
+        sqlx::query! {
+            "update alerts set state = ?, fired = ?, value = ?, updated = ?
+            where id = ?",
+            alert_state, fired, value, now, alert.id
+        }
+        .execute(&state.sql)
+        .await?;
+
+        return Ok(fire.then(|| message(rule, site, value, "firing")));
+    }
+
+    let fired = alert.state == AlertState::Firing;
+    resolve(state, &alert, value, now).await?;
+    Ok(fired.then(|| message(rule, site, value, "resolved")))
+}
+
+async fn resolve(
+    state: &AppState, alert: &Alert, value: f64, now: i64,
+) -> Result<(), sqlx::Error> {
+    // a pending alert that never fired is not worth keeping
+    if alert.state == AlertState::Pending {
+        sqlx::query!("delete from alerts where id = ?", alert.id)
+            .execute(&state.sql)
+            .await?;
+        return Ok(());
+    }
+
+    sqlx::query! {
+        "update alerts set state = ?, value = ?, resolved = ?, updated = ?
+        where id = ?",
+        AlertState::Resolved, value, now, now, alert.id
+    }
+    .execute(&state.sql)
+    .await?;
+
+    Ok(())
+}
+
+/// evaluate the enabled rules against every online site they apply to
+/// and notify about the alerts that fired or resolved. a site that fails
+/// is logged and skipped, the others are still checked
+pub async fn check(state: &AppState) -> Result<(), sqlx::Error> {
+    let now = utils::now();
+    // the current minute is still being dumped
+    let to = now - now.rem_euclid(60);
+
+    let rules = sqlx::query_as! {
+        AlertRule,
+        "select * from alert_rules where enabled = true"
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    let resolved = AlertState::Resolved;
+    let mut active = sqlx::query_as! {
+        Alert,
+        "select * from alerts where state != ?",
+        resolved
+    }
+    .fetch_all(&state.sql)
+    .await?
+    .into_iter()
+    .map(|a| ((a.rule, a.site), a))
+    .collect::<HashMap<_, _>>();
+
+    let sites = state
+        .sites
+        .lock()
+        .await
+        .values()
+        .filter(|s| s.online)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let mut summaries = HashMap::<(i64, i64), SiteSummary>::new();
+    for rule in rules.iter() {
+        for site in
+            sites.iter().filter(|s| rule.site.is_none_or(|id| id == s.id))
+        {
+            let alert = active.remove(&(rule.id, site.id));
+            let value = match rule.metric {
+                AlertMetric::SincePing => (site.latest_ping > 0)
+                    .then_some((now - site.latest_ping) as f64),
+                _ => {
+                    let summary = match summaries.entry((site.id, rule.period))
+                    {
+                        Entry::Occupied(e) => e.into_mut(),
+                        Entry::Vacant(e) => {
+                            let from = to - rule.period;
+                            match stats::summary(&state.sql, site.id, from, to)
+                                .await
+                            {
+                                Ok(v) => e.insert(v),
+                                Err(err) => {
+                                    log::error!(
+                                        "alert summary of site {}: {err:?}",
+                                        site.id
+                                    );
+                                    continue;
+                                }
+                            }
+                        }
+                    };
+                    metric(&rule.metric, summary)
+                }
+            };
+
+            // without data the alert stays as it is
+            let Some(value) = value else { continue };
+            // the transition is saved, a later failure must not lose it
+            match transition(state, rule, site, alert, value, now).await {
+                Ok(Some(text)) => utils::send_message(&text).await,
+                Ok(None) => {}
+                Err(e) => log::error!(
+                    "alert rule {} of site {}: {e:?}",
+                    rule.id,
+                    site.id
+                ),
+            }
+        }
+    }
+
+    // left from rules that were disabled or sites that went offline
+    for alert in active.into_values() {
+        if let Err(e) = resolve(state, &alert, alert.value, now).await {
+            log::error!("alert {}: {e:?}", alert.id);
+        }
+    }
+
+    breaches(state, now).await
+}
+
+/// keep an error rate incident open on the sites with a firing critical
+/// `server_errors` alert, from the first one that fired
+async fn breaches(state: &AppState, now: i64) -> Result<(), sqlx::Error> {
+    let (firing, metric, severity) = (
+        AlertState::Firing,
+        AlertMetric::ServerErrors,
+        AlertSeverity::Critical,
+    );
+    let rows = sqlx::query! {
+        "select alerts.site, alerts.fired from alerts
+        join alert_rules on alert_rules.id = alerts.rule
+        where alerts.state = ? and alert_rules.metric = ? and
+        alert_rules.severity = ?",
+        firing, metric, severity
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    let mut sites = HashMap::<i64, i64>::new();
+    for row in rows {
+        let fired = sites.entry(row.site).or_insert(row.fired);
+        *fired = row.fired.min(*fired);
+    }
+
+    let cause = IncidentCause::ErrorRate;
+    let open = sqlx::query_scalar! {
+        "select site from incidents where cause = ? and ended = 0",
+        cause
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    for site in open.into_iter().filter(|s| !sites.contains_key(s)) {
+        incident::close(&state.sql, site, cause.clone(), now).await?;
+    }
+    for (site, fired) in sites {
+        let text = "5xx rate breached a critical alert";
+        incident::open(&state.sql, site, cause.clone(), fired, text).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::alert::AlertComparator;
+
+    async fn state() -> AppState {
+        let state = utils::test_state().await;
+        sqlx::query!("insert into sites(id, name) values(1, 'test')")
+            .execute(&state.sql)
+            .await
+            .unwrap();
+        state
+    }
+
+    async fn rule(state: &AppState, rule: AlertRule) -> AlertRule {
+        sqlx::query! {
+            "insert into alert_rules(
+                id, name, metric, comparator, threshold, duration
+            ) values(?,?,?,?,?,?)",
+            rule.id, rule.name, rule.metric, rule.comparator, rule.threshold,
+            rule.duration
+        }
+        .execute(&state.sql)
+        .await
+        .unwrap();
+        rule
+    }
+
+    async fn alert(state: &AppState, rule: i64) -> Option<Alert> {
+        sqlx::query_as!(Alert, "select * from alerts where rule = ?", rule)
+            .fetch_optional(&state.sql)
+            .await
+            .unwrap()
+    }
+
+    fn errors(id: i64, duration: i64) -> AlertRule {
+        AlertRule {
+            id,
+            name: "errors".to_string(),
+            metric: AlertMetric::ServerErrors,
+            comparator: AlertComparator::Gt,
+            threshold: 0.1,
+            duration,
+            ..Default::default()
+        }
+    }
+
+    #[actix_web::test]
+    async fn pending_firing_resolved() {
+        let state = state().await;
+        let site =
+            Site { id: 1, name: "test".to_string(), ..Default::default() };
+        let rule = rule(&state, errors(1, 120)).await;
+        let (state, rule, site) = (&state, &rule, &site);
+        let step = |value, now| async move {
+            let alert = alert(state, 1).await;
+            transition(state, rule, site, alert, value, now).await.unwrap()
+        };
+
+        assert_eq!(step(0.05, 1000).await, None);
+        assert!(alert(state, 1).await.is_none());
+
+        assert_eq!(step(0.5, 1000).await, None);
+        let a = alert(state, 1).await.unwrap();
+        assert_eq!(
+            (a.state, a.started, a.fired),
+            (AlertState::Pending, 1000, 0)
+        );
+
+        assert_eq!(step(0.4, 1060).await, None);
+        assert_eq!(alert(state, 1).await.unwrap().state, AlertState::Pending);
+
+        let text = step(0.3, 1120).await.unwrap();
+        assert!(text.contains("errors is firing"), "{text}");
+        let a = alert(state, 1).await.unwrap();
+        assert_eq!(
+            (a.state, a.fired, a.value),
+            (AlertState::Firing, 1120, 0.3)
+        );
+
+        // firing is only sent once
+        assert_eq!(step(0.3, 1180).await, None);
+
+        let text = step(0.0, 1240).await.unwrap();
+        assert!(text.contains("errors is resolved"), "{text}");
+        let a = alert(state, 1).await.unwrap();
+        assert_eq!((a.state, a.resolved), (AlertState::Resolved, 1240));
+    }
+
+    #[actix_web::test]
+    async fn pending_dropped_and_instant_firing() {
+        let state = state().await;
+        let site =
+            Site { id: 1, name: "test".to_string(), ..Default::default() };
+
+        // a pending alert that stops holding is gone without a message
+        let slow = rule(&state, errors(1, 120)).await;
+        assert_eq!(
+            transition(&state, &slow, &site, None, 0.5, 0).await.unwrap(),
+            None
+        );
+        let pending = alert(&state, 1).await;
+        assert_eq!(
+            transition(&state, &slow, &site, pending, 0.0, 60).await.unwrap(),
+            None
+        );
+        assert!(alert(&state, 1).await.is_none());
+
+        // without a duration it fires right away
+        let fast = rule(&state, errors(2, 0)).await;
+        let text =
+            transition(&state, &fast, &site, None, 0.5, 0).await.unwrap();
+        assert!(text.is_some_and(|v| v.contains("firing")));
+        assert_eq!(alert(&state, 2).await.unwrap().state, AlertState::Firing);
+    }
+
+    #[actix_web::test]
+    async fn check_since_ping() {
+        let state = state().await;
+        let now = utils::now();
+        let site = Site {
+            id: 1,
+            name: "test".to_string(),
+            online: true,
+            latest_ping: now - 600,
+            ..Default::default()
+        };
+        state.sites.lock().await.insert(1, site);
+        let mut ping = errors(1, 0);
+        ping.metric = AlertMetric::SincePing;
+        ping.threshold = 300.0;
+        rule(&state, ping).await;
+
+        check(&state).await.unwrap();
+        assert_eq!(alert(&state, 1).await.unwrap().state, AlertState::Firing);
+
+        state.sites.lock().await.get_mut(&1).unwrap().latest_ping = now;
+        check(&state).await.unwrap();
+        assert_eq!(alert(&state, 1).await.unwrap().state, AlertState::Resolved);
+
+        // a site that went offline has its alerts resolved as well
+        state.sites.lock().await.get_mut(&1).unwrap().latest_ping = now - 600;
+        check(&state).await.unwrap();
+        state.sites.lock().await.get_mut(&1).unwrap().online = false;
+        check(&state).await.unwrap();
+        let alerts = sqlx::query_as!(Alert, "select * from alerts")
+            .fetch_all(&state.sql)
+            .await
+            .unwrap();
+        assert_eq!(alerts.len(), 2);
+        assert!(alerts.iter().all(|a| a.state == AlertState::Resolved));
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+
+use crate::models::site::{SiteEventKind, SiteStat};
+use crate::{utils, AppState};
+
+const WEEK: i64 = 7 * 86400;
+/// weeks of the same hour-of-week the baseline is made of
+const WEEKS: i64 = 8;
+/// fewer weeks of history than this and the hour is not checked
+const MIN_WEEKS: usize = 4;
+/// robust z-score past which a value is an anomaly
+const THRESHOLD: f64 = 3.5;
+/// hours with fewer requests are too noisy for error rate and latency
+const MIN_REQUESTS: i64 = 20;
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let n = values.len();
+    match n {
+        0 => 0.0,
+        // the same middle value twice when n is odd
+        _ => (values[(n - 1) / 2] + values[n / 2]) / 2.0,
+    }
+}
+
+/// median of the baseline and the z-score of `value` against it, using the
+/// median absolute deviation. `floor` keeps a flat baseline from turning
+/// every small change into an anomaly
+fn score(value: f64, baseline: &[f64], floor: f64) -> (f64, f64) {
+    let mut values = baseline.to_vec();
+    let center = median(&mut values);
+    let mut deviations =
+        values.iter().map(|v| (v - center).abs()).collect::<Vec<_>>();
+    let mad = median(&mut deviations) * 1.4826;
+    (center, (value - center) / mad.max(floor))
+}
+
+fn error_rate(stat: &SiteStat) -> f64 {
+    let errors = stat
+        .status
+        .values()
+        .filter(|s| s.code >= 500)
+        .map(|s| s.count)
+        .sum::<u64>();
+    errors as f64 / stat.total.max(1) as f64
+}
+
+fn latency(stat: &SiteStat) -> f64 {
+    stat.total_time as f64 / stat.total.max(1) as f64
+}
+
+struct Anomaly {
+    kind: SiteEventKind,
+    value: f64,
+    baseline: f64,
+    score: f64,
+    text: String,
+}
+
+/// compare an hour of a site with the same hour-of-week of the past weeks
+fn detect(hour: &SiteStat, history: &[&SiteStat]) -> Vec<Anomaly> {
+    let mut found = Vec::new();
+
+    // hours without a stat had no requests
+    let requests = history.iter().map(|s| s.total as f64).collect::<Vec<_>>();
+    let value = hour.total as f64;
+    let (baseline, z) = score(value, &requests, 1f64.max(value * 0.05));
+    if baseline >= MIN_REQUESTS as f64 && z <= -THRESHOLD {
+        found.push(Anomaly {
+            kind: SiteEventKind::TrafficDrop,
+            value,
+            baseline,
+            score: z,
+            text: format!("requests dropped to {value} from ~{baseline:.0}"),
+        });
+    }
+
+    if hour.total < MIN_REQUESTS {
+        return found;
+    }
+
+    let busy = history
+        .iter()
+        .filter(|s| s.total >= MIN_REQUESTS)
+        .copied()
+        .collect::<Vec<_>>();
+    if busy.len() < MIN_WEEKS {
+        return found;
+    }
+
+    let value = error_rate(hour);
+    let rates = busy.iter().map(|s| error_rate(s)).collect::<Vec<_>>();
+    let (baseline, z) = score(value, &rates, 0.005);
+    if value >= 0.01 && z >= THRESHOLD {
+        found.push(Anomaly {
+            kind: SiteEventKind::ErrorSpike,
+            value,
+            baseline,
+            score: z,
+            text: format!(
+                "5xx rate rose to {:.1}% from ~{:.1}%",
+                value * 100.0,
+                baseline * 100.0
+            ),
+        });
+    }
+
+    let value = latency(hour);
+    let times = busy.iter().map(|s| latency(s)).collect::<Vec<_>>();
+    let (baseline, z) = score(value, &times, 5f64.max(value * 0.05));
+    if z >= THRESHOLD {
+        found.push(Anomaly {
+            kind: SiteEventKind::LatencyJump,
+            value,
+            baseline,
+            score: z,
+            text: format!(
+                "mean latency rose to {value:.0}ms from ~{baseline:.0}ms"
+            ),
+        });
+    }
+
+    found
+}
+
+/// check the hour starting at `hour` of every online site, record the
+/// anomalies as site events and notify about the new ones
+pub async fn check(state: &AppState, hour: i64) -> Result<(), sqlx::Error> {
+    let since = hour - WEEKS * WEEK;
+    let stats = sqlx::query_as! {
+        SiteStat,
+        "select * from sites_stats where step = 3600 and timestamp >= ? and
+        timestamp <= ? and (? - timestamp) % ? = 0",
+        since, hour, hour, WEEK
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    let mut by_site = HashMap::<i64, Vec<SiteStat>>::new();
+    for stat in stats {
+        by_site.entry(stat.site).or_default().push(stat);
+    }
+
+    let sites = state
+        .sites
+        .lock()
+        .await
+        .values()
+        .filter(|s| s.online)
+        .map(|s| (s.id, s.name.clone(), s.timestamp))
+        .collect::<Vec<_>>();
+
+    for (id, name, created) in sites {
+        // weeks before the site existed are not counted as empty
+        let weeks = ((hour - created.max(since)) / WEEK).min(WEEKS);
+        let stats = by_site.remove(&id).unwrap_or_default();
+        // only weeks the site has stats for count toward the minimum, a
+        // site that never reported has no baseline to compare against
+        let recorded = stats.iter().filter(|s| s.timestamp != hour).count();
+        if recorded < MIN_WEEKS {
+            continue;
+        }
+
+        let empty =
+            SiteStat { site: id, timestamp: hour, ..Default::default() };
+        let current =
+            stats.iter().find(|s| s.timestamp == hour).unwrap_or(&empty);
+        let empties = (0..weeks)
+            .map(|w| SiteStat {
+                timestamp: hour - (w + 1) * WEEK,
+                ..empty.clone()
+            })
+            .collect::<Vec<_>>();
+        let history = empties
+            .iter()
+            .map(|e| {
+                stats.iter().find(|s| s.timestamp == e.timestamp).unwrap_or(e)
+            })
+            .collect::<Vec<_>>();
+
+        for anomaly in detect(current, &history) {
+            let result = sqlx::query! {
+                "insert or ignore into sites_events(
+                    site, timestamp, kind, value, baseline, score, text
+                ) values(?,?,?,?,?,?,?)",
+                id, hour, anomaly.kind, anomaly.value, anomaly.baseline,
+                anomaly.score, anomaly.text
+            }
+            .execute(&state.sql)
+            .await?;
+
+            if result.rows_affected() == 1 {
+                let text = format!("site {name}: {}", anomaly.text);
+                utils::send_message(&text).await;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::site::Status;
+    use crate::models::JsonStr;
+
+    /// an hour with `errors` 5xx responses and a mean latency of `ms`
+    fn stat(total: i64, errors: u64, ms: i64) -> SiteStat {
+        let mut status = HashMap::new();
+        for (code, count) in [(200, total as u64 - errors), (502, errors)] {
+            let s =
+                Status { code, count, max_time: 0, min_time: 0, total_time: 0 };
+            status.insert(code.to_string(), s);
+        }
+        SiteStat {
+            total,
+            total_time: total * ms,
+            status: JsonStr(status),
+            ..Default::default()
+        }
+    }
+
+    fn kinds(hour: &SiteStat, history: &[SiteStat]) -> Vec<SiteEventKind> {
+        let history = history.iter().collect::<Vec<_>>();
+        detect(hour, &history).into_iter().map(|a| a.kind).collect()
+    }
+
+    #[test]
+    fn robust_score() {
+        assert_eq!(median(&mut [3.0, 1.0, 2.0]), 2.0);
+        assert_eq!(median(&mut [4.0, 1.0, 3.0, 2.0]), 2.5);
+        assert_eq!(median(&mut []), 0.0);
+
+        let (center, z) = score(20.0, &[10.0, 12.0, 11.0, 13.0, 9.0], 0.0);
+        assert_eq!(center, 11.0);
+        assert!((z - 9.0 / 1.4826).abs() < 1e-9);
+
+        // a flat baseline falls back to the floor
+        let (center, z) = score(11.0, &[10.0; 6], 0.5);
+        assert_eq!((center, z), (10.0, 2.0));
+    }
+
+    #[test]
+    fn detects() {
+        let history =
+            (0..6).map(|w| stat(1000 + w * 10, 10, 50 + w)).collect::<Vec<_>>();
+
+        assert!(kinds(&stat(1020, 10, 52), &history).is_empty());
+        assert_eq!(
+            kinds(&stat(100, 1, 50), &history),
+            [SiteEventKind::TrafficDrop]
+        );
+        assert_eq!(
+            kinds(&stat(1020, 150, 52), &history),
+            [SiteEventKind::ErrorSpike]
+        );
+        assert_eq!(
+            kinds(&stat(1020, 10, 400), &history),
+            [SiteEventKind::LatencyJump]
+        );
+    }
+
+    #[test]
+    fn needs_busy_weeks() {
+        // quiet weeks are no baseline for the error rate and latency
+        let mut history = vec![stat(1000, 10, 50); 3];
+        history.extend(vec![stat(5, 0, 50); 3]);
+        assert!(kinds(&stat(1000, 300, 900), &history).is_empty());
+    }
+}
@@ -0,0 +1,125 @@
+use actix_web::http::header;
+use actix_web::web::{Data, Query};
+use actix_web::{get, HttpResponse, Scope};
+use serde::Deserialize;
+use utoipa::{IntoParams, OpenApi};
+
+use crate::config::Config;
+use crate::docs::UpdatePaths;
+use crate::models::site::Site;
+use crate::models::{not_found, AppErr};
+use crate::{badge, incident, utils, AppState};
+
+#[derive(OpenApi)]
+#[openapi(
+    tags((name = "api::badges")),
+    paths(status, uptime, latency),
+    servers((url = "/badges")),
+    modifiers(&UpdatePaths)
+)]
+pub struct ApiDoc;
+
+#[derive(Deserialize, IntoParams)]
+struct BadgeQuery {
+    /// the `badge_key` of the site
+    #[param(example = "<badge_key>")]
+    key: String,
+}
+
+/// compare in constant time so the key can not be guessed byte by byte
+/// from the response times
+fn verify(site: &Site, q: &BadgeQuery) -> Result<(), AppErr> {
+    let Some(key) = site.badge_key.as_deref() else {
+        return Err(not_found!("no badge was found"));
+    };
+
+    let (a, b) = (key.as_bytes(), q.key.as_bytes());
+    let diff = a.iter().zip(b).fold(0u8, |d, (x, y)| d | (x ^ y));
+    match a.len() == b.len() && diff == 0 {
+        true => Ok(()),
+        false => Err(not_found!("no badge was found")),
+    }
+}
+
+fn svg(body: String) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("image/svg+xml")
+        // readme images are proxied and cached unless told otherwise
+        .insert_header((header::CACHE_CONTROL, "no-cache, max-age=0"))
+        .body(body)
+}
+
+#[utoipa::path(
+    get,
+    params(("site_id" = i64, Path, example = 1), BadgeQuery),
+    responses((status = 200, content_type = "image/svg+xml", body = String))
+)]
+/// Status
+///
+/// up, degraded, down or unknown
+#[get("/{site_id}/status.svg")]
+async fn status(
+    site: Site, q: Query<BadgeQuery>,
+) -> Result<HttpResponse, AppErr> {
+    verify(&site, &q)?;
+    Ok(svg(badge::status(&site)))
+}
+
+#[utoipa::path(
+    get,
+    params(("site_id" = i64, Path, example = 1), BadgeQuery),
+    responses((status = 200, content_type = "image/svg+xml", body = String))
+)]
+/// Uptime
+///
+/// from the incidents of the last `Config::BADGE_UPTIME_DAYS` days
+#[get("/{site_id}/uptime.svg")]
+async fn uptime(
+    site: Site, q: Query<BadgeQuery>, state: Data<AppState>,
+) -> Result<HttpResponse, AppErr> {
+    verify(&site, &q)?;
+    let now = utils::now();
+    let from = now - Config::BADGE_UPTIME_DAYS * 86400;
+    let uptime = incident::uptime(&state.sql, site.id, from, now).await?;
+    Ok(svg(badge::uptime(uptime.uptime)))
+}
+
+#[utoipa::path(
+    get,
+    params(("site_id" = i64, Path, example = 1), BadgeQuery),
+    responses((status = 200, content_type = "image/svg+xml", body = String))
+)]
+/// Latency
+///
+/// mean response time since the last reset of the site
+#[get("/{site_id}/latency.svg")]
+async fn latency(
+    site: Site, q: Query<BadgeQuery>,
+) -> Result<HttpResponse, AppErr> {
+    verify(&site, &q)?;
+    Ok(svg(badge::latency(&site)))
+}
+
+pub fn router() -> Scope {
+    Scope::new("/badges").service(status).service(uptime).service(latency)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_check() {
+        let query = |key: &str| BadgeQuery { key: key.to_string() };
+        let mut site = Site::default();
+        assert!(verify(&site, &query("")).is_err());
+
+        site.badge_key = Some("k3y-of-the-site".to_string());
+        assert!(verify(&site, &query("k3y-of-the-site")).is_ok());
+        assert!(verify(&site, &query("k3y-of-the-sitf")).is_err());
+        // a prefix or a longer key is not the key
+        assert!(verify(&site, &query("k3y-of")).is_err());
+        assert!(verify(&site, &query("k3y-of-the-site!")).is_err());
+        assert!(verify(&site, &query("")).is_err());
+    }
+}
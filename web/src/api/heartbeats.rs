@@ -0,0 +1,87 @@
+use actix_web::web::{Bytes, Data, Path};
+use actix_web::{route, HttpResponse, Scope};
+use utoipa::OpenApi;
+
+use crate::docs::UpdatePaths;
+use crate::models::heartbeat::{Heartbeat, HeartbeatKind};
+use crate::models::{not_found, AppErr};
+use crate::{heartbeat, AppState};
+
+#[derive(OpenApi)]
+#[openapi(
+    tags((name = "api::heartbeats")),
+    paths(success, start, fail),
+    servers((url = "/heartbeats")),
+    modifiers(&UpdatePaths)
+)]
+pub struct ApiDoc;
+
+async fn ping(
+    token: &str, kind: HeartbeatKind, body: &[u8], state: &AppState,
+) -> Result<HttpResponse, AppErr> {
+    let heartbeat = sqlx::query_as! {
+        Heartbeat,
+        "select * from heartbeats where token = ?",
+        token
+    }
+    .fetch_optional(&state.sql)
+    .await?
+    .ok_or(not_found!("no heartbeat was found"))?;
+
+    heartbeat::ping(state, heartbeat, kind, body).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[utoipa::path(
+    post,
+    path = "/{token}/",
+    params(("token" = String, Path, example = "<token>")),
+    request_body(content = String, content_type = "text/plain"),
+    responses((status = 200))
+)]
+/// Success
+///
+/// the job ran, GET works too. the body is kept as its output
+#[route("/{token}/", method = "GET", method = "POST")]
+async fn success(
+    token: Path<String>, body: Bytes, state: Data<AppState>,
+) -> Result<HttpResponse, AppErr> {
+    ping(&token, HeartbeatKind::Success, &body, &state).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/{token}/start/",
+    params(("token" = String, Path, example = "<token>")),
+    responses((status = 200))
+)]
+/// Start
+///
+/// the job started, it is late unless it ends within the grace
+#[route("/{token}/start/", method = "GET", method = "POST")]
+async fn start(
+    token: Path<String>, body: Bytes, state: Data<AppState>,
+) -> Result<HttpResponse, AppErr> {
+    ping(&token, HeartbeatKind::Start, &body, &state).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/{token}/fail/",
+    params(("token" = String, Path, example = "<token>")),
+    request_body(content = String, content_type = "text/plain"),
+    responses((status = 200))
+)]
+/// Fail
+///
+/// the job failed, the body is kept as its output
+#[route("/{token}/fail/", method = "GET", method = "POST")]
+async fn fail(
+    token: Path<String>, body: Bytes, state: Data<AppState>,
+) -> Result<HttpResponse, AppErr> {
+    ping(&token, HeartbeatKind::Fail, &body, &state).await
+}
+
+pub fn router() -> Scope {
+    Scope::new("/heartbeats").service(success).service(start).service(fail)
+}
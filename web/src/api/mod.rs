@@ -1,4 +1,7 @@
-pub mod sites;
+pub mod badges;
 pub mod deploy;
+pub mod heartbeats;
+pub mod sites;
+pub mod status;
 pub mod user;
 pub mod verification;
@@ -1,25 +1,47 @@
-use actix_web::web::{Data, Json, Query};
+use actix_web::web::{Bytes, Data, Json, PayloadConfig, Query};
 use actix_web::{get, post, HttpRequest, HttpResponse, Scope};
+use chrono::{Datelike, Months, NaiveDate, NaiveTime, Utc};
+use hmac::{Hmac, Mac};
 // use actix_ws::AggregatedMessage;
 // use futures_util::StreamExt;
 use serde::Deserialize;
+use sha2::Sha256;
 use std::collections::HashMap;
-use utoipa::{OpenApi, ToSchema};
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
+use crate::config::Config;
 use crate::docs::UpdatePaths;
-use crate::models::site::{SiteMessage, Status};
+use crate::models::incident::{Incident, IncidentCause, Uptime};
+use crate::models::probe::{Probe, ProbeMethod, ProbeResult};
+use crate::models::site::{
+    merge_status, CertStatus, MetricKind, SiteApdex, SiteCert, SiteEvent,
+    SiteEventKind, SiteForecast, SiteInstance, SiteMessage, SiteMetric,
+    SiteRoute, SiteStat, SiteSummary, Status,
+};
+use crate::models::slo::{Slo, SloKind, SloReport};
 use crate::models::user::{Authorization, User};
+use crate::models::{
+    bad_auth, bad_request, not_found, AppErr, JsonStr, ListInput,
+};
 use crate::models::{site::Site, Response};
-use crate::models::{AppErr, not_found, ListInput};
 use crate::utils::CutOff;
-use crate::{utils, AppState};
+use crate::{incident, slo, stats, utils, AppState};
 
 #[derive(OpenApi)]
 #[openapi(
     tags((name = "api::sites")),
-    paths(list, dump, ping, message_add, message_list),
+    paths(
+        list, dump, ping, message_add, message_list, metric_list,
+        metric_names, instance_list, stat_list, summary, forecast,
+        event_list, incident_list, uptime, slo_list, apdex, apdex_routes, probe_list, probe_results, cert
+    ),
     components(schemas(
-        Site, Status, SiteDumpBody, SiteMessage, SiteAddMessageBody
+        Site, Status, SiteDumpBody, SiteDumpBatch, SiteMessage, SiteAddMessageBody,
+        SiteMetric, MetricKind, SiteDumpMetric, SitePingBody, SiteInstance,
+        SiteStat, SiteSummary, SiteForecast, SiteEvent, SiteEventKind,
+        Incident, IncidentCause, Uptime, Slo, SloKind, SloReport,
+        SiteDumpRoute, SiteApdex, Probe, ProbeMethod, ProbeResult, SiteCert,
+        CertStatus
     )),
     servers((url = "/sites")),
     modifiers(&UpdatePaths)
@@ -55,19 +77,125 @@ struct SiteDumpBody {
     max_time: i64,
     min_time: i64,
     status: HashMap<String, Status>,
+    /// request count per latency bucket, see `Config::LATENCY_BUCKETS`
+    #[serde(default)]
+    latency: Vec<i64>,
+    /// the dog instance that sent the dump
+    #[serde(default)]
+    instance: String,
+    /// increases with every dump of an instance, retried dumps are dropped
+    #[serde(default)]
+    seq: i64,
+    /// unix timestamps of the window the dump covers
+    start: Option<i64>,
+    end: Option<i64>,
+    /// start of the covered window, sent by older dogs when back-filling
+    timestamp: Option<i64>,
+    /// custom statsd metrics aggregated over the window
+    #[serde(default)]
+    metrics: HashMap<String, SiteDumpMetric>,
+    /// requests per route, for the log formats that carry the path
+    #[serde(default)]
+    routes: HashMap<String, SiteDumpRoute>,
+    /// distinct client addresses of the window, after dog anonymized them
+    #[serde(default)]
+    visitors: i64,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct SiteDumpRoute {
+    total: i64,
+    total_time: i64,
+    max_time: i64,
+    errors: i64,
+    latency: Vec<i64>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct SiteDumpMetric {
+    kind: MetricKind,
+    value: f64,
+    count: i64,
+    min: f64,
+    max: f64,
+}
+
+/// dog sends the spooled dumps one by one or as a batch
+#[derive(Deserialize, ToSchema)]
+#[serde(untagged)]
+enum SiteDumpBatch {
+    One(Box<SiteDumpBody>),
+    Many(Vec<SiteDumpBody>),
+}
+
+/// signed dumps are only accepted this many seconds around now
+const SIGNATURE_WINDOW: i64 = 300;
+/// most dumps that are accepted in one batch
+const BATCH_MAX: usize = 64;
+
+/// check the `x-heimdall-signature` header: a hex hmac-sha256 of
+/// `{timestamp}\n{nonce}\n{body}` keyed with the signing key of the site.
+/// the nonce is remembered for the length of the window so the request can
+/// not be replayed. unsigned dumps pass unless the site requires signatures
+async fn verify_signature(
+    rq: &HttpRequest, body: &[u8], site: &Site, state: &AppState,
+) -> Result<(), AppErr> {
+    let header = |name| rq.headers().get(name).and_then(|v| v.to_str().ok());
+    let Some(signature) = header("x-heimdall-signature") else {
+        if site.signature_required {
+            return Err(bad_auth!("this site only accepts signed dumps"));
+        }
+        return Ok(());
+    };
+    let Some(key) = site.signing_key.as_deref() else {
+        return Err(bad_auth!("this site has no signing key"));
+    };
+
+    let now = utils::now();
+    let timestamp = header("x-heimdall-timestamp")
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|v| now.abs_diff(*v) <= SIGNATURE_WINDOW as u64)
+        .ok_or(bad_auth!("invalid or expired signature timestamp"))?;
+    let nonce = header("x-heimdall-nonce")
+        .filter(|v| !v.is_empty() && v.len() <= 64)
+        .ok_or(bad_auth!("invalid signature nonce"))?;
+
+    let signature = (0..signature.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(signature.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<_>>>()
+        .ok_or(bad_auth!("invalid signature"))?;
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes())
+        .map_err(|_| bad_auth!("invalid signature"))?;
+    mac.update(format!("{timestamp}\n{nonce}\n").as_bytes());
+    mac.update(body);
+    mac.verify_slice(&signature).map_err(|_| bad_auth!("invalid signature"))?;
+
+    let mut nonces = state.nonces.lock().await;
+    nonces.retain(|_, t| now.abs_diff(*t) <= SIGNATURE_WINDOW as u64);
+    if nonces.insert(format!("{}:{nonce}", site.id), timestamp).is_some() {
+        return Err(bad_auth!("this dump was already received"));
+    }
+
+    Ok(())
 }
 
 #[utoipa::path(
     post,
-    request_body = SiteDumpBody,
+    request_body = SiteDumpBatch,
     responses((status = 200))
 )]
 /// Dump
+///
+/// the body may be gzip or zstd compressed (with `content-encoding`) and
+/// signed with the `x-heimdall-signature`, `x-heimdall-timestamp` and
+/// `x-heimdall-nonce` headers, which is required when the site sets
+/// `signature_required`
 #[post("/dump/")]
 async fn dump(
-    rq: HttpRequest, body: Json<SiteDumpBody>, state: Data<AppState>,
+    rq: HttpRequest, body: Bytes, state: Data<AppState>,
 ) -> Result<HttpResponse, AppErr> {
-    let now = utils::now();
     let mut sites = state.sites.lock().await;
     let site = match Authorization::try_from(&rq)? {
         Authorization::Site { id, token } => sites
@@ -79,6 +207,60 @@ async fn dump(
     }
     .map_err(|_| not_found!("no site was found"))?;
 
+    verify_signature(&rq, &body, site, &state).await?;
+
+    let dumps = match serde_json::from_slice::<SiteDumpBatch>(&body) {
+        Ok(SiteDumpBatch::One(v)) => vec![*v],
+        Ok(SiteDumpBatch::Many(v)) => v,
+        Err(e) => return Err(bad_request!(format!("invalid dump: {e}"))),
+    };
+    if dumps.len() > BATCH_MAX {
+        return Err(bad_request!("too many dumps in one batch"));
+    }
+
+    for body in dumps.iter() {
+        dump_add(site, body, &state).await?;
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+async fn dump_add(
+    site: &mut Site, body: &SiteDumpBody, state: &AppState,
+) -> Result<(), AppErr> {
+    let now = utils::now();
+    let instance = if body.instance.is_empty() {
+        None
+    } else {
+        let mut name = body.instance.clone();
+        name.cut_off(64);
+        let instance = sqlx::query_as! {
+            SiteInstance,
+            "select * from sites_instances where site = ? and instance = ?",
+            site.id, name
+        }
+        .fetch_optional(&state.sql)
+        .await?;
+
+        if instance.as_ref().is_some_and(|v| body.seq <= v.seq) {
+            log::warn!(
+                "dropped duplicate dump: site {} instance {name} seq {}",
+                site.id,
+                body.seq
+            );
+            return Ok(());
+        }
+
+        Some(instance.unwrap_or(SiteInstance {
+            site: site.id,
+            instance: name,
+            ..Default::default()
+        }))
+    };
+
+    let start = body.start.or(body.timestamp).unwrap_or(now);
+    let end = body.end.unwrap_or(start.max(now)).min(now);
+
     site.latest_dump_timestamp = now;
     site.total_requests += body.total;
     site.total_requests_time += body.total_time;
@@ -86,19 +268,8 @@ async fn dump(
     if body.min_time < site.requests_min_time || site.requests_min_time == 0 {
         site.requests_min_time = body.min_time;
     }
-    site.latest_request = utils::now();
-    for (sk, ns) in body.status.iter() {
-        if let Some(os) = site.status.get_mut(sk) {
-            os.count += ns.count;
-            os.total_time += ns.total_time;
-            os.max_time = os.max_time.max(ns.max_time);
-            if ns.min_time < os.min_time || os.min_time == 0 {
-                os.min_time = ns.min_time;
-            }
-        } else {
-            site.status.insert(sk.clone(), ns.clone());
-        }
-    }
+    site.latest_request = end.max(site.latest_request);
+    merge_status(&mut site.status, &body.status);
 
     sqlx::query! {"
         update sites set
@@ -123,17 +294,121 @@ async fn dump(
     .execute(&state.sql)
     .await?;
 
-    Ok(HttpResponse::Ok().finish())
+    let stat = SiteStat {
+        site: site.id,
+        total: body.total,
+        total_time: body.total_time,
+        max_time: body.max_time,
+        min_time: body.min_time,
+        status: JsonStr(body.status.clone()),
+        latency: JsonStr(match body.latency.len() {
+            n if n == Config::LATENCY_BUCKETS.len() + 1 => body.latency.clone(),
+            _ => Vec::new(),
+        }),
+        visitors: body.visitors.max(0),
+        ..Default::default()
+    };
+    stats::add(&state.sql, start, &stat).await?;
+
+    let routes = body
+        .routes
+        .iter()
+        .filter(|(_, r)| r.latency.len() == Config::LATENCY_BUCKETS.len() + 1)
+        .take(256)
+        .map(|(name, r)| {
+            let mut route = name.clone();
+            route.cut_off(255);
+            SiteRoute {
+                site: site.id,
+                route,
+                total: r.total,
+                total_time: r.total_time,
+                max_time: r.max_time,
+                errors: r.errors,
+                latency: JsonStr(r.latency.clone()),
+                ..Default::default()
+            }
+        })
+        .collect::<Vec<_>>();
+    stats::routes_add(&state.sql, start, &routes).await?;
+
+    for (name, m) in body.metrics.iter().take(256) {
+        let mut name = name.clone();
+        name.cut_off(255);
+        sqlx::query! {
+            "insert into sites_metrics(site, name, kind, timestamp, value,
+            count, min, max) values(?,?,?,?,?,?,?,?)",
+            site.id, name, m.kind, start, m.value, m.count, m.min, m.max
+        }
+        .execute(&state.sql)
+        .await?;
+    }
+
+    if let Some(mut instance) = instance {
+        instance.seq = body.seq;
+        instance.latest_dump = now;
+        instance.total_requests += body.total;
+        instance.total_requests_time += body.total_time;
+        instance.requests_max_time =
+            body.max_time.max(instance.requests_max_time);
+        if body.min_time < instance.requests_min_time
+            || instance.requests_min_time == 0
+        {
+            instance.requests_min_time = body.min_time;
+        }
+        merge_status(&mut instance.status, &body.status);
+
+        sqlx::query! {"
+            insert into sites_instances(
+                site, instance, seq, latest_dump, total_requests,
+                total_requests_time, requests_max_time, requests_min_time,
+                status
+            ) values(?,?,?,?,?,?,?,?,?)
+            on conflict(site, instance) do update set
+            seq = excluded.seq,
+            latest_dump = excluded.latest_dump,
+            total_requests = excluded.total_requests,
+            total_requests_time = excluded.total_requests_time,
+            requests_max_time = excluded.requests_max_time,
+            requests_min_time = excluded.requests_min_time,
+            status = excluded.status
+        ",
+            instance.site,
+            instance.instance,
+            instance.seq,
+            instance.latest_dump,
+            instance.total_requests,
+            instance.total_requests_time,
+            instance.requests_max_time,
+            instance.requests_min_time,
+            instance.status
+        }
+        .execute(&state.sql)
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize, ToSchema, Default)]
+struct SitePingBody {
+    #[serde(default)]
+    instance: String,
+    #[serde(default)]
+    version: String,
 }
 
 #[utoipa::path(
     post,
+    request_body = SitePingBody,
     responses((status = 200))
 )]
 /// Ping
+///
+/// older dogs send an empty body
 #[post("/ping/")]
 async fn ping(
-    rq: HttpRequest, state: Data<AppState>,
+    rq: HttpRequest, body: Bytes, state: Data<AppState>,
 ) -> Result<HttpResponse, AppErr> {
     let mut sites = state.sites.lock().await;
     let site = match Authorization::try_from(&rq)? {
@@ -146,6 +421,12 @@ async fn ping(
     }
     .map_err(|_| not_found!("no site was found"))?;
 
+    let body = match body.is_empty() {
+        true => SitePingBody::default(),
+        false => serde_json::from_slice::<SitePingBody>(&body)
+            .map_err(|e| bad_request!(format!("invalid ping: {e}")))?,
+    };
+
     site.latest_ping = utils::now();
 
     sqlx::query! {
@@ -155,6 +436,21 @@ async fn ping(
     .execute(&state.sql)
     .await?;
 
+    if !body.instance.is_empty() {
+        let mut instance = body.instance;
+        let mut version = body.version;
+        instance.cut_off(64);
+        version.cut_off(32);
+        sqlx::query! {
+            "insert into sites_instances(site, instance, version, latest_ping)
+            values(?,?,?,?) on conflict(site, instance) do update set
+            version = excluded.version, latest_ping = excluded.latest_ping",
+            site.id, instance, version, site.latest_ping
+        }
+        .execute(&state.sql)
+        .await?;
+    }
+
     Ok(HttpResponse::Ok().finish())
 }
 
@@ -245,6 +541,450 @@ async fn message_list(
     Ok(Json(messages))
 }
 
+#[derive(Deserialize, IntoParams)]
+struct MetricListQuery {
+    #[param(example = "signups")]
+    name: String,
+    #[param(example = 0)]
+    from: Option<i64>,
+    to: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    params(("site_id" = i64, Path, example = 1), MetricListQuery),
+    responses((status = 200, body = Vec<SiteMetric>))
+)]
+/// Metric List
+#[get("/{site_id}/metrics/")]
+async fn metric_list(
+    _: User, site: Site, q: Query<MetricListQuery>, state: Data<AppState>,
+) -> Response<Vec<SiteMetric>> {
+    let from = q.from.unwrap_or(0);
+    let to = q.to.unwrap_or(i64::MAX);
+    let metrics = sqlx::query_as! {
+        SiteMetric,
+        "select * from sites_metrics where site = ? and name = ? and
+        timestamp >= ? and timestamp <= ? order by timestamp desc limit 4096",
+        site.id, q.name, from, to
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    Ok(Json(metrics))
+}
+
+#[utoipa::path(
+    get,
+    params(("site_id" = i64, Path, example = 1)),
+    responses((status = 200, body = Vec<String>))
+)]
+/// Metric Names
+#[get("/{site_id}/metrics/names/")]
+async fn metric_names(
+    _: User, site: Site, state: Data<AppState>,
+) -> Response<Vec<String>> {
+    let names = sqlx::query! {
+        "select distinct name from sites_metrics where site = ? order by name",
+        site.id
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    Ok(Json(names.into_iter().map(|r| r.name).collect()))
+}
+
+#[utoipa::path(
+    get,
+    params(("site_id" = i64, Path, example = 1)),
+    responses((status = 200, body = Vec<SiteInstance>))
+)]
+/// Instance List
+#[get("/{site_id}/instances/")]
+async fn instance_list(
+    _: User, site: Site, state: Data<AppState>,
+) -> Response<Vec<SiteInstance>> {
+    let instances = sqlx::query_as! {
+        SiteInstance,
+        "select * from sites_instances where site = ? order by instance",
+        site.id
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    Ok(Json(instances))
+}
+
+#[derive(Deserialize, IntoParams)]
+struct StatListQuery {
+    /// default is a day before `to`
+    from: Option<i64>,
+    /// default is now
+    to: Option<i64>,
+    /// bucket size in seconds, a multiple of 60. default is an hour
+    #[param(example = 3600)]
+    step: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    params(("site_id" = i64, Path, example = 1), StatListQuery),
+    responses((status = 200, body = Vec<SiteStat>))
+)]
+/// Stat List
+///
+/// requests from `from` to `to` in buckets of `step` seconds, empty buckets
+/// are left out
+#[get("/{site_id}/stats/")]
+async fn stat_list(
+    _: User, site: Site, q: Query<StatListQuery>, state: Data<AppState>,
+) -> Response<Vec<SiteStat>> {
+    let to = q.to.unwrap_or_else(utils::now);
+    let from = q.from.unwrap_or(to - 86400);
+    let step = q.step.unwrap_or(3600);
+    if step < 60 || step % 60 != 0 {
+        return Err(bad_request!("step must be a multiple of 60"));
+    }
+    if from >= to || (to - from) / step > 4096 {
+        return Err(bad_request!("invalid range, at most 4096 buckets"));
+    }
+
+    Ok(Json(stats::list(&state.sql, site.id, from, to, step).await?))
+}
+
+#[derive(Deserialize, IntoParams)]
+struct SummaryQuery {
+    /// default is a day before `to`
+    from: Option<i64>,
+    /// default is now
+    to: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    params(("site_id" = i64, Path, example = 1), SummaryQuery),
+    responses((status = 200, body = SiteSummary))
+)]
+/// Summary
+///
+/// error rates by class, request rates and latency from `from` to `to`
+#[get("/{site_id}/summary/")]
+async fn summary(
+    _: User, site: Site, q: Query<SummaryQuery>, state: Data<AppState>,
+) -> Response<SiteSummary> {
+    let to = q.to.unwrap_or_else(utils::now);
+    let from = q.from.unwrap_or(to - 86400);
+    if from >= to {
+        return Err(bad_request!("from must be before to"));
+    }
+
+    Ok(Json(stats::summary(&state.sql, site.id, from, to).await?))
+}
+
+#[utoipa::path(
+    get,
+    params(("site_id" = i64, Path, example = 1), ListInput),
+    responses((status = 200, body = Vec<Incident>))
+)]
+/// Incident List
+#[get("/{site_id}/incidents/")]
+async fn incident_list(
+    _: User, site: Site, q: Query<ListInput>, state: Data<AppState>,
+) -> Response<Vec<Incident>> {
+    let offset = q.page * 32;
+    let incidents = sqlx::query_as! {
+        Incident,
+        "select * from incidents where site = ?
+        order by started desc, id desc limit 32 offset ?",
+        site.id, offset
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    Ok(Json(incidents))
+}
+
+#[derive(Deserialize, IntoParams)]
+struct UptimeQuery {
+    /// a month in utc, used when `from` and `to` are missing. default is
+    /// the current month
+    #[param(example = "2024-05")]
+    month: Option<String>,
+    from: Option<i64>,
+    /// default is now
+    to: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    params(("site_id" = i64, Path, example = 1), UptimeQuery),
+    responses((status = 200, body = Uptime))
+)]
+/// Uptime
+///
+/// uptime percentage and downtime minutes from the incidents of a month or
+/// from `from` to `to`
+#[get("/{site_id}/uptime/")]
+async fn uptime(
+    _: User, site: Site, q: Query<UptimeQuery>, state: Data<AppState>,
+) -> Response<Uptime> {
+    let (from, to) = match q.from {
+        Some(from) => (from, q.to.unwrap_or_else(utils::now)),
+        None => {
+            let month = match &q.month {
+                Some(m) => NaiveDate::parse_from_str(&format!("{m}-01"), "%F")
+                    .map_err(|_| bad_request!("month must be like 2024-05"))?,
+                None => Utc::now().date_naive().with_day(1).expect("day 1"),
+            };
+            let next = month + Months::new(1);
+            (
+                month.and_time(NaiveTime::MIN).and_utc().timestamp(),
+                next.and_time(NaiveTime::MIN).and_utc().timestamp(),
+            )
+        }
+    };
+    if from >= to {
+        return Err(bad_request!("from must be before to"));
+    }
+
+    Ok(Json(incident::uptime(&state.sql, site.id, from, to).await?))
+}
+
+#[derive(Deserialize, IntoParams)]
+struct ApdexQuery {
+    /// default is a day before `to`
+    from: Option<i64>,
+    /// default is now
+    to: Option<i64>,
+    /// bucket size in seconds, a multiple of 60. default is an hour
+    #[param(example = 3600)]
+    step: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    params(("site_id" = i64, Path, example = 1), ApdexQuery),
+    responses((status = 200, body = Vec<SiteApdex>))
+)]
+/// Apdex
+///
+/// apdex of the site from `from` to `to` in buckets of `step` seconds, for
+/// its `apdex_t`
+#[get("/{site_id}/apdex/")]
+async fn apdex(
+    _: User, site: Site, q: Query<ApdexQuery>, state: Data<AppState>,
+) -> Response<Vec<SiteApdex>> {
+    let to = q.to.unwrap_or_else(utils::now);
+    let from = q.from.unwrap_or(to - 86400);
+    let step = q.step.unwrap_or(3600);
+    if step < 60 || step % 60 != 0 {
+        return Err(bad_request!("step must be a multiple of 60"));
+    }
+    if from >= to || (to - from) / step > 4096 {
+        return Err(bad_request!("invalid range, at most 4096 buckets"));
+    }
+
+    let t = site.apdex_t;
+    Ok(Json(stats::apdex_list(&state.sql, site.id, from, to, step, t).await?))
+}
+
+#[utoipa::path(
+    get,
+    params(("site_id" = i64, Path, example = 1), SummaryQuery),
+    responses((status = 200, body = Vec<SiteApdex>))
+)]
+/// Apdex Routes
+///
+/// apdex of the 100 busiest routes from `from` to `to`, kept per hour and
+/// per day
+#[get("/{site_id}/apdex/routes/")]
+async fn apdex_routes(
+    _: User, site: Site, q: Query<SummaryQuery>, state: Data<AppState>,
+) -> Response<Vec<SiteApdex>> {
+    let to = q.to.unwrap_or_else(utils::now);
+    let from = q.from.unwrap_or(to - 86400);
+    if from >= to {
+        return Err(bad_request!("from must be before to"));
+    }
+
+    let routes = stats::routes_list(&state.sql, site.id, from, to).await?;
+    Ok(Json(
+        routes
+            .iter()
+            .take(100)
+            .map(|r| SiteApdex {
+                timestamp: from,
+                route: r.route.clone(),
+                ..stats::apdex(&r.latency, r.max_time, r.errors, site.apdex_t)
+            })
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    params(("site_id" = i64, Path, example = 1)),
+    responses((status = 200, body = Vec<Probe>))
+)]
+/// Probe List
+///
+/// the synthetic http checks of the site with their failure streaks
+#[get("/{site_id}/probes/")]
+async fn probe_list(
+    _: User, site: Site, state: Data<AppState>,
+) -> Response<Vec<Probe>> {
+    let probes = sqlx::query_as! {
+        Probe,
+        "select * from probes where site = ? order by id",
+        site.id
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    Ok(Json(probes))
+}
+
+#[utoipa::path(
+    get,
+    params(
+        ("site_id" = i64, Path, example = 1),
+        ("probe_id" = i64, Path, example = 1),
+        ListInput
+    ),
+    responses((status = 200, body = Vec<ProbeResult>))
+)]
+/// Probe Results
+///
+/// the latest results of a probe, newest first
+#[get("/{site_id}/probes/{probe_id}/results/")]
+async fn probe_results(
+    _: User, site: Site, probe: Probe, q: Query<ListInput>,
+    state: Data<AppState>,
+) -> Response<Vec<ProbeResult>> {
+    if probe.site != site.id {
+        return Err(not_found!("no probe was found"));
+    }
+
+    let offset = q.page * 32;
+    let results = sqlx::query_as! {
+        ProbeResult,
+        "select * from probes_results where probe = ?
+        order by timestamp desc, id desc limit 32 offset ?",
+        probe.id, offset
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    Ok(Json(results))
+}
+
+#[utoipa::path(
+    get,
+    params(("site_id" = i64, Path, example = 1)),
+    responses((status = 200, body = SiteCert))
+)]
+/// Cert
+///
+/// the latest certificate read from the `tls_host` of the site
+#[get("/{site_id}/cert/")]
+async fn cert(
+    _: User, site: Site, state: Data<AppState>,
+) -> Response<SiteCert> {
+    let cert = sqlx::query_as! {
+        SiteCert,
+        "select * from sites_certs where site = ?",
+        site.id
+    }
+    .fetch_optional(&state.sql)
+    .await?
+    .ok_or(not_found!("no certificate was read for this site"))?;
+
+    Ok(Json(cert))
+}
+
+#[utoipa::path(
+    get,
+    params(("site_id" = i64, Path, example = 1)),
+    responses((status = 200, body = Vec<SloReport>))
+)]
+/// Slo List
+///
+/// the slos of the site with their compliance, remaining error budget and
+/// burn rates
+#[get("/{site_id}/slos/")]
+async fn slo_list(
+    _: User, site: Site, state: Data<AppState>,
+) -> Response<Vec<SloReport>> {
+    let slos = sqlx::query_as! {
+        Slo,
+        "select * from slos where site = ? order by id",
+        site.id
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    let mut reports = Vec::with_capacity(slos.len());
+    for slo in slos {
+        reports.push(slo::report(&state.sql, slo).await?);
+    }
+
+    Ok(Json(reports))
+}
+
+#[derive(Deserialize, IntoParams)]
+struct ForecastQuery {
+    /// days to forecast, at most 90
+    #[param(example = 14)]
+    days: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    params(("site_id" = i64, Path, example = 1), ForecastQuery),
+    responses((status = 200, body = Vec<SiteForecast>))
+)]
+/// Forecast
+///
+/// daily requests of the coming days, from a holt-winters model with a
+/// weekly seasonality fitted on the daily stats. needs two weeks of history
+#[get("/{site_id}/forecast/")]
+async fn forecast(
+    _: User, site: Site, q: Query<ForecastQuery>, state: Data<AppState>,
+) -> Response<Vec<SiteForecast>> {
+    let days = q.days.unwrap_or(14);
+    if days == 0 || days > 90 {
+        return Err(bad_request!("days must be between 1 and 90"));
+    }
+
+    stats::forecast(&state.sql, site.id, days)
+        .await?
+        .map(Json)
+        .ok_or(bad_request!("at least two weeks of history are needed"))
+}
+
+#[utoipa::path(
+    get,
+    params(("site_id" = i64, Path, example = 1), ListInput),
+    responses((status = 200, body = Vec<SiteEvent>))
+)]
+/// Event List
+#[get("/{site_id}/events/")]
+async fn event_list(
+    _: User, site: Site, q: Query<ListInput>, state: Data<AppState>,
+) -> Response<Vec<SiteEvent>> {
+    let offset = q.page * 32;
+    let events = sqlx::query_as! {
+        SiteEvent,
+        "select * from sites_events where site = ?
+        order by timestamp desc, id desc limit 32 offset ?",
+        site.id, offset
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    Ok(Json(events))
+}
+
 // #[utoipa::path(get)]
 // /// live
 // #[get("/live/")]
@@ -296,9 +1036,146 @@ async fn message_list(
 
 pub fn router() -> Scope {
     Scope::new("/sites")
+        .app_data(PayloadConfig::new(4 * 1024 * 1024))
         .service(list)
         .service(dump)
         .service(ping)
         .service(message_add)
         .service(message_list)
+        .service(metric_list)
+        .service(metric_names)
+        .service(instance_list)
+        .service(stat_list)
+        .service(summary)
+        .service(forecast)
+        .service(event_list)
+        .service(incident_list)
+        .service(uptime)
+        .service(slo_list)
+        .service(apdex)
+        .service(apdex_routes)
+        .service(probe_list)
+        .service(probe_results)
+        .service(cert)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+    use std::io::Write;
+
+    const KEY: &str = "signing key";
+
+    fn site() -> Site {
+        Site {
+            id: 1,
+            token: Some("abc".to_string()),
+            online: true,
+            signing_key: Some(KEY.to_string()),
+            signature_required: true,
+            ..Default::default()
+        }
+    }
+
+    async fn state() -> AppState {
+        let state = utils::test_state().await;
+        state.sites.lock().await.insert(1, site());
+        state
+    }
+
+    /// a dump request signed the way dog/src/sender.rs signs it
+    fn signed(body: &[u8], timestamp: i64, nonce: &str) -> test::TestRequest {
+        let mut mac = Hmac::<Sha256>::new_from_slice(KEY.as_bytes()).unwrap();
+        mac.update(format!("{timestamp}\n{nonce}\n").as_bytes());
+        mac.update(body);
+        let signature = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|v| format!("{v:02x}"))
+            .collect::<String>();
+
+        test::TestRequest::post()
+            .uri("/dump/")
+            .insert_header(("authorization", "site 1:abc"))
+            .insert_header(("x-heimdall-timestamp", timestamp.to_string()))
+            .insert_header(("x-heimdall-nonce", nonce))
+            .insert_header(("x-heimdall-signature", signature))
+    }
+
+    #[actix_web::test]
+    async fn signature() {
+        let state = state().await;
+        let mut site = site();
+        let now = utils::now();
+        let body = b"[]";
+        let check = |rq: test::TestRequest, body: &'static [u8]| {
+            let rq = rq.to_http_request();
+            let (site, state) = (&site, &state);
+            async move { verify_signature(&rq, body, site, state).await }
+        };
+
+        assert!(check(signed(body, now, "a"), body).await.is_ok());
+        // the same nonce again is a replay, even with a fresh timestamp
+        assert!(check(signed(body, now + 1, "a"), body).await.is_err());
+        assert!(check(signed(body, now, "b"), b"[{}]").await.is_err());
+        assert!(check(signed(body, now, ""), body).await.is_err());
+
+        let window = SIGNATURE_WINDOW;
+        assert!(check(signed(body, now - window, "c"), body).await.is_ok());
+        assert!(check(signed(body, now - window - 1, "d"), body)
+            .await
+            .is_err());
+        assert!(check(signed(body, now + window + 1, "e"), body)
+            .await
+            .is_err());
+        assert!(check(signed(body, i64::MIN, "f"), body).await.is_err());
+        assert!(check(signed(body, i64::MAX, "g"), body).await.is_err());
+
+        let unsigned = || test::TestRequest::post();
+        assert!(check(unsigned(), body).await.is_err());
+        site.signature_required = false;
+        let rq = unsigned().to_http_request();
+        assert!(verify_signature(&rq, body, &site, &state).await.is_ok());
+    }
+
+    /// the signature covers the json, before it was compressed
+    #[actix_web::test]
+    async fn compressed() {
+        let app = test::init_service(
+            App::new().app_data(Data::new(state().await)).service(dump),
+        )
+        .await;
+        let body = b"[]";
+        let now = utils::now();
+
+        let mut gzip = flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        );
+        gzip.write_all(body).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let zstd = zstd::encode_all(&body[..], 0).unwrap();
+
+        for (nonce, encoding, data) in
+            [("a", "gzip", gzip), ("b", "zstd", zstd)]
+        {
+            let rq = signed(body, now, nonce)
+                .insert_header(("content-encoding", encoding))
+                .set_payload(data)
+                .to_request();
+            let res = test::call_service(&app, rq).await;
+            assert_eq!(res.status(), StatusCode::OK, "{encoding}");
+        }
+
+        // signed over the compressed bytes
+        let zstd = zstd::encode_all(&body[..], 0).unwrap();
+        let rq = signed(&zstd, now, "c")
+            .insert_header(("content-encoding", "zstd"))
+            .set_payload(zstd)
+            .to_request();
+        let res = test::call_service(&app, rq).await;
+        assert!(res.status().is_client_error());
+    }
 }
@@ -0,0 +1,83 @@
+use actix_web::http::header::ContentType;
+use actix_web::web::{Data, Json, Path};
+use actix_web::{get, HttpResponse, Scope};
+use utoipa::OpenApi;
+
+use crate::config::Config;
+use crate::docs::UpdatePaths;
+use crate::models::incident::IncidentCause;
+use crate::models::status::{
+    StatusDay, StatusIncident, StatusLevel, StatusNote, StatusPage,
+    StatusReport, StatusSite,
+};
+use crate::models::{not_found, AppErr, Response};
+use crate::{status, utils, AppState};
+
+#[derive(OpenApi)]
+#[openapi(
+    tags((name = "api::status")),
+    paths(status_json),
+    components(schemas(
+        StatusReport, StatusSite, StatusLevel, StatusDay, StatusIncident,
+        StatusNote, IncidentCause
+    )),
+    servers((url = "/status")),
+    modifiers(&UpdatePaths)
+)]
+pub struct ApiDoc;
+
+/// the pages are public, a cached report keeps every request from
+/// querying the incidents of all the sites
+async fn page_report(
+    slug: &str, state: &AppState,
+) -> Result<StatusReport, AppErr> {
+    let cached = state.status.lock().await.get(slug).cloned();
+    if let Some(report) = cached {
+        if report.timestamp > utils::now() - Config::STATUS_CACHE {
+            return Ok(report);
+        }
+    }
+
+    let page = sqlx::query_as! {
+        StatusPage,
+        "select * from status_pages where slug = ?",
+        slug
+    }
+    .fetch_optional(&state.sql)
+    .await?
+    .ok_or(not_found!("no status page was found"))?;
+
+    let report = status::report(state, page).await?;
+    state.status.lock().await.insert(slug.to_string(), report.clone());
+    Ok(report)
+}
+
+#[utoipa::path(
+    get,
+    params(("slug" = String, Path, example = "main")),
+    responses((status = 200, body = StatusReport))
+)]
+/// Report
+///
+/// public, the json of the page served at `/status/{slug}/`
+#[get("/{slug}/")]
+async fn status_json(
+    slug: Path<String>, state: Data<AppState>,
+) -> Response<StatusReport> {
+    Ok(Json(page_report(&slug, &state).await?))
+}
+
+/// the server rendered status page, outside of `/api`
+#[get("/status/{slug}/")]
+pub async fn status_html(
+    slug: Path<String>, state: Data<AppState>,
+) -> Result<HttpResponse, AppErr> {
+    let report = page_report(&slug, &state).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(status::html(&report)))
+}
+
+pub fn router() -> Scope {
+    Scope::new("/status").service(status_json)
+}
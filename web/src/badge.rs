@@ -0,0 +1,162 @@
+use crate::models::site::{Site, SiteHealth};
+use crate::utils;
+
+const GREEN: &str = "#4c1";
+const YELLOW_GREEN: &str = "#97ca00";
+const YELLOW: &str = "#dfb317";
+const ORANGE: &str = "#fe7d37";
+const RED: &str = "#e05d44";
+const GREY: &str = "#9f9f9f";
+
+/// rough width of the text in verdana 11px, close enough for the short
+/// labels of the badges
+fn width(text: &str) -> usize {
+    let tenths = text
+        .chars()
+        .map(|c| match c {
+            'i' | 'j' | 'l' | '.' | ',' | ':' | ';' | '\'' | '|' | '!' => 35,
+            ' ' | 'f' | 'r' | 't' | '(' | ')' | '[' | ']' | '-' => 45,
+            'm' | 'w' | 'M' | 'W' | '%' => 100,
+            'A'..='Z' => 75,
+            _ => 65,
+        })
+        .sum::<usize>();
+    tenths.div_ceil(10)
+}
+
+/// a flat badge like the ones of shields.io
+pub fn svg(label: &str, message: &str, color: &str) -> String {
+    let lw = width(label) + 10;
+    let mw = width(message) + 10;
+    let (label, message) = (utils::escape(label), utils::escape(message));
+    let w = lw + mw;
+    let (lx, mx) = (lw * 5, lw * 10 + mw * 5);
+
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"20\" \
+        role=\"img\" aria-label=\"{label}: {message}\">\
+        <title>{label}: {message}</title>\
+        <linearGradient id=\"s\" x2=\"0\" y2=\"100%\">\
+        <stop offset=\"0\" stop-color=\"#bbb\" stop-opacity=\".1\"/>\
+        <stop offset=\"1\" stop-opacity=\".1\"/></linearGradient>\
+        <clipPath id=\"r\">\
+        <rect width=\"{w}\" height=\"20\" rx=\"3\" fill=\"#fff\"/></clipPath>\
+        <g clip-path=\"url(#r)\">\
+        <rect width=\"{lw}\" height=\"20\" fill=\"#555\"/>\
+        <rect x=\"{lw}\" width=\"{mw}\" height=\"20\" fill=\"{color}\"/>\
+        <rect width=\"{w}\" height=\"20\" fill=\"url(#s)\"/></g>\
+        <g fill=\"#fff\" text-anchor=\"middle\" font-size=\"110\" \
+        font-family=\"Verdana,Geneva,DejaVu Sans,sans-serif\">\
+        <text x=\"{lx}\" y=\"150\" fill=\"#010101\" fill-opacity=\".3\" \
+        transform=\"scale(.1)\">{label}</text>\
+        <text x=\"{lx}\" y=\"140\" transform=\"scale(.1)\">{label}</text>\
+        <text x=\"{mx}\" y=\"150\" fill=\"#010101\" fill-opacity=\".3\" \
+        transform=\"scale(.1)\">{message}</text>\
+        <text x=\"{mx}\" y=\"140\" transform=\"scale(.1)\">{message}</text>\
+        </g></svg>"
+    )
+}
+
+/// the health of the site, degraded while one of its instances is silent
+pub fn status(site: &Site) -> String {
+    let (message, color) = match site.health {
+        SiteHealth::Down => ("down", RED),
+        _ if site.degraded => ("degraded", YELLOW),
+        SiteHealth::Up => ("up", GREEN),
+        SiteHealth::Unknown => ("unknown", GREY),
+    };
+    svg("status", message, color)
+}
+
+/// `uptime` from 0 to 100
+pub fn uptime(uptime: f64) -> String {
+    let color = match uptime {
+        u if u >= 99.9 => GREEN,
+        u if u >= 99.0 => YELLOW_GREEN,
+        u if u >= 97.0 => YELLOW,
+        u if u >= 95.0 => ORANGE,
+        _ => RED,
+    };
+    let message = match uptime >= 100.0 {
+        true => "100%".to_string(),
+        false => format!("{uptime:.2}%"),
+    };
+    svg("uptime", &message, color)
+}
+
+/// mean response time since the site was reset, colored by its apdex
+/// threshold
+pub fn latency(site: &Site) -> String {
+    if site.total_requests == 0 {
+        return svg("latency", "n/a", GREY);
+    }
+
+    let mean = site.total_requests_time / site.total_requests;
+    let color = match mean {
+        m if m <= site.apdex_t => GREEN,
+        m if m <= site.apdex_t * 4 => YELLOW,
+        _ => RED,
+    };
+    svg("latency", &format!("{mean} ms"), color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color(svg: &str) -> &str {
+        let (_, rest) = svg.split_once("<rect x=\"").unwrap();
+        let (_, rest) = rest.split_once("fill=\"").unwrap();
+        &rest[..rest.find('"').unwrap()]
+    }
+
+    #[test]
+    fn render() {
+        let badge = svg("uptime", "99.95%", GREEN);
+        assert!(badge.starts_with("<svg "));
+        assert!(badge.ends_with("</svg>"));
+        assert!(badge.contains("<title>uptime: 99.95%</title>"));
+        // the label and the message each get 5px of padding on both sides
+        let w = width("uptime") + width("99.95%") + 20;
+        assert!(badge.contains(&format!("width=\"{w}\" height=\"20\" role")));
+        assert_eq!(color(&badge), GREEN);
+        assert!(width("mmm") > width("iii"));
+
+        let badge = svg("a<b", "\"x\" & y", GREY);
+        assert!(badge.contains("<title>a&lt;b: &quot;x&quot; &amp; y</title>"));
+        assert!(!badge.contains("a<b"));
+    }
+
+    #[test]
+    fn colors() {
+        let site =
+            |health, degraded| Site { health, degraded, ..Default::default() };
+        let up = status(&site(SiteHealth::Up, false));
+        assert!(up.contains(">up</text>") && color(&up) == GREEN);
+        let degraded = status(&site(SiteHealth::Up, true));
+        assert!(degraded.contains(">degraded<") && color(&degraded) == YELLOW);
+        let down = status(&site(SiteHealth::Down, true));
+        assert!(down.contains(">down<") && color(&down) == RED);
+        let unknown = status(&site(SiteHealth::Unknown, false));
+        assert!(unknown.contains(">unknown<") && color(&unknown) == GREY);
+
+        assert!(uptime(100.0).contains(">100%<"));
+        assert!(uptime(99.123).contains(">99.12%<"));
+        let colors = [99.95, 99.5, 98.0, 96.0, 90.0].map(uptime);
+        let colors = colors.iter().map(|b| color(b)).collect::<Vec<_>>();
+        assert_eq!(colors, [GREEN, YELLOW_GREEN, YELLOW, ORANGE, RED]);
+
+        let site = |time: i64| Site {
+            total_requests: 10,
+            total_requests_time: time * 10,
+            apdex_t: 100,
+            ..Default::default()
+        };
+        let fast = latency(&site(80));
+        assert!(fast.contains(">80 ms<") && color(&fast) == GREEN);
+        assert_eq!(color(&latency(&site(400))), YELLOW);
+        assert_eq!(color(&latency(&site(401))), RED);
+        let none = latency(&Site::default());
+        assert!(none.contains(">n/a<") && color(&none) == GREY);
+    }
+}
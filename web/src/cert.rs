@@ -0,0 +1,296 @@
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use openssl::asn1::{Asn1Time, Asn1TimeRef};
+use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+use openssl::x509::{X509NameRef, X509VerifyResult};
+
+use crate::config::Config;
+use crate::models::site::{CertStatus, Site, SiteCert};
+use crate::models::JsonStr;
+use crate::{utils, AppState};
+
+fn name(name: &X509NameRef) -> String {
+    name.entries()
+        .filter_map(|e| {
+            let key = e.object().nid().short_name().ok()?;
+            let value = e.data().to_string().ok()?;
+            Some(format!("{key}={value}"))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn timestamp(time: &Asn1TimeRef) -> i64 {
+    Asn1Time::from_unix(0)
+        .and_then(|epoch| epoch.diff(time))
+        .map(|d| d.days as i64 * 86400 + d.secs as i64)
+        .unwrap_or(0)
+}
+
+fn ip(bytes: &[u8]) -> Option<String> {
+    let ip = match bytes.len() {
+        4 => IpAddr::from(<[u8; 4]>::try_from(bytes).ok()?),
+        16 => IpAddr::from(<[u8; 16]>::try_from(bytes).ok()?),
+        _ => return None,
+    };
+    Some(ip.to_string())
+}
+
+/// connect to host:port and read the served chain. blocks, the error is
+/// `Err` only when no certificate could be read
+fn read(host: &str, port: u16) -> Result<SiteCert, String> {
+    let timeout = Duration::from_secs(10);
+    let addr = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or("no address was found")?;
+    let stream = TcpStream::connect_timeout(&addr, timeout)
+        .map_err(|e| e.to_string())?;
+    stream.set_read_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+
+    // an expired or broken certificate still has to be read, the result of
+    // the verification is kept instead of failing the handshake
+    let mut builder =
+        SslConnector::builder(SslMethod::tls()).map_err(|e| e.to_string())?;
+    builder.set_verify(SslVerifyMode::NONE);
+    let stream =
+        builder.build().connect(host, stream).map_err(|e| e.to_string())?;
+
+    let ssl = stream.ssl();
+    let leaf = ssl.peer_certificate().ok_or("no certificate was served")?;
+    let sans = leaf
+        .subject_alt_names()
+        .map(|names| {
+            names
+                .iter()
+                .filter_map(|n| {
+                    n.dnsname().map(String::from).or_else(|| ip(n.ipaddress()?))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let chain = ssl
+        .peer_cert_chain()
+        .map(|chain| chain.iter().map(|c| name(c.subject_name())).collect())
+        .unwrap_or_default();
+
+    let verify = ssl.verify_result();
+    Ok(SiteCert {
+        subject: name(leaf.subject_name()),
+        issuer: name(leaf.issuer_name()),
+        sans: JsonStr(sans),
+        chain: JsonStr(chain),
+        not_before: timestamp(leaf.not_before()),
+        not_after: timestamp(leaf.not_after()),
+        error: match verify == X509VerifyResult::OK {
+            true => String::new(),
+            false => verify.error_string().to_string(),
+        },
+        ..Default::default()
+    })
+}
+
+fn status(cert: &SiteCert, read: bool, now: i64) -> CertStatus {
+    if !read {
+        CertStatus::Error
+    } else if cert.not_after <= now {
+        CertStatus::Expired
+    } else if !cert.error.is_empty() {
+        CertStatus::Invalid
+    } else if cert.not_after - now < Config::CERT_WARN_DAYS[0] * 86400 {
+        CertStatus::Expiring
+    } else {
+        CertStatus::Valid
+    }
+}
+
+/// the smallest of `Config::CERT_WARN_DAYS` the expiry is within, unless
+/// the certificate was already warned about at that level
+fn warn_level(cert: &SiteCert, now: i64) -> Option<i64> {
+    if cert.not_after == 0 {
+        return None;
+    }
+
+    let left = cert.not_after - now;
+    Config::CERT_WARN_DAYS
+        .into_iter()
+        .filter(|days| left < days * 86400)
+        .min()
+        .filter(|level| cert.warned == 0 || *level < cert.warned)
+}
+
+/// read the certificate of a site, store it and warn when its expiry
+/// passes one of `Config::CERT_WARN_DAYS`. the last certificate read is
+/// kept while the host can not be reached
+pub async fn check(
+    state: &AppState, site: &Site,
+) -> Result<SiteCert, sqlx::Error> {
+    let now = utils::now();
+    let (host, port) = (site.tls_host.clone(), site.tls_port);
+    let result = tokio::task::spawn_blocking(move || read(&host, port as u16))
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+
+    let old = sqlx::query_as! {
+        SiteCert,
+        "select * from sites_certs where site = ?",
+        site.id
+    }
+    .fetch_optional(&state.sql)
+    .await?
+    .filter(|c| c.host == site.tls_host && c.port == site.tls_port);
+
+    let mut messages = Vec::new();
+    let read = result.is_ok();
+    let mut cert = match result {
+        Ok(mut cert) => {
+            match &old {
+                Some(o) if o.not_after == cert.not_after => {
+                    cert.warned = o.warned
+                }
+                // the warnings start over for a new certificate
+                Some(o) if o.warned != 0 => messages.push(format!(
+                    "site {}: the certificate of {} was renewed",
+                    site.name, site.tls_host
+                )),
+                _ => {}
+            }
+            cert
+        }
+        Err(e) => SiteCert { error: e, ..old.unwrap_or_default() },
+    };
+    cert.site = site.id;
+    cert.host = site.tls_host.clone();
+    cert.port = site.tls_port;
+    cert.timestamp = now;
+
+    if let Some(level) = warn_level(&cert, now) {
+        let left = cert.not_after - now;
+        cert.warned = level;
+        messages.push(match left {
+            ..=0 => format!(
+                "site {}: the certificate of {} expired {}d ago",
+                site.name,
+                site.tls_host,
+                -left / 86400
+            ),
+            _ => format!(
+                "site {}: the certificate of {} expires in {}d {}h",
+                site.name,
+                site.tls_host,
+                left / 86400,
+                left % 86400 / 3600
+            ),
+        });
+    }
+
+    let status = status(&cert, read, now);
+    if status == CertStatus::Invalid && site.cert_status != status {
+        messages.push(format!(
+            "site {}: the certificate of {} is invalid, {}",
+            site.name, site.tls_host, cert.error
+        ));
+    }
+
+    sqlx::query! {
+        "insert or replace into sites_certs(
+            site, host, port, timestamp, subject, issuer, sans, chain,
+            not_before, not_after, error, warned
+        ) values(?,?,?,?,?,?,?,?,?,?,?,?)",
+        cert.site, cert.host, cert.port, cert.timestamp, cert.subject,
+        cert.issuer, cert.sans, cert.chain, cert.not_before, cert.not_after,
+        cert.error, cert.warned
+    }
+    .execute(&state.sql)
+    .await?;
+
+    sqlx::query! {
+        "update sites set cert_status = ?, cert_expires = ? where id = ?",
+        status, cert.not_after, site.id
+    }
+    .execute(&state.sql)
+    .await?;
+
+    let mut sites = state.sites.lock().await;
+    if let Some(s) = sites.get_mut(&site.id) {
+        s.cert_status = status;
+        s.cert_expires = cert.not_after;
+    }
+    drop(sites);
+
+    for text in messages {
+        utils::send_message(&text).await;
+    }
+
+    Ok(cert)
+}
+
+/// check the certificates of the online sites that have a `tls_host`
+pub async fn check_all(state: &AppState) -> Result<(), sqlx::Error> {
+    let sites = state
+        .sites
+        .lock()
+        .await
+        .values()
+        .filter(|s| s.online && !s.tls_host.is_empty())
+        .cloned()
+        .collect::<Vec<_>>();
+
+    for site in sites {
+        check(state, &site).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 86400;
+
+    fn cert(left: i64, warned: i64) -> SiteCert {
+        SiteCert { not_after: 1000 * DAY + left, warned, ..Default::default() }
+    }
+
+    #[test]
+    fn warn_levels() {
+        let now = 1000 * DAY;
+        let level = |left, warned| warn_level(&cert(left, warned), now);
+        assert_eq!(level(30 * DAY, 0), None);
+        assert_eq!(level(21 * DAY, 0), None);
+        assert_eq!(level(21 * DAY - 1, 0), Some(21));
+        // each level is warned about once
+        assert_eq!(level(20 * DAY, 21), None);
+        assert_eq!(level(6 * DAY, 21), Some(7));
+        assert_eq!(level(6 * DAY, 7), None);
+        assert_eq!(level(12 * 3600, 7), Some(1));
+        // a certificate first seen late starts at its own level
+        assert_eq!(level(3 * DAY, 0), Some(7));
+        assert_eq!(level(-DAY, 0), Some(1));
+        assert_eq!(level(-DAY, 1), None);
+        // nothing was ever read
+        let none = SiteCert::default();
+        assert_eq!(warn_level(&none, now), None);
+    }
+
+    #[test]
+    fn statuses() {
+        let now = 1000 * DAY;
+        let status = |cert: SiteCert| status(&cert, true, now);
+        assert_eq!(status(cert(30 * DAY, 0)), CertStatus::Valid);
+        assert_eq!(status(cert(20 * DAY, 0)), CertStatus::Expiring);
+        assert_eq!(status(cert(0, 0)), CertStatus::Expired);
+        let invalid = SiteCert { error: "self signed".into(), ..cert(DAY, 0) };
+        assert_eq!(status(invalid.clone()), CertStatus::Invalid);
+        let expired = SiteCert { not_after: now - 1, ..invalid };
+        assert_eq!(status(expired), CertStatus::Expired);
+        assert_eq!(
+            super::status(&cert(30 * DAY, 0), false, now),
+            CertStatus::Error
+        );
+    }
+}
@@ -8,6 +8,12 @@ pub struct Config {
     // pub group_id: String,
     pub deploy_key: String,
     pub deploy_repo: HashMap<String, PathBuf>,
+    /// days to keep the minute, hour and day stats, 0 keeps them forever.
+    /// set with `STATS_RETENTION="7,90,0"`
+    pub stats_retention: [i64; 3],
+    /// seconds without a ping or a dump before a site is down.
+    /// set with `OFFLINE_AFTER="300"`
+    pub offline_after: i64,
 }
 
 macro_rules! evar {
@@ -26,22 +32,87 @@ impl Config {
         b"abcdefghijklmnopqrstuvwxyzABCDEFGHMNOPQRSTUVWXYZ0123456789";
     pub const SITE_NAME_ABC: &'static [u8] =
         b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789-_.";
+    /// seconds without a ping before an instance is considered silent
+    pub const SILENT_AFTER: i64 = 300;
+    /// apdex threshold of new sites in ms, the default of the column
+    pub const APDEX_T: i64 = 500;
+    /// bytes of a probe response read for the body check
+    pub const PROBE_BODY_MAX: usize = 1024 * 1024;
+    /// port the certificate of new sites is read from
+    pub const TLS_PORT: i64 = 443;
+    /// days before the expiry of a certificate a warning is sent at
+    pub const CERT_WARN_DAYS: [i64; 3] = [21, 7, 1];
+    /// bytes of a heartbeat ping body kept as its output
+    pub const HEARTBEAT_OUTPUT_MAX: usize = 10 * 1024;
+    /// pings kept per heartbeat
+    pub const HEARTBEAT_PINGS: i64 = 100;
+    /// days of uptime bars on the status pages
+    pub const STATUS_DAYS: i64 = 90;
+    /// seconds a status page report is cached for
+    pub const STATUS_CACHE: i64 = 60;
+    /// days the uptime badge covers
+    pub const BADGE_UPTIME_DAYS: i64 = 30;
+    /// bucket sizes of the stats: minute, hour and day
+    pub const STATS_STEPS: [i64; 3] = [60, 3600, 86400];
+    /// upper bounds of the latency buckets dog sends, in milliseconds.
+    /// there is one more bucket for everything slower
+    pub const LATENCY_BUCKETS: [i64; 11] =
+        [5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+    /// oldest bucket kept for a stats step, `None` when it is kept forever
+    pub fn stats_cutoff(&self, step: i64, now: i64) -> Option<i64> {
+        let i = Self::STATS_STEPS.iter().position(|s| *s == step)?;
+        match self.stats_retention[i] {
+            0 => None,
+            days => Some(now - days * 86400),
+        }
+    }
+}
+
+fn stats_retention() -> [i64; 3] {
+    let mut days = [7, 90, 0];
+    if let Ok(value) = std::env::var("STATS_RETENTION") {
+        for (d, v) in days.iter_mut().zip(value.split(',')) {
+            *d = v.trim().parse().expect("invalid STATS_RETENTION");
+        }
+    }
+    days
 }
 
 pub fn config() -> &'static Config {
     static STATE: OnceLock<Config> = OnceLock::new();
 
-    let data = std::fs::read_to_string("deploy_repo.json")
-        .expect("reading deploy_repo.json");
-
-    let deploy_repo = serde_json::from_str::<HashMap<String, String>>(&data)
-        .expect("invalid deploy_repo.json");
-    STATE.get_or_init(|| Config {
-        iris_pass: evar!("IRIS_PASS"),
-        // group_id: evar!("TELOXIDE_GROUP_ID"),
-        deploy_key: evar!("DEPLOY_KEY"),
-        deploy_repo: HashMap::from_iter(
-            deploy_repo.iter().map(|(repo, path)| (repo.clone(), path.into())),
-        ),
+    STATE.get_or_init(|| {
+        // tests run without a .env or a deploy_repo.json
+        if cfg!(test) {
+            return Config {
+                iris_pass: String::new(),
+                deploy_key: String::new(),
+                deploy_repo: HashMap::new(),
+                stats_retention: stats_retention(),
+                offline_after: 300,
+            };
+        }
+
+        let data = std::fs::read_to_string("deploy_repo.json")
+            .expect("reading deploy_repo.json");
+        let deploy_repo =
+            serde_json::from_str::<HashMap<String, String>>(&data)
+                .expect("invalid deploy_repo.json");
+
+        Config {
+            iris_pass: evar!("IRIS_PASS"),
+            // group_id: evar!("TELOXIDE_GROUP_ID"),
+            deploy_key: evar!("DEPLOY_KEY"),
+            deploy_repo: HashMap::from_iter(
+                deploy_repo
+                    .iter()
+                    .map(|(repo, path)| (repo.clone(), path.into())),
+            ),
+            stats_retention: stats_retention(),
+            offline_after: std::env::var("OFFLINE_AFTER")
+                .map(|v| v.trim().parse().expect("invalid OFFLINE_AFTER"))
+                .unwrap_or(300),
+        }
     })
 }
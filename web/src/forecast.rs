@@ -0,0 +1,174 @@
+/// additive holt-winters fitted on a series with a seasonality of `season`
+/// points. the smoothing factors are picked by a grid search on the
+/// one-step-ahead squared error
+pub struct HoltWinters {
+    alpha: f64,
+    beta: f64,
+    gamma: f64,
+    season: usize,
+    level: f64,
+    trend: f64,
+    seasonals: Vec<f64>,
+    /// standard deviation of the one-step-ahead errors
+    sigma: f64,
+}
+
+/// a forecasted point with its 95% band
+pub struct Point {
+    pub value: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl HoltWinters {
+    /// needs at least two seasons of history
+    pub fn fit(series: &[f64], season: usize) -> Option<Self> {
+        if season == 0 || series.len() < season * 2 {
+            return None;
+        }
+
+        const GRID: [f64; 9] = [0.05, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.8, 0.95];
+        let mut best: Option<(f64, Self)> = None;
+        for alpha in GRID {
+            for beta in GRID.iter().take(5).copied() {
+                for gamma in GRID {
+                    let (sse, model) =
+                        Self::run(series, season, alpha, beta, gamma);
+                    if best.as_ref().is_none_or(|(b, _)| sse < *b) {
+                        best = Some((sse, model));
+                    }
+                }
+            }
+        }
+
+        best.map(|(_, model)| model)
+    }
+
+    fn run(
+        series: &[f64], season: usize, alpha: f64, beta: f64, gamma: f64,
+    ) -> (f64, Self) {
+        let m = season;
+        let first = series[..m].iter().sum::<f64>() / m as f64;
+        let second = series[m..m * 2].iter().sum::<f64>() / m as f64;
+
+        let mut level = first;
+        let mut trend = (second - first) / m as f64;
+        let mut seasonals =
+            series[..m].iter().map(|v| v - first).collect::<Vec<_>>();
+
+        let mut sse = 0.0;
+        for (t, value) in series.iter().enumerate().skip(m) {
+            let s = seasonals[t % m];
+            let error = value - (level + trend + s);
+            sse += error * error;
+
+            let last = level;
+            level = alpha * (value - s) + (1.0 - alpha) * (level + trend);
+            trend = beta * (level - last) + (1.0 - beta) * trend;
+            seasonals[t % m] = gamma * (value - level) + (1.0 - gamma) * s;
+        }
+
+        let n = (series.len() - m) as f64;
+        let model = Self {
+            alpha,
+            beta,
+            gamma,
+            season,
+            level,
+            trend,
+            // rotated so the next point of the series is at index 0
+            seasonals: (0..m)
+                .map(|i| seasonals[(series.len() + i) % m])
+                .collect(),
+            sigma: (sse / n).sqrt(),
+        };
+        (sse, model)
+    }
+
+    /// the next `horizon` points. the band widens with the horizon, see
+    /// hyndman et al. "forecasting with exponential smoothing", class 1
+    pub fn forecast(&self, horizon: usize) -> Vec<Point> {
+        let mut variance = 0.0;
+        (1..=horizon)
+            .map(|h| {
+                let j = (h - 1) as f64;
+                if h > 1 {
+                    let seasonal = if (h - 1) % self.season == 0 {
+                        self.gamma
+                    } else {
+                        0.0
+                    };
+                    let c = self.alpha * (1.0 + j * self.beta) + seasonal;
+                    variance += c * c;
+                }
+
+                let value = self.level
+                    + h as f64 * self.trend
+                    + self.seasonals[(h - 1) % self.season];
+                let band = 1.96 * self.sigma * (1.0 + variance).sqrt();
+                Point {
+                    value: value.max(0.0),
+                    lower: (value - band).max(0.0),
+                    upper: (value + band).max(0.0),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WEEK: [f64; 7] = [20.0, 10.0, 0.0, 0.0, 5.0, -15.0, -20.0];
+
+    /// a weekly pattern over a rising trend
+    fn series(days: usize) -> Vec<f64> {
+        (0..days).map(|t| 500.0 + 3.0 * t as f64 + WEEK[t % 7]).collect()
+    }
+
+    #[test]
+    fn too_short() {
+        assert!(HoltWinters::fit(&series(13), 7).is_none());
+        assert!(HoltWinters::fit(&series(20), 0).is_none());
+        assert!(HoltWinters::fit(&series(14), 7).is_some());
+    }
+
+    #[test]
+    fn seasonal_trend() {
+        let history = series(56);
+        let expected = series(70)[56..].to_vec();
+        let model = HoltWinters::fit(&history, 7).unwrap();
+        let points = model.forecast(14);
+
+        assert_eq!(points.len(), 14);
+        for (point, want) in points.iter().zip(expected) {
+            assert!((point.value - want).abs() < 5.0, "{} {want}", point.value);
+            assert!(point.lower <= point.value && point.value <= point.upper);
+        }
+    }
+
+    #[test]
+    fn flat() {
+        let model = HoltWinters::fit(&[40.0; 28], 7).unwrap();
+        for point in model.forecast(10) {
+            assert!((point.value - 40.0).abs() < 1e-9);
+            assert!((point.upper - point.lower).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn band_widens() {
+        let noisy = series(56)
+            .iter()
+            .enumerate()
+            .map(|(t, v)| v + [8.0, -6.0, 3.0, -9.0, 5.0][t % 5])
+            .collect::<Vec<_>>();
+        let points = HoltWinters::fit(&noisy, 7).unwrap().forecast(21);
+        let widths =
+            points.iter().map(|p| p.upper - p.lower).collect::<Vec<_>>();
+        assert!(widths[0] > 0.0);
+        assert!(widths.windows(2).all(|w| w[1] >= w[0]));
+        assert!(widths[20] > widths[0]);
+    }
+}
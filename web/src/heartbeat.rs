@@ -0,0 +1,213 @@
+use crate::config::Config;
+use crate::models::heartbeat::{Heartbeat, HeartbeatKind, HeartbeatState};
+use crate::utils::CutOff;
+use crate::{utils, AppState};
+
+async fn label(state: &AppState, heartbeat: &Heartbeat) -> String {
+    let sites = state.sites.lock().await;
+    match heartbeat.site.and_then(|id| sites.get(&id)) {
+        Some(site) => {
+            format!("site {}: heartbeat {}", site.name, heartbeat.name)
+        }
+        None => format!("heartbeat {}", heartbeat.name),
+    }
+}
+
+/// record a ping of a job. a start keeps a late or failed state until the
+/// run reports its end
+pub async fn ping(
+    state: &AppState, heartbeat: Heartbeat, kind: HeartbeatKind, output: &[u8],
+) -> Result<(), sqlx::Error> {
+    let now = utils::now();
+    let mut output = String::from_utf8_lossy(output).to_string();
+    output.cut_off(Config::HEARTBEAT_OUTPUT_MAX);
+
+    let running = heartbeat.last_start > heartbeat.last_ping;
+    let duration = match kind {
+        HeartbeatKind::Start => 0,
+        _ if running => now - heartbeat.last_start,
+        _ => 0,
+    };
+
+    sqlx::query! {
+        "insert into heartbeats_pings(
+            heartbeat, timestamp, kind, duration, output
+        ) values(?,?,?,?,?)",
+        heartbeat.id, now, kind, duration, output
+    }
+    .execute(&state.sql)
+    .await?;
+
+    // only the latest pings are kept
+    sqlx::query! {
+        "delete from heartbeats_pings where heartbeat = ? and id <= (
+            select id from heartbeats_pings where heartbeat = ?
+            order by id desc limit 1 offset ?
+        )",
+        heartbeat.id, heartbeat.id, Config::HEARTBEAT_PINGS
+    }
+    .execute(&state.sql)
+    .await?;
+
+    let old = heartbeat.state.clone();
+    let mut heartbeat = heartbeat;
+    match kind {
+        HeartbeatKind::Start => {
+            heartbeat.last_start = now;
+            if matches!(old, HeartbeatState::New | HeartbeatState::Up) {
+                heartbeat.state = HeartbeatState::Started;
+            }
+        }
+        HeartbeatKind::Success => {
+            heartbeat.last_ping = now;
+            heartbeat.state = HeartbeatState::Up;
+        }
+        HeartbeatKind::Fail => {
+            heartbeat.last_ping = now;
+            heartbeat.state = HeartbeatState::Failed;
+        }
+    }
+
+    sqlx::query! {
+        "update heartbeats set state = ?, last_ping = ?, last_start = ?
+        where id = ?",
+        heartbeat.state, heartbeat.last_ping, heartbeat.last_start,
+        heartbeat.id
+    }
+    .execute(&state.sql)
+    .await?;
+
+    let text = match (&old, &heartbeat.state) {
+        (HeartbeatState::Failed, HeartbeatState::Failed) => return Ok(()),
+        (_, HeartbeatState::Failed) => {
+            let mut output = output;
+            output.cut_off(512);
+            format!("{} failed\n{output}", label(state, &heartbeat).await)
+        }
+        (HeartbeatState::Late | HeartbeatState::Failed, HeartbeatState::Up) => {
+            format!("{} is up again", label(state, &heartbeat).await)
+        }
+        _ => return Ok(()),
+    };
+    utils::send_message(text.trim_end()).await;
+
+    Ok(())
+}
+
+/// mark the heartbeats that missed their ping, or whose run did not end
+/// within the grace, as late
+pub async fn check(state: &AppState) -> Result<(), sqlx::Error> {
+    let now = utils::now();
+    let (up, started) = (HeartbeatState::Up, HeartbeatState::Started);
+    let heartbeats = sqlx::query_as! {
+        Heartbeat,
+        "select * from heartbeats where state = ? or state = ?",
+        up, started
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    for heartbeat in heartbeats {
+        let (deadline, text) = match heartbeat.state {
+            HeartbeatState::Started => (
+                heartbeat.last_start + heartbeat.grace,
+                format!("started {}s ago", now - heartbeat.last_start),
+            ),
+            _ => (
+                heartbeat.last_ping + heartbeat.period + heartbeat.grace,
+                format!("last ping {}s ago", now - heartbeat.last_ping),
+            ),
+        };
+        if now <= deadline {
+            continue;
+        }
+
+        let late = HeartbeatState::Late;
+        sqlx::query! {
+            "update heartbeats set state = ? where id = ?",
+            late, heartbeat.id
+        }
+        .execute(&state.sql)
+        .await?;
+
+        let label = label(state, &heartbeat).await;
+        utils::send_message(&format!("{label} is late, {text}")).await;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a heartbeat that runs hourly with 5 minutes of grace
+    async fn add(
+        state: &AppState, id: i64, hb: HeartbeatState, ping: i64, start: i64,
+    ) {
+        sqlx::query! {
+            "insert into heartbeats(
+                id, name, token, period, grace, state, last_ping, last_start
+            ) values(?,?,?,3600,300,?,?,?)",
+            id, id, id, hb, ping, start
+        }
+        .execute(&state.sql)
+        .await
+        .unwrap();
+    }
+
+    async fn get(state: &AppState, id: i64) -> Heartbeat {
+        sqlx::query_as!(Heartbeat, "select * from heartbeats where id = ?", id)
+            .fetch_one(&state.sql)
+            .await
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn lateness() {
+        let state = utils::test_state().await;
+        let now = utils::now();
+        let hour = 3600;
+        add(&state, 1, HeartbeatState::Up, now - hour - 290, 0).await;
+        add(&state, 2, HeartbeatState::Up, now - hour - 310, 0).await;
+        // a run only gets the grace to finish
+        add(&state, 3, HeartbeatState::Started, now - 60, now - 290).await;
+        add(&state, 4, HeartbeatState::Started, now - 60, now - 310).await;
+        // never pinged, or already reported
+        add(&state, 5, HeartbeatState::New, 0, 0).await;
+        add(&state, 6, HeartbeatState::Failed, now - 9 * hour, 0).await;
+
+        check(&state).await.unwrap();
+        let mut states = Vec::new();
+        for id in 1..=6 {
+            states.push(get(&state, id).await.state);
+        }
+        use HeartbeatState::*;
+        assert_eq!(states, [Up, Late, Started, Late, New, Failed]);
+    }
+
+    #[actix_web::test]
+    async fn pings() {
+        let state = utils::test_state().await;
+        add(&state, 1, HeartbeatState::New, 0, 0).await;
+        let send = |kind| async {
+            ping(&state, get(&state, 1).await, kind, b"").await.unwrap();
+            get(&state, 1).await.state
+        };
+
+        assert_eq!(send(HeartbeatKind::Start).await, HeartbeatState::Started);
+        assert_eq!(send(HeartbeatKind::Success).await, HeartbeatState::Up);
+        assert_eq!(send(HeartbeatKind::Fail).await, HeartbeatState::Failed);
+        // a start does not hide a failure, only the end of the run does
+        assert_eq!(send(HeartbeatKind::Start).await, HeartbeatState::Failed);
+        assert_eq!(send(HeartbeatKind::Success).await, HeartbeatState::Up);
+
+        let late = HeartbeatState::Late;
+        sqlx::query!("update heartbeats set state = ? where id = 1", late)
+            .execute(&state.sql)
+            .await
+            .unwrap();
+        assert_eq!(send(HeartbeatKind::Start).await, HeartbeatState::Late);
+        assert_eq!(send(HeartbeatKind::Success).await, HeartbeatState::Up);
+    }
+}
@@ -0,0 +1,166 @@
+use sqlx::{Pool, Sqlite};
+
+use crate::models::incident::{Incident, IncidentCause, Uptime};
+use crate::utils;
+
+/// open an incident for the site unless one of the same cause is open
+pub async fn open(
+    pool: &Pool<Sqlite>, site: i64, cause: IncidentCause, started: i64,
+    text: &str,
+) -> Result<(), sqlx::Error> {
+    let open = sqlx::query! {
+        "select id from incidents where site = ? and cause = ? and ended = 0",
+        site, cause
+    }
+    .fetch_optional(pool)
+    .await?;
+    if open.is_some() {
+        return Ok(());
+    }
+
+    sqlx::query! {
+        "insert into incidents(site, cause, started, text) values(?,?,?,?)",
+        site, cause, started, text
+    }
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// close the open incidents of a cause
+pub async fn close(
+    pool: &Pool<Sqlite>, site: i64, cause: IncidentCause, ended: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query! {
+        "update incidents set ended = max(started, ?)
+        where site = ? and cause = ? and ended = 0",
+        ended, site, cause
+    }
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// seconds from `from` to `to` covered by the incidents, sorted by start.
+/// open ones last until `to`
+pub fn downtime(incidents: &[Incident], from: i64, to: i64) -> i64 {
+    // union of the incidents clamped to the range
+    let mut downtime = 0;
+    let mut end = from;
+    for incident in incidents.iter() {
+        let ended = if incident.ended == 0 { to } else { incident.ended };
+        let start = incident.started.max(end);
+        let stop = ended.min(to);
+        if stop > start {
+            downtime += stop - start;
+        }
+        end = end.max(stop);
+    }
+    downtime
+}
+
+pub async fn uptime(
+    pool: &Pool<Sqlite>, site: i64, from: i64, to: i64,
+) -> Result<Uptime, sqlx::Error> {
+    let to = to.min(utils::now()).max(from);
+    let incidents = sqlx::query_as! {
+        Incident,
+        "select * from incidents where site = ? and started < ? and
+        (ended = 0 or ended > ?) order by started",
+        site, to, from
+    }
+    .fetch_all(pool)
+    .await?;
+
+    let downtime = downtime(&incidents, from, to);
+    let total = (to - from).max(1);
+    Ok(Uptime {
+        site,
+        from,
+        to,
+        uptime: (total - downtime) as f64 / total as f64 * 100.0,
+        downtime_minutes: downtime as f64 / 60.0,
+        incidents: incidents.len() as i64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2025-01-01 and 2025-02-01, utc
+    const JAN: i64 = 1735689600;
+    const FEB: i64 = 1738368000;
+
+    fn incidents(spans: &[(i64, i64)]) -> Vec<Incident> {
+        spans
+            .iter()
+            .map(|(started, ended)| Incident {
+                started: *started,
+                ended: *ended,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn downtime_union() {
+        let down = |spans: &[(i64, i64)]| downtime(&incidents(spans), 0, 1000);
+        assert_eq!(down(&[]), 0);
+        assert_eq!(down(&[(100, 200), (300, 350)]), 150);
+        // overlapping ones are counted once
+        assert_eq!(down(&[(100, 200), (150, 300)]), 200);
+        // nested in a longer one
+        assert_eq!(down(&[(100, 500), (200, 300), (400, 450)]), 400);
+        assert_eq!(down(&[(100, 500), (200, 300), (450, 600)]), 500);
+        // open ones last until the end of the range
+        assert_eq!(down(&[(900, 0)]), 100);
+        assert_eq!(down(&[(100, 200), (150, 0), (300, 400)]), 900);
+        // clamped to the range on both sides
+        assert_eq!(down(&[(-500, 100), (950, 2000)]), 150);
+        assert_eq!(down(&[(-500, 0)]), 1000);
+    }
+
+    #[test]
+    fn downtime_month() {
+        let hour = 3600;
+        let spans = [
+            // started in december
+            (JAN - hour, JAN + hour),
+            (JAN + 10 * hour, JAN + 12 * hour),
+            (JAN + 11 * hour, 0),
+        ];
+        let month = incidents(&spans);
+        assert_eq!(downtime(&month[..2], JAN, FEB), 3 * hour);
+        assert_eq!(downtime(&month, JAN, FEB), hour + (FEB - JAN - 10 * hour));
+        // nothing of january is in december
+        assert_eq!(downtime(&month[1..], JAN - 31 * 86400, JAN), 0);
+    }
+
+    #[actix_web::test]
+    async fn open_close_uptime() {
+        let pool = utils::test_pool().await;
+        sqlx::query!("insert into sites(id, name) values(1, 'test')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let cause = IncidentCause::MissedPings;
+        let start = JAN + 86400;
+        open(&pool, 1, cause.clone(), start, "down").await.unwrap();
+        // one open incident per cause
+        open(&pool, 1, cause.clone(), start + 60, "down").await.unwrap();
+        close(&pool, 1, cause.clone(), start + 3600).await.unwrap();
+        // an incident that ends before it starts is empty
+        open(&pool, 1, cause.clone(), start + 7200, "down").await.unwrap();
+        close(&pool, 1, cause, start).await.unwrap();
+
+        let uptime = uptime(&pool, 1, JAN, FEB).await.unwrap();
+        assert_eq!(uptime.incidents, 2);
+        assert_eq!(uptime.downtime_minutes, 60.0);
+        let total = (FEB - JAN) as f64;
+        assert_eq!(uptime.uptime, (total - 3600.0) / total * 100.0);
+    }
+}
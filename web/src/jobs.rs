@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use actix_web::web::Data;
+
+use crate::config::config;
+use crate::models::incident::IncidentCause;
+use crate::models::site::{SiteEventKind, SiteHealth, SiteInstance};
+use crate::{
+    alert, anomaly, cert, heartbeat, incident, probe, slo, stats, utils,
+    AppState,
+};
+
+/// mark a site as degraded while one of its instances is silent
+pub async fn instances(state: Data<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+
+    loop {
+        interval.tick().await;
+        if let Err(e) = instances_check(&state).await {
+            log::error!("instances check: {e:?}");
+        }
+    }
+}
+
+async fn instances_check(state: &AppState) -> Result<(), sqlx::Error> {
+    let now = utils::now();
+    let instances = sqlx::query_as! {
+        SiteInstance,
+        "select * from sites_instances where latest_ping > 0"
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    let mut silent = HashMap::<i64, Vec<String>>::new();
+    for instance in instances.into_iter().filter(|i| i.silent(now)) {
+        silent.entry(instance.site).or_default().push(instance.instance);
+    }
+
+    let mut changed = Vec::new();
+    let mut sites = state.sites.lock().await;
+    for site in sites.values_mut() {
+        let degraded = silent.get(&site.id);
+        if site.degraded == degraded.is_some() {
+            continue;
+        }
+
+        site.degraded = degraded.is_some();
+        changed.push((site.id, site.name.clone(), site.degraded));
+    }
+    drop(sites);
+
+    for (id, name, degraded) in changed {
+        sqlx::query!(
+            "update sites set degraded = ? where id = ?",
+            degraded,
+            id
+        )
+        .execute(&state.sql)
+        .await?;
+
+        let text = match silent.get(&id) {
+            Some(names) => format!(
+                "site {name} is degraded, silent instances: {}",
+                names.join(", ")
+            ),
+            None => format!("site {name} is no longer degraded"),
+        };
+        utils::send_message(&text).await;
+    }
+
+    Ok(())
+}
+
+/// mark sites down when nothing arrived from them within the grace period
+/// and up again once something does
+pub async fn health(state: Data<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(30));
+
+    loop {
+        interval.tick().await;
+        if let Err(e) = health_check(&state).await {
+            log::error!("health check: {e:?}");
+        }
+    }
+}
+
+async fn health_check(state: &AppState) -> Result<(), sqlx::Error> {
+    let now = utils::now();
+    let grace = config().offline_after;
+
+    let mut changes = Vec::new();
+    let mut sites = state.sites.lock().await;
+    for site in sites.values_mut().filter(|s| s.online) {
+        let seen = site.latest_ping.max(site.latest_dump_timestamp);
+        let health = match seen {
+            0 => SiteHealth::Unknown,
+            _ if now - seen > grace => SiteHealth::Down,
+            _ => SiteHealth::Up,
+        };
+        if site.health == health {
+            continue;
+        }
+
+        let old = std::mem::replace(&mut site.health, health.clone());
+        changes.push((site.id, site.name.clone(), old, health, seen));
+    }
+    drop(sites);
+
+    for (id, name, old, health, seen) in changes {
+        sqlx::query!("update sites set health = ? where id = ?", health, id)
+            .execute(&state.sql)
+            .await?;
+
+        let (kind, value, text) = match health {
+            SiteHealth::Down => (
+                SiteEventKind::Down,
+                now - seen,
+                format!("site {name} is down, last seen {}s ago", now - seen),
+            ),
+            // the first ping after a restart of web is not a recovery
+            SiteHealth::Up if old == SiteHealth::Down => {
+                let down = SiteEventKind::Down;
+                let since = sqlx::query_scalar! {
+                    "select timestamp from sites_events where site = ? and
+                    kind = ? order by timestamp desc limit 1",
+                    id, down
+                }
+                .fetch_optional(&state.sql)
+                .await?
+                .unwrap_or(now);
+                (
+                    SiteEventKind::Up,
+                    now - since,
+                    format!("site {name} is up after {}s", now - since),
+                )
+            }
+            _ => continue,
+        };
+
+        sqlx::query! {
+            "insert or ignore into sites_events(
+                site, timestamp, kind, value, baseline, text
+            ) values(?,?,?,?,?,?)",
+            id, now, kind, value, grace, text
+        }
+        .execute(&state.sql)
+        .await?;
+
+        // the site was down from the last time it was seen until the first
+        // ping or dump after that
+        let cause = IncidentCause::MissedPings;
+        match kind {
+            SiteEventKind::Down => {
+                incident::open(&state.sql, id, cause, seen, &text).await?
+            }
+            _ => incident::close(&state.sql, id, cause, seen).await?,
+        }
+        utils::send_message(&text).await;
+    }
+
+    Ok(())
+}
+
+/// roll the minute stats up into hours and days and prune the old ones
+pub async fn stats(state: Data<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+
+    loop {
+        interval.tick().await;
+        if let Err(e) = stats::rollup(&state.sql).await {
+            log::error!("stats rollup: {e:?}");
+        }
+        if let Err(e) = stats::prune(&state.sql).await {
+            log::error!("stats prune: {e:?}");
+        }
+    }
+}
+
+/// check every hour for anomalies once its stats are complete, a few
+/// minutes after it ends
+pub async fn anomalies(state: Data<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+    let mut checked = 0;
+
+    loop {
+        interval.tick().await;
+        let done = utils::now() - 300 - 3600;
+        let hour = done - done.rem_euclid(3600);
+        if hour == checked {
+            continue;
+        }
+
+        match anomaly::check(&state, hour).await {
+            Ok(()) => checked = hour,
+            Err(e) => log::error!("anomaly check: {e:?}"),
+        }
+    }
+}
+
+/// evaluate the alert rules every minute
+pub async fn alerts(state: Data<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+
+    loop {
+        interval.tick().await;
+        if let Err(e) = alert::check(&state).await {
+            log::error!("alert check: {e:?}");
+        }
+    }
+}
+
+/// watch the burn rates of the slos every minute
+pub async fn slos(state: Data<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+
+    loop {
+        interval.tick().await;
+        if let Err(e) = slo::check(&state).await {
+            log::error!("slo check: {e:?}");
+        }
+    }
+}
+
+/// run the probes that are due, each has its own interval
+pub async fn probes(state: Data<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(10));
+
+    loop {
+        interval.tick().await;
+        if let Err(e) = probe::check(&state).await {
+            log::error!("probe check: {e:?}");
+        }
+    }
+}
+
+/// read the certificates of the sites every hour
+pub async fn certs(state: Data<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(3600));
+
+    loop {
+        interval.tick().await;
+        if let Err(e) = cert::check_all(&state).await {
+            log::error!("cert check: {e:?}");
+        }
+    }
+}
+
+/// report the heartbeats that are late
+pub async fn heartbeats(state: Data<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(30));
+
+    loop {
+        interval.tick().await;
+        if let Err(e) = heartbeat::check(&state).await {
+            log::error!("heartbeat check: {e:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::incident::Incident;
+    use crate::models::site::{Site, SiteEvent};
+
+    async fn site(state: &AppState, id: i64, online: bool) {
+        sqlx::query!("insert into sites(id, name) values(?, ?)", id, id)
+            .execute(&state.sql)
+            .await
+            .unwrap();
+        let site = Site { id, online, ..Default::default() };
+        state.sites.lock().await.insert(id, site);
+    }
+
+    async fn seen(state: &AppState, ago: i64) {
+        let ping = utils::now() - ago;
+        state.sites.lock().await.get_mut(&1).unwrap().latest_ping = ping;
+    }
+
+    async fn health(state: &AppState, id: i64) -> SiteHealth {
+        let stored =
+            sqlx::query_as!(Site, "select * from sites where id = ?", id)
+                .fetch_one(&state.sql)
+                .await
+                .unwrap();
+        let health = state.sites.lock().await[&id].health.clone();
+        assert_eq!(health, stored.health);
+        health
+    }
+
+    #[actix_web::test]
+    async fn health_transitions() {
+        let state = utils::test_state().await;
+        site(&state, 1, true).await;
+        site(&state, 2, false).await;
+        let grace = config().offline_after;
+
+        // never seen
+        health_check(&state).await.unwrap();
+        assert_eq!(health(&state, 1).await, SiteHealth::Unknown);
+
+        seen(&state, 10).await;
+        health_check(&state).await.unwrap();
+        assert_eq!(health(&state, 1).await, SiteHealth::Up);
+
+        seen(&state, grace + 10).await;
+        health_check(&state).await.unwrap();
+        assert_eq!(health(&state, 1).await, SiteHealth::Down);
+        // offline sites are left alone
+        state.sites.lock().await.get_mut(&2).unwrap().latest_ping = 1;
+        health_check(&state).await.unwrap();
+        assert_eq!(health(&state, 2).await, SiteHealth::Unknown);
+
+        seen(&state, 0).await;
+        health_check(&state).await.unwrap();
+        assert_eq!(health(&state, 1).await, SiteHealth::Up);
+
+        let events = sqlx::query_as! {
+            SiteEvent,
+            "select * from sites_events where site = 1 order by kind"
+        }
+        .fetch_all(&state.sql)
+        .await
+        .unwrap();
+        let kinds = events.iter().map(|e| e.kind.clone()).collect::<Vec<_>>();
+        assert_eq!(kinds, [SiteEventKind::Down, SiteEventKind::Up]);
+
+        // the incident covers the time the site was not seen
+        let incidents = sqlx::query_as!(Incident, "select * from incidents")
+            .fetch_all(&state.sql)
+            .await
+            .unwrap();
+        assert_eq!(incidents.len(), 1);
+        let incident = &incidents[0];
+        assert_eq!(incident.cause, IncidentCause::MissedPings);
+        assert!(incident.ended > 0);
+        assert!(incident.ended - incident.started >= grace + 10);
+    }
+}
@@ -10,25 +10,49 @@ use actix_web::{
     App, HttpResponse, HttpServer, Responder,
 };
 use models::site::Site;
+use models::status::StatusReport;
 use sqlx::sqlite::SqliteConnectOptions;
 use sqlx::sqlite::SqliteJournalMode;
 use sqlx::{Pool, Sqlite, SqlitePool};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::read_to_string;
 use std::str::FromStr;
 use tokio::sync::Mutex;
 use utoipa::OpenApi;
 
 mod admin;
+mod alert;
+mod anomaly;
 mod api;
+mod badge;
+mod cert;
 mod config;
 mod docs;
+mod forecast;
+mod heartbeat;
+mod incident;
+mod jobs;
 mod models;
+mod probe;
+mod slo;
+mod stats;
+mod status;
+mod systemd;
 mod utils;
 
 pub struct AppState {
     pub sql: Pool<Sqlite>,
+    /// the sites by id. the dumps and the watchdog wait on this lock, so
+    /// it is never held across database writes
     pub sites: Mutex<HashMap<i64, Site>>,
+    /// nonces of signed dumps, kept until their timestamp is too old
+    pub nonces: Mutex<HashMap<String, i64>>,
+    /// ids of the probes that are running, they are not started again
+    /// until they finish
+    pub probes: Mutex<HashSet<i64>>,
+    /// status page reports by slug, served again until they are
+    /// `Config::STATUS_CACHE` seconds old
+    pub status: Mutex<HashMap<String, StatusReport>>,
 }
 
 #[get("/openapi.json")]
@@ -38,9 +62,18 @@ async fn openapi() -> impl Responder {
     doc.merge(api::user::ApiDoc::openapi());
     doc.merge(api::verification::ApiDoc::openapi());
     doc.merge(api::sites::ApiDoc::openapi());
+    doc.merge(api::heartbeats::ApiDoc::openapi());
+    doc.merge(api::status::ApiDoc::openapi());
+    doc.merge(api::badges::ApiDoc::openapi());
 
     let mut admin_doc = ApiDoc::openapi();
     admin_doc.merge(admin::sites::ApiDoc::openapi());
+    admin_doc.merge(admin::alerts::ApiDoc::openapi());
+    admin_doc.merge(admin::incidents::ApiDoc::openapi());
+    admin_doc.merge(admin::slos::ApiDoc::openapi());
+    admin_doc.merge(admin::probes::ApiDoc::openapi());
+    admin_doc.merge(admin::heartbeats::ApiDoc::openapi());
+    admin_doc.merge(admin::status::ApiDoc::openapi());
 
     doc_add_prefix(&mut admin_doc, "/admin", false);
     doc.merge(admin_doc);
@@ -73,13 +106,26 @@ fn config_app(app: &mut ServiceConfig) {
     }
 
     app.service(openapi).service(rapidoc);
+    app.service(api::status::status_html);
     app.service(
         scope("/api")
             .service(api::user::router())
             .service(api::deploy::router())
             .service(api::verification::verification)
             .service(api::sites::router())
-            .service(scope("/admin").service(admin::sites::router())),
+            .service(api::heartbeats::router())
+            .service(api::status::router())
+            .service(api::badges::router())
+            .service(
+                scope("/admin")
+                    .service(admin::sites::router())
+                    .service(admin::alerts::router())
+                    .service(admin::incidents::router())
+                    .service(admin::slos::router())
+                    .service(admin::probes::router())
+                    .service(admin::heartbeats::router())
+                    .service(admin::status::router()),
+            ),
     );
     app.default_service(|r: ServiceRequest| {
         actix_utils::future::ok(
@@ -124,7 +170,15 @@ async fn main() -> std::io::Result<()> {
     .map(|s| (s.id, s.clone()))
     .collect::<HashMap<_, _>>();
 
-    let data = Data::new(AppState { sql: pool, sites: Mutex::new(sites) });
+    let data = Data::new(AppState {
+        sql: pool,
+        sites: Mutex::new(sites),
+        nonces: Mutex::new(HashMap::new()),
+        probes: Mutex::new(HashSet::new()),
+        status: Mutex::new(HashMap::new()),
+    });
+    let watchdog_data = data.clone();
+    let jobs_data = data.clone();
 
     let server = HttpServer::new(move || {
         App::new()
@@ -143,5 +197,16 @@ async fn main() -> std::io::Result<()> {
         server
     };
 
+    systemd::notify("READY=1");
+    actix_web::rt::spawn(systemd::watchdog(watchdog_data));
+    actix_web::rt::spawn(jobs::instances(jobs_data.clone()));
+    actix_web::rt::spawn(jobs::health(jobs_data.clone()));
+    actix_web::rt::spawn(jobs::stats(jobs_data.clone()));
+    actix_web::rt::spawn(jobs::anomalies(jobs_data.clone()));
+    actix_web::rt::spawn(jobs::alerts(jobs_data.clone()));
+    actix_web::rt::spawn(jobs::slos(jobs_data.clone()));
+    actix_web::rt::spawn(jobs::probes(jobs_data.clone()));
+    actix_web::rt::spawn(jobs::certs(jobs_data.clone()));
+    actix_web::rt::spawn(jobs::heartbeats(jobs_data));
     server.run().await
 }
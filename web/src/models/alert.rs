@@ -0,0 +1,127 @@
+use std::{future::Future, pin::Pin};
+
+use super::{not_found, AppErr};
+use crate::AppState;
+use actix_http::Payload;
+use actix_web::{
+    web::{Data, Path},
+    FromRequest, HttpRequest,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
+
+super::sql_enum! {
+    pub enum AlertMetric {
+        Requests,
+        ClientErrors,
+        ServerErrors,
+        MeanTime,
+        P95,
+        SincePing,
+    }
+
+    pub enum AlertComparator {
+        Gt,
+        Ge,
+        Lt,
+        Le,
+    }
+
+    pub enum AlertSeverity {
+        Info,
+        Warning,
+        Critical,
+    }
+
+    pub enum AlertState {
+        Pending,
+        Firing,
+        Resolved,
+    }
+}
+
+impl AlertComparator {
+    pub fn check(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            Self::Gt => value > threshold,
+            Self::Ge => value >= threshold,
+            Self::Lt => value < threshold,
+            Self::Le => value <= threshold,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+        }
+    }
+}
+
+/// fires when `metric` of a site compared to `threshold` holds for
+/// `duration` seconds
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema, Clone, Default)]
+pub struct AlertRule {
+    pub id: i64,
+    /// `None` for a rule that applies to every online site
+    pub site: Option<i64>,
+    pub name: String,
+    /// requests in the period, client_errors and server_errors as the
+    /// share of 4xx and 5xx responses from 0 to 1, mean_time and p95 in
+    /// milliseconds, or since_ping in seconds which ignores the period
+    pub metric: AlertMetric,
+    pub comparator: AlertComparator,
+    pub threshold: f64,
+    /// seconds of stats the metric is computed over
+    pub period: i64,
+    /// seconds the condition has to hold before the alert fires
+    pub duration: i64,
+    pub severity: AlertSeverity,
+    pub enabled: bool,
+    pub timestamp: i64,
+}
+
+/// a rule matching a site. it is pending until the rule duration passes,
+/// then firing until the condition stops holding
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema, Clone, Default)]
+pub struct Alert {
+    pub id: i64,
+    pub rule: i64,
+    pub site: i64,
+    pub state: AlertState,
+    /// the latest value of the metric
+    pub value: f64,
+    pub started: i64,
+    pub fired: i64,
+    pub resolved: i64,
+    pub updated: i64,
+}
+
+impl FromRequest for AlertRule {
+    type Error = AppErr;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _pl: &mut Payload) -> Self::Future {
+        #[derive(Deserialize)]
+        struct Rid {
+            rule_id: i64,
+        }
+        let state = req.app_data::<Data<AppState>>().unwrap().clone();
+        let path = Path::<Rid>::extract(req);
+
+        Box::pin(async move {
+            let id = path.await?.rule_id;
+            sqlx::query_as! {
+                AlertRule,
+                "select * from alert_rules where id = ?",
+                id
+            }
+            .fetch_optional(&state.sql)
+            .await?
+            .ok_or(not_found!("no alert rule was found"))
+        })
+    }
+}
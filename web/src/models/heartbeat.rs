@@ -0,0 +1,86 @@
+use std::{future::Future, pin::Pin};
+
+use super::{not_found, AppErr};
+use crate::AppState;
+use actix_http::Payload;
+use actix_web::{
+    web::{Data, Path},
+    FromRequest, HttpRequest,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
+
+super::sql_enum! {
+    pub enum HeartbeatState {
+        New,
+        Up,
+        Started,
+        Late,
+        Failed,
+    }
+
+    pub enum HeartbeatKind {
+        Success,
+        Start,
+        Fail,
+    }
+}
+
+/// a job that pings `/api/heartbeats/{token}/` every `period` seconds. it
+/// is late once a ping is `grace` seconds overdue or a started run did not
+/// finish within `grace`
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema, Clone, Default)]
+pub struct Heartbeat {
+    pub id: i64,
+    pub site: Option<i64>,
+    pub name: String,
+    /// the secret part of the ping url
+    pub token: String,
+    pub period: i64,
+    pub grace: i64,
+    /// new until the first ping, nothing is reported for new heartbeats
+    pub state: HeartbeatState,
+    /// the latest success or fail
+    pub last_ping: i64,
+    pub last_start: i64,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema, Clone, Default)]
+pub struct HeartbeatPing {
+    pub id: i64,
+    pub heartbeat: i64,
+    pub timestamp: i64,
+    pub kind: HeartbeatKind,
+    /// seconds since the start of the run, 0 without a start
+    pub duration: i64,
+    /// the request body, e.g. the output of the job
+    pub output: String,
+}
+
+impl FromRequest for Heartbeat {
+    type Error = AppErr;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _pl: &mut Payload) -> Self::Future {
+        #[derive(Deserialize)]
+        struct Hid {
+            heartbeat_id: i64,
+        }
+        let state = req.app_data::<Data<AppState>>().unwrap().clone();
+        let path = Path::<Hid>::extract(req);
+
+        Box::pin(async move {
+            let id = path.await?.heartbeat_id;
+            sqlx::query_as! {
+                Heartbeat,
+                "select * from heartbeats where id = ?",
+                id
+            }
+            .fetch_optional(&state.sql)
+            .await?
+            .ok_or(not_found!("no heartbeat was found"))
+        })
+    }
+}
@@ -0,0 +1,84 @@
+use std::{future::Future, pin::Pin};
+
+use super::{not_found, AppErr};
+use crate::AppState;
+use actix_http::Payload;
+use actix_web::{
+    web::{Data, Path},
+    FromRequest, HttpRequest,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
+
+super::sql_enum! {
+    pub enum IncidentCause {
+        MissedPings,
+        ProbeFailure,
+        ErrorRate,
+        Manual,
+    }
+}
+
+/// a period a site was down or failing. missed pings, failing probes and
+/// error rate breaches (critical `server_errors` alerts) are opened and
+/// closed automatically, admins can add others by hand
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema, Clone, Default)]
+pub struct Incident {
+    pub id: i64,
+    pub site: i64,
+    pub cause: IncidentCause,
+    pub started: i64,
+    /// 0 while the incident is open
+    pub ended: i64,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema, Clone, Default)]
+pub struct IncidentNote {
+    pub id: i64,
+    pub incident: i64,
+    /// the admin who wrote it, `None` once they are deleted
+    pub author: Option<i64>,
+    pub timestamp: i64,
+    pub text: String,
+}
+
+/// uptime of a site from `from` to `to`. overlapping incidents are only
+/// counted once and the range ends now at the latest
+#[derive(Debug, Serialize, ToSchema, Default)]
+pub struct Uptime {
+    pub site: i64,
+    pub from: i64,
+    pub to: i64,
+    /// from 0 to 100
+    pub uptime: f64,
+    pub downtime_minutes: f64,
+    pub incidents: i64,
+}
+
+impl FromRequest for Incident {
+    type Error = AppErr;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _pl: &mut Payload) -> Self::Future {
+        #[derive(Deserialize)]
+        struct Iid {
+            incident_id: i64,
+        }
+        let state = req.app_data::<Data<AppState>>().unwrap().clone();
+        let path = Path::<Iid>::extract(req);
+
+        Box::pin(async move {
+            let id = path.await?.incident_id;
+            sqlx::query_as! {
+                Incident,
+                "select * from incidents where id = ?",
+                id
+            }
+            .fetch_optional(&state.sql)
+            .await?
+            .ok_or(not_found!("no incident was found"))
+        })
+    }
+}
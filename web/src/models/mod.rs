@@ -1,9 +1,13 @@
+pub mod alert;
 pub mod common;
 pub mod deploy;
 mod error;
+pub mod heartbeat;
+pub mod incident;
+pub mod probe;
 pub mod site;
+pub mod slo;
+pub mod status;
 pub mod user;
 pub use common::*;
-pub(crate) use error::{
-    bad_auth, bad_request, forbidden, not_found, AppErr,
-};
+pub(crate) use error::{bad_auth, bad_request, forbidden, not_found, AppErr};
@@ -0,0 +1,108 @@
+use std::{future::Future, pin::Pin};
+
+use super::{not_found, AppErr};
+use crate::AppState;
+use actix_http::Payload;
+use actix_web::{
+    web::{Data, Path},
+    FromRequest, HttpRequest,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
+
+super::sql_enum! {
+    pub enum ProbeMethod {
+        Get,
+        Head,
+        Post,
+        Put,
+        Patch,
+        Delete,
+        Options,
+    }
+}
+
+impl ProbeMethod {
+    pub fn method(&self) -> awc::http::Method {
+        match self {
+            Self::Get => awc::http::Method::GET,
+            Self::Head => awc::http::Method::HEAD,
+            Self::Post => awc::http::Method::POST,
+            Self::Put => awc::http::Method::PUT,
+            Self::Patch => awc::http::Method::PATCH,
+            Self::Delete => awc::http::Method::DELETE,
+            Self::Options => awc::http::Method::OPTIONS,
+        }
+    }
+}
+
+/// an http check of a site run by the web service every `interval`
+/// seconds, it fails on errors, timeouts, an unexpected status or a body
+/// without the expected text
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema, Clone, Default)]
+pub struct Probe {
+    pub id: i64,
+    pub site: i64,
+    pub name: String,
+    pub url: String,
+    pub method: ProbeMethod,
+    /// expected status, 0 accepts any 2xx
+    pub status: i64,
+    /// text the body has to contain, empty skips the check
+    pub body: String,
+    /// ms
+    pub timeout: i64,
+    /// seconds between the checks
+    pub interval: i64,
+    /// failures in a row before a probe_failure incident is opened
+    pub failures: i64,
+    pub enabled: bool,
+    /// current failures in a row
+    pub streak: i64,
+    /// first failure of the streak, 0 while the probe passes
+    pub since: i64,
+    /// the latest check
+    pub checked: i64,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema, Clone, Default)]
+pub struct ProbeResult {
+    pub id: i64,
+    pub probe: i64,
+    pub timestamp: i64,
+    pub ok: bool,
+    /// 0 without a response
+    pub status: i64,
+    /// ms until the body was read
+    pub latency: i64,
+    /// why the check failed, empty when it passed
+    pub error: String,
+}
+
+impl FromRequest for Probe {
+    type Error = AppErr;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _pl: &mut Payload) -> Self::Future {
+        #[derive(Deserialize)]
+        struct Pid {
+            probe_id: i64,
+        }
+        let state = req.app_data::<Data<AppState>>().unwrap().clone();
+        let path = Path::<Pid>::extract(req);
+
+        Box::pin(async move {
+            let id = path.await?.probe_id;
+            sqlx::query_as! {
+                Probe,
+                "select * from probes where id = ?",
+                id
+            }
+            .fetch_optional(&state.sql)
+            .await?
+            .ok_or(not_found!("no probe was found"))
+        })
+    }
+}
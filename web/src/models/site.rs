@@ -37,6 +37,266 @@ pub struct Site {
     pub online: bool,
     pub latest_message_timestamp: i64,
     pub latest_dump_timestamp: i64,
+    pub degraded: bool,
+    /// down without a ping or a dump within `Config::offline_after`,
+    /// unknown until the first one arrives
+    pub health: SiteHealth,
+    /// apdex threshold in ms: requests within it are satisfied, within
+    /// four times it tolerating
+    pub apdex_t: i64,
+    /// host the certificate is read from, empty skips the check
+    pub tls_host: String,
+    pub tls_port: i64,
+    /// expiring within the first of `Config::CERT_WARN_DAYS`, invalid
+    /// when the chain or the host does not verify, error when it could not
+    /// be read
+    pub cert_status: CertStatus,
+    /// expiry of the served certificate, 0 until it is read
+    pub cert_expires: i64,
+    /// key of the public badges, they are off while it is null
+    pub badge_key: Option<String>,
+    /// hmac key of signed dumps, dog keeps it as `HEIMDALL_SIGNING_KEY`
+    pub signing_key: Option<String>,
+    /// reject dumps that are not signed with `signing_key`
+    pub signature_required: bool,
+}
+
+super::sql_enum! {
+    pub enum SiteHealth {
+        Unknown,
+        Up,
+        Down,
+    }
+
+    pub enum CertStatus {
+        Unknown,
+        Valid,
+        Expiring,
+        Expired,
+        Invalid,
+        Error,
+    }
+}
+
+/// the latest certificate served at the `tls_host` of a site
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema, Clone, Default)]
+pub struct SiteCert {
+    pub site: i64,
+    pub host: String,
+    pub port: i64,
+    /// when it was checked
+    pub timestamp: i64,
+    pub subject: String,
+    pub issuer: String,
+    #[schema(value_type = Vec<String>)]
+    pub sans: JsonStr<Vec<String>>,
+    /// subjects of the served chain from the leaf up
+    #[schema(value_type = Vec<String>)]
+    pub chain: JsonStr<Vec<String>>,
+    pub not_before: i64,
+    pub not_after: i64,
+    /// why the connection or the verification failed, empty when valid
+    pub error: String,
+    /// smallest warning sent for this certificate in days, 0 for none
+    pub warned: i64,
+}
+
+/// a dog reporting for a site, sites behind several hosts have one each
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema, Clone, Default)]
+pub struct SiteInstance {
+    pub site: i64,
+    pub instance: String,
+    /// sequence number of the latest applied dump
+    pub seq: i64,
+    pub version: String,
+    pub latest_ping: i64,
+    pub latest_dump: i64,
+    pub total_requests: i64,
+    pub total_requests_time: i64,
+    pub requests_max_time: i64,
+    pub requests_min_time: i64,
+    #[schema(value_type = HashMap<String, u64>)]
+    pub status: JsonStr<HashMap<String, Status>>,
+}
+
+/// requests of a site in the bucket of `step` seconds starting at
+/// `timestamp`. the steps are listed in `Config::STATS_STEPS`
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema, Clone, Default)]
+pub struct SiteStat {
+    pub site: i64,
+    pub step: i64,
+    pub timestamp: i64,
+    pub total: i64,
+    pub total_time: i64,
+    pub max_time: i64,
+    pub min_time: i64,
+    #[schema(value_type = HashMap<String, u64>)]
+    pub status: JsonStr<HashMap<String, Status>>,
+    /// already included in the bucket of the next step
+    pub rolled: bool,
+    /// request count per `Config::LATENCY_BUCKETS`, empty when the dogs
+    /// did not send it
+    #[schema(value_type = Vec<i64>)]
+    pub latency: JsonStr<Vec<i64>>,
+    /// distinct client addresses per dump window, summed. a client seen in
+    /// several windows is counted in each
+    pub visitors: i64,
+}
+
+impl SiteStat {
+    pub fn add(&mut self, other: &SiteStat) {
+        self.total += other.total;
+        self.visitors += other.visitors;
+        self.total_time += other.total_time;
+        self.max_time = self.max_time.max(other.max_time);
+        if other.min_time < self.min_time || self.min_time == 0 {
+            self.min_time = other.min_time;
+        }
+        merge_status(&mut self.status, &other.status);
+
+        if self.latency.len() < other.latency.len() {
+            self.latency.resize(other.latency.len(), 0);
+        }
+        for (a, b) in self.latency.iter_mut().zip(other.latency.iter()) {
+            *a += b;
+        }
+    }
+}
+
+/// requests of a route of a site in the hour or day starting at
+/// `timestamp`. the route is the path with the ids replaced by `:id`
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema, Clone, Default)]
+pub struct SiteRoute {
+    pub site: i64,
+    /// 3600 or 86400
+    pub step: i64,
+    pub timestamp: i64,
+    pub route: String,
+    pub total: i64,
+    pub total_time: i64,
+    pub max_time: i64,
+    /// 5xx responses
+    pub errors: i64,
+    /// request count per `Config::LATENCY_BUCKETS`
+    #[schema(value_type = Vec<i64>)]
+    pub latency: JsonStr<Vec<i64>>,
+}
+
+impl SiteRoute {
+    pub fn add(&mut self, other: &SiteRoute) {
+        self.total += other.total;
+        self.total_time += other.total_time;
+        self.max_time = self.max_time.max(other.max_time);
+        self.errors += other.errors;
+        if self.latency.len() < other.latency.len() {
+            self.latency.resize(other.latency.len(), 0);
+        }
+        for (a, b) in self.latency.iter_mut().zip(other.latency.iter()) {
+            *a += b;
+        }
+    }
+}
+
+/// apdex of a stats bucket or of a route for the `apdex_t` of the site.
+/// the counts are estimated from the latency buckets and 5xx responses are
+/// frustrated whatever their time
+#[derive(Debug, Serialize, ToSchema, Default)]
+pub struct SiteApdex {
+    /// start of the bucket, `from` for the routes
+    pub timestamp: i64,
+    /// empty for the buckets
+    pub route: String,
+    pub total: i64,
+    pub satisfied: f64,
+    pub tolerating: f64,
+    pub frustrated: f64,
+    /// from 0 to 1, `None` without latency data
+    pub score: Option<f64>,
+}
+
+/// the requests of a site from `from` to `to`, computed from its stats
+#[derive(Debug, Serialize, ToSchema, Default)]
+pub struct SiteSummary {
+    pub from: i64,
+    pub to: i64,
+    pub total: i64,
+    pub requests_per_day: f64,
+    pub requests_per_hour: f64,
+    /// share of the requests answered with 4xx, from 0 to 1
+    pub error_rate_4xx: f64,
+    /// share of the requests answered with 5xx, from 0 to 1
+    pub error_rate_5xx: f64,
+    /// response times in milliseconds
+    pub mean_time: f64,
+    pub min_time: i64,
+    pub max_time: i64,
+    /// estimated from the latency buckets, missing without latency data
+    pub p50: Option<i64>,
+    pub p90: Option<i64>,
+    pub p95: Option<i64>,
+    pub p99: Option<i64>,
+}
+
+pub fn merge_status(
+    status: &mut HashMap<String, Status>, new: &HashMap<String, Status>,
+) {
+    for (sk, ns) in new.iter() {
+        if let Some(os) = status.get_mut(sk) {
+            os.count += ns.count;
+            os.total_time += ns.total_time;
+            os.max_time = os.max_time.max(ns.max_time);
+            if ns.min_time < os.min_time || os.min_time == 0 {
+                os.min_time = ns.min_time;
+            }
+        } else {
+            status.insert(sk.clone(), ns.clone());
+        }
+    }
+}
+
+/// forecasted requests of a day, with the bounds of its 95% band
+#[derive(Debug, Serialize, ToSchema, Default)]
+pub struct SiteForecast {
+    /// start of the day
+    pub timestamp: i64,
+    pub requests: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+super::sql_enum! {
+    pub enum SiteEventKind {
+        TrafficDrop,
+        ErrorSpike,
+        LatencyJump,
+        Down,
+        Up,
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema, Clone, Default)]
+pub struct SiteEvent {
+    pub id: i64,
+    pub site: i64,
+    pub timestamp: i64,
+    pub kind: SiteEventKind,
+    /// the value that was seen and what was expected from the baseline.
+    /// for down the seconds since the site was last seen and the grace
+    /// period, for up the seconds it was down
+    pub value: f64,
+    pub baseline: f64,
+    /// robust z-score of the value against the baseline
+    pub score: f64,
+    pub text: String,
+}
+
+impl SiteInstance {
+    /// an instance that pinged before but not in the last `SILENT_AFTER`
+    /// seconds, until it pings again or an admin removes it. replays never
+    /// ping and are not counted
+    pub fn silent(&self, now: i64) -> bool {
+        self.latest_ping > 0 && self.latest_ping < now - Config::SILENT_AFTER
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow, ToSchema, Clone, Default)]
@@ -48,6 +308,30 @@ pub struct SiteMessage {
     pub tag: String,
 }
 
+super::sql_enum! {
+    pub enum MetricKind {
+        Counter,
+        Gauge,
+        Timer,
+        Set,
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema, Clone, Default)]
+pub struct SiteMetric {
+    pub id: i64,
+    pub site: i64,
+    pub name: String,
+    pub kind: MetricKind,
+    pub timestamp: i64,
+    /// sum for counters and timers, latest value for gauges and
+    /// unique members for sets
+    pub value: f64,
+    pub count: i64,
+    pub min: f64,
+    pub max: f64,
+}
+
 impl Site {
     pub fn verify_name(name: &str) -> Result<(), AppErr> {
         if name.is_empty() || name.len() > 100 {
@@ -60,6 +344,20 @@ impl Site {
 
         Ok(())
     }
+
+    /// a dns name or an ip, empty is allowed
+    pub fn verify_host(host: &str) -> Result<(), AppErr> {
+        if host.len() > 253 {
+            return Err(bad_request!("tls_host is too long"));
+        }
+
+        if !host.chars().all(|c| c.is_ascii_alphanumeric() || ".-:".contains(c))
+        {
+            return Err(bad_request!("invalid tls_host characters"));
+        }
+
+        Ok(())
+    }
 }
 
 impl FromRequest for Site {
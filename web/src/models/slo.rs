@@ -0,0 +1,86 @@
+use std::{future::Future, pin::Pin};
+
+use super::{not_found, AppErr};
+use crate::AppState;
+use actix_http::Payload;
+use actix_web::{
+    web::{Data, Path},
+    FromRequest, HttpRequest,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
+
+super::sql_enum! {
+    pub enum SloKind {
+        Availability,
+        Latency,
+    }
+}
+
+/// `target` of the requests of the last `days` days are good: not 5xx for
+/// availability, answered within `threshold` ms for latency
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema, Clone, Default)]
+pub struct Slo {
+    pub id: i64,
+    pub site: i64,
+    pub name: String,
+    pub kind: SloKind,
+    /// from 0 to 1, e.g. 0.995
+    pub target: f64,
+    pub threshold: i64,
+    pub days: i64,
+    /// burn rate that both the 1h and 6h windows have to reach before a
+    /// fast burn is reported, 0 never reports it
+    pub burn_alert: f64,
+    /// the budget is burning fast right now
+    pub burning: bool,
+    pub timestamp: i64,
+}
+
+/// how an slo is doing, computed from the stats
+#[derive(Debug, Serialize, ToSchema, Default)]
+pub struct SloReport {
+    pub slo: Slo,
+    /// requests over the slo days, only the ones with latency data for
+    /// latency slos
+    pub total: i64,
+    /// requests that were not good, estimated from the latency buckets for
+    /// latency slos
+    pub bad: f64,
+    /// share of good requests over the slo days, `None` without requests
+    /// or latency data
+    pub compliance: Option<f64>,
+    /// share of the error budget left, below 0 once it is spent
+    pub budget_remaining: Option<f64>,
+    /// how many times faster than allowed the budget was spent in the last
+    /// hour and 6 hours, 1 spends exactly the budget over the slo days
+    pub burn_1h: Option<f64>,
+    pub burn_6h: Option<f64>,
+}
+
+impl FromRequest for Slo {
+    type Error = AppErr;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _pl: &mut Payload) -> Self::Future {
+        #[derive(Deserialize)]
+        struct Sid {
+            slo_id: i64,
+        }
+        let state = req.app_data::<Data<AppState>>().unwrap().clone();
+        let path = Path::<Sid>::extract(req);
+
+        Box::pin(async move {
+            let id = path.await?.slo_id;
+            sqlx::query_as! {
+                Slo,
+                "select * from slos where id = ?",
+                id
+            }
+            .fetch_optional(&state.sql)
+            .await?
+            .ok_or(not_found!("no slo was found"))
+        })
+    }
+}
@@ -0,0 +1,111 @@
+use std::{future::Future, pin::Pin};
+
+use super::{not_found, AppErr, JsonStr};
+use crate::models::incident::IncidentCause;
+use crate::AppState;
+use actix_http::Payload;
+use actix_web::{
+    web::{Data, Path},
+    FromRequest, HttpRequest,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
+
+/// a public page listing a group of sites, served without auth at
+/// `/status/{slug}/` and `/api/status/{slug}/`
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema, Clone, Default)]
+pub struct StatusPage {
+    pub id: i64,
+    pub slug: String,
+    pub title: String,
+    pub description: String,
+    /// ids of the sites in the order they are listed
+    #[schema(value_type = Vec<i64>)]
+    pub sites: JsonStr<Vec<i64>>,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusLevel {
+    Operational,
+    Degraded,
+    Outage,
+    Unknown,
+}
+
+/// the uptime of a day in utc
+#[derive(Debug, Serialize, ToSchema, Clone)]
+pub struct StatusDay {
+    /// start of the day
+    pub timestamp: i64,
+    /// from 0 to 100, null for the days before the site was added
+    pub uptime: Option<f64>,
+    pub downtime_minutes: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema, Clone)]
+pub struct StatusNote {
+    pub timestamp: i64,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, ToSchema, Clone)]
+pub struct StatusIncident {
+    pub cause: IncidentCause,
+    pub started: i64,
+    pub text: String,
+    /// newest first
+    pub notes: Vec<StatusNote>,
+}
+
+/// what a status page shows of a site, nothing private like its stats
+#[derive(Debug, Serialize, ToSchema, Clone)]
+pub struct StatusSite {
+    pub name: String,
+    pub status: StatusLevel,
+    /// over the days of the bars since the site was added, from 0 to 100
+    pub uptime: f64,
+    /// oldest first, the last one is today
+    pub days: Vec<StatusDay>,
+    /// the incidents that are still open
+    pub incidents: Vec<StatusIncident>,
+}
+
+#[derive(Debug, Serialize, ToSchema, Clone)]
+pub struct StatusReport {
+    pub slug: String,
+    pub title: String,
+    pub description: String,
+    /// the worst status of the sites
+    pub status: StatusLevel,
+    pub sites: Vec<StatusSite>,
+    pub timestamp: i64,
+}
+
+impl FromRequest for StatusPage {
+    type Error = AppErr;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _pl: &mut Payload) -> Self::Future {
+        #[derive(Deserialize)]
+        struct Pid {
+            page_id: i64,
+        }
+        let state = req.app_data::<Data<AppState>>().unwrap().clone();
+        let path = Path::<Pid>::extract(req);
+
+        Box::pin(async move {
+            let id = path.await?.page_id;
+            sqlx::query_as! {
+                StatusPage,
+                "select * from status_pages where id = ?",
+                id
+            }
+            .fetch_optional(&state.sql)
+            .await?
+            .ok_or(not_found!("no status page was found"))
+        })
+    }
+}
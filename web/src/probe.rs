@@ -0,0 +1,253 @@
+use std::time::{Duration, Instant};
+
+use actix_web::web::Data;
+use sqlx::{Pool, Sqlite};
+
+use crate::config::Config;
+use crate::models::incident::IncidentCause;
+use crate::models::probe::{Probe, ProbeMethod, ProbeResult};
+use crate::utils::CutOff;
+use crate::{incident, utils, AppState};
+
+/// run a probe once, the result is not stored
+pub async fn run(probe: &Probe) -> ProbeResult {
+    let timeout = Duration::from_millis(probe.timeout.max(1) as u64);
+    let client = awc::Client::builder().timeout(timeout).finish();
+    let mut result = ProbeResult {
+        probe: probe.id,
+        timestamp: utils::now(),
+        ..Default::default()
+    };
+
+    let start = Instant::now();
+    let response = client.request(probe.method.method(), &probe.url).send();
+    let error = match response.await {
+        Err(e) => Some(e.to_string()),
+        Ok(mut response) => {
+            let status = response.status();
+            let body = response.body().limit(Config::PROBE_BODY_MAX).await;
+            result.status = status.as_u16() as i64;
+
+            let expected = match probe.status {
+                0 => status.is_success(),
+                s => result.status == s,
+            };
+            if !expected {
+                Some(format!("unexpected status {}", result.status))
+            } else if probe.body.is_empty() || probe.method == ProbeMethod::Head
+            {
+                None
+            } else {
+                let text = probe.body.as_str();
+                match body.map(|b| String::from_utf8_lossy(&b).contains(text)) {
+                    Err(e) => Some(format!("reading the body: {e}")),
+                    Ok(false) => Some("the expected text was not found".into()),
+                    Ok(true) => None,
+                }
+            }
+        }
+    };
+    result.latency = start.elapsed().as_millis() as i64;
+    result.ok = error.is_none();
+    result.error = error.unwrap_or_default();
+    result.error.cut_off(1024);
+
+    result
+}
+
+/// keep the probe_failure incident of a site open while any of its
+/// enabled probes failed `failures` times in a row
+pub async fn settle(
+    pool: &Pool<Sqlite>, site: i64, now: i64,
+) -> Result<(), sqlx::Error> {
+    let since = sqlx::query_scalar! {
+        "select since from probes where site = ? and enabled = true and
+        streak >= failures order by since limit 1",
+        site
+    }
+    .fetch_optional(pool)
+    .await?;
+
+    let cause = IncidentCause::ProbeFailure;
+    match since {
+        Some(since) => {
+            let text = "synthetic probes are failing";
+            incident::open(pool, site, cause, since, text).await
+        }
+        None => incident::close(pool, site, cause, now).await,
+    }
+}
+
+/// store the result of a probe, move its failure streak along and report
+/// when it starts failing or passes again
+pub async fn record(
+    state: &AppState, probe: &Probe, result: &mut ProbeResult,
+) -> Result<(), sqlx::Error> {
+    let r = sqlx::query! {
+        "insert into probes_results(
+            probe, timestamp, ok, status, latency, error
+        ) values(?,?,?,?,?,?)",
+        result.probe, result.timestamp, result.ok, result.status,
+        result.latency, result.error
+    }
+    .execute(&state.sql)
+    .await?;
+    result.id = r.last_insert_rowid();
+
+    let (streak, since) = match result.ok {
+        true => (0, 0),
+        false if probe.since == 0 => (probe.streak + 1, result.timestamp),
+        false => (probe.streak + 1, probe.since),
+    };
+    sqlx::query! {
+        "update probes set streak = ?, since = ?, checked = ? where id = ?",
+        streak, since, result.timestamp, probe.id
+    }
+    .execute(&state.sql)
+    .await?;
+
+    // manual runs of a disabled probe are only recorded
+    let failing = streak >= probe.failures;
+    if !probe.enabled || failing == (probe.streak >= probe.failures) {
+        return Ok(());
+    }
+
+    settle(&state.sql, probe.site, result.timestamp).await?;
+    let site = state.sites.lock().await.get(&probe.site).cloned();
+    let name = site.map(|s| s.name).unwrap_or_default();
+    let text = match failing {
+        true => format!(
+            "site {name}: probe {} failed {streak} times in a row, {}",
+            probe.name, result.error
+        ),
+        false => format!(
+            "site {name}: probe {} passes again after {}s",
+            probe.name,
+            result.timestamp - probe.since
+        ),
+    };
+    utils::send_message(&text).await;
+
+    Ok(())
+}
+
+/// start the enabled probes of the online sites that are due. each one
+/// runs and records its result on its own, so a slow probe does not hold
+/// back the others
+pub async fn check(state: &Data<AppState>) -> Result<(), sqlx::Error> {
+    let now = utils::now();
+    let probes = sqlx::query_as! {
+        Probe,
+        "select * from probes where enabled = true and checked + interval <= ?",
+        now
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    let sites = state.sites.lock().await;
+    let probes = probes
+        .into_iter()
+        .filter(|p| sites.get(&p.site).is_some_and(|s| s.online))
+        .collect::<Vec<_>>();
+    drop(sites);
+
+    let mut running = state.probes.lock().await;
+    for probe in probes {
+        if !running.insert(probe.id) {
+            continue;
+        }
+
+        let state = state.clone();
+        actix_web::rt::spawn(async move {
+            let mut result = run(&probe).await;
+            if let Err(e) = record(&state, &probe, &mut result).await {
+                log::error!("probe {} record: {e:?}", probe.id);
+            }
+            state.probes.lock().await.remove(&probe.id);
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{web, App, HttpResponse, HttpServer};
+
+    /// a local server with a healthy, a failing and a slow route
+    fn serve() -> String {
+        let server = HttpServer::new(|| {
+            App::new()
+                .route("/ok/", web::get().to(|| async { "all good" }))
+                .route(
+                    "/down/",
+                    web::get().to(|| async {
+                        HttpResponse::ServiceUnavailable().body("down")
+                    }),
+                )
+                .route(
+                    "/slow/",
+                    web::get().to(|| async {
+                        tokio::time::sleep(Duration::from_secs(2)).await;
+                        "late"
+                    }),
+                )
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+
+        let addr = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+        format!("http://{addr}")
+    }
+
+    fn probe(url: String, body: &str) -> Probe {
+        Probe { url, body: body.into(), timeout: 1000, ..Default::default() }
+    }
+
+    #[actix_web::test]
+    async fn passes() {
+        let base = serve();
+        let result = run(&probe(format!("{base}/ok/"), "good")).await;
+        assert!(result.ok, "{}", result.error);
+        assert_eq!(result.status, 200);
+        assert!(result.error.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn unexpected_status() {
+        let base = serve();
+        let result = run(&probe(format!("{base}/down/"), "")).await;
+        assert!(!result.ok);
+        assert_eq!(result.status, 503);
+        assert_eq!(result.error, "unexpected status 503");
+
+        // an explicit status replaces the 2xx check
+        let mut p = probe(format!("{base}/down/"), "");
+        p.status = 503;
+        assert!(run(&p).await.ok);
+    }
+
+    #[actix_web::test]
+    async fn missing_text() {
+        let base = serve();
+        let result = run(&probe(format!("{base}/ok/"), "bad")).await;
+        assert!(!result.ok);
+        assert_eq!(result.status, 200);
+        assert_eq!(result.error, "the expected text was not found");
+    }
+
+    #[actix_web::test]
+    async fn timeout() {
+        let base = serve();
+        let mut p = probe(format!("{base}/slow/"), "");
+        p.timeout = 200;
+        let result = run(&p).await;
+        assert!(!result.ok);
+        assert_eq!(result.status, 0);
+        assert!(!result.error.is_empty());
+        assert!(result.latency < 2000, "took {}ms", result.latency);
+    }
+}
@@ -0,0 +1,282 @@
+use sqlx::{Pool, Sqlite};
+
+use crate::models::site::SiteStat;
+use crate::models::slo::{Slo, SloKind, SloReport};
+use crate::{stats, utils, AppState};
+
+/// requests that were not good and all the requests of a stat. `None` for
+/// a latency slo without latency data
+fn bad(slo: &Slo, stat: &SiteStat) -> Option<(f64, f64)> {
+    match slo.kind {
+        SloKind::Availability => {
+            let bad = stat
+                .status
+                .values()
+                .filter(|s| s.code >= 500)
+                .map(|s| s.count as f64)
+                .sum::<f64>();
+            Some((bad, stat.total as f64))
+        }
+        SloKind::Latency => {
+            let latency = stats::latency(stat);
+            let good = stats::below(latency, stat.max_time, slo.threshold)?;
+            let total = latency.iter().sum::<i64>() as f64;
+            Some(((total - good).max(0.0), total))
+        }
+    }
+}
+
+/// the burn rate of the budget over the stat, `None` without requests
+fn burn(slo: &Slo, stat: &SiteStat) -> Option<f64> {
+    let (bad, total) = bad(slo, stat)?;
+    if total == 0.0 {
+        return None;
+    }
+
+    let allowed = (1.0 - slo.target).max(f64::EPSILON);
+    Some(bad / total / allowed)
+}
+
+async fn burns(
+    pool: &Pool<Sqlite>, slo: &Slo, to: i64,
+) -> Result<(Option<f64>, Option<f64>), sqlx::Error> {
+    let hour = stats::total(pool, slo.site, to - 3600, to).await?;
+    let hours = stats::total(pool, slo.site, to - 6 * 3600, to).await?;
+    Ok((burn(slo, &hour), burn(slo, &hours)))
+}
+
+/// the end of the last complete minute, the current one is still dumped
+fn end() -> i64 {
+    let now = utils::now();
+    now - now.rem_euclid(60)
+}
+
+pub async fn report(
+    pool: &Pool<Sqlite>, slo: Slo,
+) -> Result<SloReport, sqlx::Error> {
+    let to = end();
+    let stat = stats::total(pool, slo.site, to - slo.days * 86400, to).await?;
+    let (burn_1h, burn_6h) = burns(pool, &slo, to).await?;
+
+    let mut report = SloReport::default();
+    if let Some((bad, total)) = bad(&slo, &stat).filter(|(_, t)| *t > 0.0) {
+        let allowed = (1.0 - slo.target) * total;
+        report.total = total as i64;
+        report.bad = bad;
+        report.compliance = Some(1.0 - bad / total);
+        report.budget_remaining = Some(1.0 - bad / allowed.max(f64::EPSILON));
+    }
+    report.burn_1h = burn_1h;
+    report.burn_6h = burn_6h;
+    report.slo = slo;
+
+    Ok(report)
+}
+
+/// report a fast burn when both the 1h and 6h burn rates of an slo reach
+/// its `burn_alert`, and again once it stops
+pub async fn check(state: &AppState) -> Result<(), sqlx::Error> {
+    let to = end();
+    let slos = sqlx::query_as! {
+        Slo,
+        "select * from slos where burn_alert > 0 or burning = true"
+    }
+    .fetch_all(&state.sql)
+    .await?;
+
+    let mut messages = Vec::new();
+    for slo in slos {
+        let site = state.sites.lock().await.get(&slo.site).cloned();
+        let Some(site) = site.filter(|s| s.online) else { continue };
+
+        let (burn_1h, burn_6h) = burns(&state.sql, &slo, to).await?;
+        let burning = slo.burn_alert > 0.0
+            && burn_1h.zip(burn_6h).is_some_and(|(a, b)| {
+                a >= slo.burn_alert && b >= slo.burn_alert
+            });
+        if burning == slo.burning {
+            continue;
+        }
+
+        sqlx::query! {
+            "update slos set burning = ? where id = ?",
+            burning, slo.id
+        }
+        .execute(&state.sql)
+        .await?;
+
+        let (a, b) = (burn_1h.unwrap_or(0.0), burn_6h.unwrap_or(0.0));
+        messages.push(match burning {
+            true => format!(
+                "site {}: slo {} is burning its budget fast, {a:.1}x over \
+                 1h and {b:.1}x over 6h",
+                site.name, slo.name
+            ),
+            false => format!(
+                "site {}: slo {} is no longer burning fast",
+                site.name, slo.name
+            ),
+        });
+    }
+
+    for text in messages {
+        utils::send_message(&text).await;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::models::site::{Site, Status};
+    use crate::models::JsonStr;
+    use std::collections::HashMap;
+
+    /// `ok` 200 and `errors` 500 responses, all between 25 and 50ms
+    fn stat(ok: u64, errors: u64) -> SiteStat {
+        let status = |code: u16, count| Status {
+            code,
+            count,
+            max_time: 45,
+            min_time: 30,
+            total_time: count * 40,
+        };
+        let mut latency = vec![0; Config::LATENCY_BUCKETS.len() + 1];
+        latency[3] = (ok + errors) as i64;
+        SiteStat {
+            site: 1,
+            total: (ok + errors) as i64,
+            max_time: 45,
+            status: JsonStr(HashMap::from([
+                ("200".to_string(), status(200, ok)),
+                ("500".to_string(), status(500, errors)),
+            ])),
+            latency: JsonStr(latency),
+            ..Default::default()
+        }
+    }
+
+    fn slo(kind: SloKind, target: f64) -> Slo {
+        Slo {
+            id: 1,
+            site: 1,
+            name: "slo".to_string(),
+            kind,
+            target,
+            threshold: 30,
+            days: 30,
+            burn_alert: 14.4,
+            ..Default::default()
+        }
+    }
+
+    fn near(a: Option<f64>, b: f64) -> bool {
+        a.is_some_and(|a| (a - b).abs() < 1e-9)
+    }
+
+    #[test]
+    fn burn_rates() {
+        let availability = slo(SloKind::Availability, 0.99);
+        assert_eq!(burn(&availability, &SiteStat::default()), None);
+        assert_eq!(burn(&availability, &stat(0, 0)), None);
+        assert!(near(burn(&availability, &stat(1000, 0)), 0.0));
+        // spending the budget exactly over the window
+        assert!(near(burn(&availability, &stat(990, 10)), 1.0));
+        assert!(near(burn(&availability, &stat(0, 1000)), 100.0));
+        // a target of 100% has no budget, any error burns it at once
+        let strict = slo(SloKind::Availability, 1.0);
+        assert!(near(burn(&strict, &stat(1000, 0)), 0.0));
+        assert!(burn(&strict, &stat(999, 1)).unwrap() > 1e12);
+
+        // a fifth of the 25-50ms bucket is at most 30ms
+        let latency = slo(SloKind::Latency, 0.9);
+        assert!(near(burn(&latency, &stat(100, 0)), 8.0));
+        let mut blind = stat(100, 0);
+        blind.latency = JsonStr(Vec::new());
+        assert_eq!(burn(&latency, &blind), None);
+    }
+
+    async fn state() -> AppState {
+        let state = utils::test_state().await;
+        sqlx::query!("insert into sites(id, name) values(1, 'test')")
+            .execute(&state.sql)
+            .await
+            .unwrap();
+        let site = Site { id: 1, online: true, ..Default::default() };
+        state.sites.lock().await.insert(1, site);
+        state
+    }
+
+    async fn availability(state: &AppState) -> SloReport {
+        let slo = slo(SloKind::Availability, 0.99);
+        report(&state.sql, slo).await.unwrap()
+    }
+
+    #[actix_web::test]
+    async fn budget() {
+        let state = state().await;
+        let report = availability(&state).await;
+        assert_eq!((report.total, report.bad), (0, 0.0));
+        assert_eq!(report.compliance, None);
+        assert_eq!(report.budget_remaining, None);
+        assert_eq!((report.burn_1h, report.burn_6h), (None, None));
+
+        stats::add(&state.sql, end() - 120, &stat(995, 5)).await.unwrap();
+        // the 30 days are read from the hours
+        stats::rollup(&state.sql).await.unwrap();
+        let report = availability(&state).await;
+        assert_eq!((report.total, report.bad), (1000, 5.0));
+        assert!(near(report.compliance, 0.995));
+        assert!(near(report.budget_remaining, 0.5));
+        assert!(near(report.burn_1h, 0.5));
+
+        // every request failed: the budget is spent 100 times over
+        stats::add(&state.sql, end() - 60, &stat(0, 1000)).await.unwrap();
+        stats::add(&state.sql, end() - 120, &stat(0, 1000)).await.unwrap();
+        stats::rollup(&state.sql).await.unwrap();
+        let report = availability(&state).await;
+        assert_eq!((report.total, report.bad), (3000, 2005.0));
+        assert!(near(report.budget_remaining, 1.0 - 2005.0 / 30.0));
+    }
+
+    async fn burning(state: &AppState) -> bool {
+        sqlx::query_scalar!("select burning from slos where id = 1")
+            .fetch_one(&state.sql)
+            .await
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn burn_alert() {
+        let state = state().await;
+        let slo = slo(SloKind::Availability, 0.99);
+        sqlx::query! {
+            "insert into slos(id, site, name, target, burn_alert)
+            values(?,?,?,?,?)",
+            slo.id, slo.site, slo.name, slo.target, slo.burn_alert
+        }
+        .execute(&state.sql)
+        .await
+        .unwrap();
+
+        // 10x is under the 14.4x of the alert
+        stats::add(&state.sql, end() - 120, &stat(900, 100)).await.unwrap();
+        check(&state).await.unwrap();
+        assert!(!burning(&state).await);
+
+        // 15x over both the 1h and 6h windows
+        stats::add(&state.sql, end() - 60, &stat(800, 200)).await.unwrap();
+        check(&state).await.unwrap();
+        assert!(burning(&state).await);
+
+        // turning the alert off ends the burn
+        sqlx::query!("update slos set burn_alert = 0 where id = 1")
+            .execute(&state.sql)
+            .await
+            .unwrap();
+        check(&state).await.unwrap();
+        assert!(!burning(&state).await);
+    }
+}
@@ -0,0 +1,682 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use sqlx::{Pool, Sqlite};
+
+use crate::config::{config, Config};
+use crate::forecast::HoltWinters;
+use crate::models::site::{
+    SiteApdex, SiteForecast, SiteRoute, SiteStat, SiteSummary,
+};
+use crate::utils;
+
+async fn stat_get(
+    pool: &Pool<Sqlite>, site: i64, step: i64, timestamp: i64,
+) -> Result<SiteStat, sqlx::Error> {
+    let stat = sqlx::query_as! {
+        SiteStat,
+        "select * from sites_stats where site = ? and step = ? and timestamp = ?",
+        site, step, timestamp
+    }
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(stat.unwrap_or(SiteStat { site, step, timestamp, ..Default::default() }))
+}
+
+/// write the bucket, unless `rolled` it is left for the next rollup
+async fn stat_set(
+    pool: &Pool<Sqlite>, stat: &SiteStat, rolled: bool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query! {"
+        insert into sites_stats(
+            site, step, timestamp, total, total_time, max_time, min_time,
+            status, latency, visitors, rolled
+        ) values(?,?,?,?,?,?,?,?,?,?,?)
+        on conflict(site, step, timestamp) do update set
+        total = excluded.total,
+        visitors = excluded.visitors,
+        total_time = excluded.total_time,
+        max_time = excluded.max_time,
+        min_time = excluded.min_time,
+        status = excluded.status,
+        latency = excluded.latency,
+        rolled = excluded.rolled
+    ",
+        stat.site,
+        stat.step,
+        stat.timestamp,
+        stat.total,
+        stat.total_time,
+        stat.max_time,
+        stat.min_time,
+        stat.status,
+        stat.latency,
+        stat.visitors,
+        rolled
+    }
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// the steps that still keep a bucket starting at `start`, finest first
+fn steps_keeping(start: i64, steps: &[i64]) -> Vec<i64> {
+    let now = utils::now();
+    steps
+        .iter()
+        .copied()
+        .filter(|step| {
+            let timestamp = start - start.rem_euclid(*step);
+            config().stats_cutoff(*step, now).is_none_or(|c| timestamp >= c)
+        })
+        .collect()
+}
+
+/// add the requests of a dump window starting at `start` to its minute.
+/// a window older than the minute retention, like a replayed log, can not
+/// be rolled up from its minutes. it is added to the finest step that still
+/// keeps it and to every step above it, already rolled
+pub async fn add(
+    pool: &Pool<Sqlite>, start: i64, new: &SiteStat,
+) -> Result<(), sqlx::Error> {
+    let steps = steps_keeping(start, &Config::STATS_STEPS);
+    let backdated = steps.first() != Some(&Config::STATS_STEPS[0]);
+    let steps = match backdated {
+        true => steps.as_slice(),
+        false => &steps[..1],
+    };
+
+    for step in steps {
+        let timestamp = start - start.rem_euclid(*step);
+        let mut stat = stat_get(pool, new.site, *step, timestamp).await?;
+        stat.add(new);
+        stat_set(pool, &stat, backdated).await?;
+    }
+
+    Ok(())
+}
+
+/// rebuild the hours and days whose minutes or hours changed since the
+/// last rollup. a bucket that starts before the retention of its source
+/// step is not rebuilt, some of its source buckets may be pruned already
+pub async fn rollup(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+    let now = utils::now();
+    for pair in Config::STATS_STEPS.windows(2) {
+        let (step, next) = (pair[0], pair[1]);
+        let cutoff = config().stats_cutoff(step, now);
+        let changed = sqlx::query_as! {
+            SiteStat,
+            "select * from sites_stats where step = ? and rolled = false",
+            step
+        }
+        .fetch_all(pool)
+        .await?;
+
+        let mut buckets = BTreeSet::new();
+        for stat in changed.iter() {
+            // marked first, a dump landing after this is caught next time
+            sqlx::query! {
+                "update sites_stats set rolled = true
+                where site = ? and step = ? and timestamp = ?",
+                stat.site, stat.step, stat.timestamp
+            }
+            .execute(pool)
+            .await?;
+            buckets.insert((stat.site, stat.timestamp - stat.timestamp % next));
+        }
+
+        for (site, timestamp) in buckets {
+            if cutoff.is_some_and(|c| timestamp < c) {
+                continue;
+            }
+
+            let end = timestamp + next;
+            let stats = sqlx::query_as! {
+                SiteStat,
+                "select * from sites_stats where site = ? and step = ? and
+                timestamp >= ? and timestamp < ?",
+                site, step, timestamp, end
+            }
+            .fetch_all(pool)
+            .await?;
+
+            let mut total =
+                SiteStat { site, step: next, timestamp, ..Default::default() };
+            stats.iter().for_each(|s| total.add(s));
+            stat_set(pool, &total, false).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// add the routes of a dump window starting at `start` to their hour and
+/// day. the days are not rolled up from the hours, they are kept as long
+/// as the day stats
+pub async fn routes_add(
+    pool: &Pool<Sqlite>, start: i64, routes: &[SiteRoute],
+) -> Result<(), sqlx::Error> {
+    for step in steps_keeping(start, &Config::STATS_STEPS[1..]) {
+        let timestamp = start - start.rem_euclid(step);
+        for new in routes {
+            route_add(pool, step, timestamp, new).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn route_add(
+    pool: &Pool<Sqlite>, step: i64, timestamp: i64, new: &SiteRoute,
+) -> Result<(), sqlx::Error> {
+    let route = sqlx::query_as! {
+        SiteRoute,
+        "select * from sites_routes where site = ? and step = ? and
+        timestamp = ? and route = ?",
+        new.site, step, timestamp, new.route
+    }
+    .fetch_optional(pool)
+    .await?;
+
+    let mut route = route.unwrap_or(SiteRoute {
+        site: new.site,
+        step,
+        timestamp,
+        route: new.route.clone(),
+        ..Default::default()
+    });
+    route.add(new);
+
+    sqlx::query! {"
+        insert into sites_routes(
+            site, step, timestamp, route, total, total_time, max_time,
+            errors, latency
+        ) values(?,?,?,?,?,?,?,?,?)
+        on conflict(site, step, timestamp, route) do update set
+        total = excluded.total,
+        total_time = excluded.total_time,
+        max_time = excluded.max_time,
+        errors = excluded.errors,
+        latency = excluded.latency
+    ",
+        route.site,
+        route.step,
+        route.timestamp,
+        route.route,
+        route.total,
+        route.total_time,
+        route.max_time,
+        route.errors,
+        route.latency
+    }
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// the routes of a site from `from` to `to`, busiest first. read from the
+/// hours while they still cover `from`, from the days after that
+pub async fn routes_list(
+    pool: &Pool<Sqlite>, site: i64, from: i64, to: i64,
+) -> Result<Vec<SiteRoute>, sqlx::Error> {
+    let step = match steps_keeping(from, &Config::STATS_STEPS[1..2]).is_empty()
+    {
+        true => Config::STATS_STEPS[2],
+        false => Config::STATS_STEPS[1],
+    };
+    let rows = sqlx::query_as! {
+        SiteRoute,
+        "select * from sites_routes where site = ? and step = ? and
+        timestamp >= ? and timestamp < ?",
+        site, step, from, to
+    }
+    .fetch_all(pool)
+    .await?;
+
+    let mut routes = BTreeMap::<String, SiteRoute>::new();
+    for row in rows.iter() {
+        routes
+            .entry(row.route.clone())
+            .or_insert_with(|| SiteRoute {
+                site,
+                step,
+                timestamp: from,
+                route: row.route.clone(),
+                ..Default::default()
+            })
+            .add(row);
+    }
+
+    let mut routes = routes.into_values().collect::<Vec<_>>();
+    routes.sort_by_key(|r| std::cmp::Reverse(r.total));
+    Ok(routes)
+}
+
+/// remove the buckets that are past their retention and already rolled up
+pub async fn prune(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+    let now = utils::now();
+    let last = Config::STATS_STEPS[Config::STATS_STEPS.len() - 1];
+    for step in Config::STATS_STEPS {
+        let Some(cutoff) = config().stats_cutoff(step, now) else { continue };
+        // the last step is never rolled up
+        let rolled = step != last;
+        sqlx::query! {
+            "delete from sites_stats where step = ? and timestamp < ? and
+            (rolled = true or not ?)",
+            step, cutoff, rolled
+        }
+        .execute(pool)
+        .await?;
+    }
+
+    // the routes are kept as long as the hours and days
+    for step in &Config::STATS_STEPS[1..] {
+        let Some(cutoff) = config().stats_cutoff(*step, now) else { continue };
+        sqlx::query! {
+            "delete from sites_routes where step = ? and timestamp < ?",
+            step, cutoff
+        }
+        .execute(pool)
+        .await?;
+    }
+
+    // the probe results are kept as long as the minutes
+    if let Some(cutoff) = config().stats_cutoff(Config::STATS_STEPS[0], now) {
+        sqlx::query!("delete from probes_results where timestamp < ?", cutoff)
+            .execute(pool)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// the stats of a site from `from` to `to` in buckets of `step` seconds,
+/// read from the finest table that still covers `from`
+pub async fn list(
+    pool: &Pool<Sqlite>, site: i64, from: i64, to: i64, step: i64,
+) -> Result<Vec<SiteStat>, sqlx::Error> {
+    let now = utils::now();
+    let source = Config::STATS_STEPS
+        .into_iter()
+        .filter(|s| step % s == 0)
+        .find(|s| config().stats_cutoff(*s, now).is_none_or(|c| from >= c))
+        .unwrap_or(Config::STATS_STEPS[Config::STATS_STEPS.len() - 1]);
+
+    let stats = sqlx::query_as! {
+        SiteStat,
+        "select * from sites_stats where site = ? and step = ? and
+        timestamp >= ? and timestamp < ? order by timestamp",
+        site, source, from, to
+    }
+    .fetch_all(pool)
+    .await?;
+
+    let mut buckets = BTreeMap::<i64, SiteStat>::new();
+    for stat in stats.iter() {
+        let timestamp =
+            stat.timestamp - (stat.timestamp - from).rem_euclid(step);
+        buckets
+            .entry(timestamp)
+            .or_insert_with(|| SiteStat {
+                site,
+                step,
+                timestamp,
+                ..Default::default()
+            })
+            .add(stat);
+    }
+
+    Ok(buckets.into_values().collect())
+}
+
+/// every request of a site from `from` to `to` in a single stat
+pub async fn total(
+    pool: &Pool<Sqlite>, site: i64, from: i64, to: i64,
+) -> Result<SiteStat, sqlx::Error> {
+    // a single bucket over the whole range, any step divides whole days
+    let step = ((to - from + 86399) / 86400).max(1) * 86400;
+    let mut total = SiteStat { site, timestamp: from, ..Default::default() };
+    for stat in list(pool, site, from, to, step).await? {
+        total.add(&stat);
+    }
+
+    Ok(total)
+}
+
+/// the latency buckets of a stat, empty when some of its dogs did not send
+/// them
+pub fn latency(stat: &SiteStat) -> &[i64] {
+    match stat.latency.len() == Config::LATENCY_BUCKETS.len() + 1 {
+        true => stat.latency.as_slice(),
+        false => &[],
+    }
+}
+
+/// estimate how many requests took at most `threshold` ms, interpolating
+/// inside the latency bucket it falls in like `percentile`
+pub fn below(latency: &[i64], max_time: i64, threshold: i64) -> Option<f64> {
+    if latency.is_empty() {
+        return None;
+    }
+
+    let mut count = 0.0;
+    for (i, n) in latency.iter().enumerate() {
+        let lower = if i == 0 { 0 } else { Config::LATENCY_BUCKETS[i - 1] };
+        let upper = Config::LATENCY_BUCKETS
+            .get(i)
+            .copied()
+            .unwrap_or(max_time)
+            .max(lower);
+        if threshold >= upper {
+            count += *n as f64;
+        } else if threshold > lower {
+            let part = (threshold - lower) as f64 / (upper - lower) as f64;
+            count += *n as f64 * part;
+        }
+    }
+
+    Some(count)
+}
+
+/// satisfied, tolerating and frustrated requests for a threshold of `t` ms
+pub fn apdex(latency: &[i64], max_time: i64, errors: i64, t: i64) -> SiteApdex {
+    let total = latency.iter().sum::<i64>();
+    if total == 0 {
+        return SiteApdex::default();
+    }
+    let (Some(satisfied), Some(within)) =
+        (below(latency, max_time, t), below(latency, max_time, t * 4))
+    else {
+        return SiteApdex::default();
+    };
+
+    // the errors are spread over the buckets like the other requests
+    let ok = 1.0 - (errors as f64 / total as f64).min(1.0);
+    let satisfied = satisfied * ok;
+    let tolerating = (within * ok - satisfied).max(0.0);
+    SiteApdex {
+        total,
+        satisfied,
+        tolerating,
+        frustrated: (total as f64 - satisfied - tolerating).max(0.0),
+        score: Some((satisfied + tolerating / 2.0) / total as f64),
+        ..Default::default()
+    }
+}
+
+/// the apdex of a site from `from` to `to` in buckets of `step` seconds
+pub async fn apdex_list(
+    pool: &Pool<Sqlite>, site: i64, from: i64, to: i64, step: i64, t: i64,
+) -> Result<Vec<SiteApdex>, sqlx::Error> {
+    let stats = list(pool, site, from, to, step).await?;
+    Ok(stats
+        .iter()
+        .map(|stat| {
+            let errors = stat
+                .status
+                .values()
+                .filter(|s| s.code >= 500)
+                .map(|s| s.count as i64)
+                .sum::<i64>();
+            SiteApdex {
+                timestamp: stat.timestamp,
+                ..apdex(latency(stat), stat.max_time, errors, t)
+            }
+        })
+        .collect())
+}
+
+/// estimate the `q` quantile by interpolating inside the latency bucket it
+/// falls in. the last bucket has no bound, `max_time` is used instead
+fn percentile(latency: &[i64], max_time: i64, q: f64) -> Option<i64> {
+    let total = latency.iter().sum::<i64>();
+    if total == 0 {
+        return None;
+    }
+
+    let rank = q * total as f64;
+    let mut seen = 0;
+    for (i, count) in latency.iter().enumerate() {
+        if *count == 0 || ((seen + count) as f64) < rank {
+            seen += count;
+            continue;
+        }
+
+        let lower = if i == 0 { 0 } else { Config::LATENCY_BUCKETS[i - 1] };
+        let upper = Config::LATENCY_BUCKETS
+            .get(i)
+            .copied()
+            .unwrap_or(max_time)
+            .max(lower);
+        let part = (rank - seen as f64) / *count as f64;
+        return Some(lower + ((upper - lower) as f64 * part) as i64);
+    }
+
+    None
+}
+
+/// error rates, request rates and latency of a site from `from` to `to`
+pub async fn summary(
+    pool: &Pool<Sqlite>, site: i64, from: i64, to: i64,
+) -> Result<SiteSummary, sqlx::Error> {
+    let total = total(pool, site, from, to).await?;
+
+    let (mut c4xx, mut c5xx) = (0, 0);
+    for status in total.status.values() {
+        match status.code {
+            400..=499 => c4xx += status.count,
+            500..=599 => c5xx += status.count,
+            _ => {}
+        }
+    }
+
+    let hours = (to - from) as f64 / 3600.0;
+    let requests = total.total.max(1) as f64;
+    let latency = latency(&total);
+
+    Ok(SiteSummary {
+        from,
+        to,
+        total: total.total,
+        requests_per_day: total.total as f64 / hours * 24.0,
+        requests_per_hour: total.total as f64 / hours,
+        error_rate_4xx: c4xx as f64 / requests,
+        error_rate_5xx: c5xx as f64 / requests,
+        mean_time: total.total_time as f64 / requests,
+        min_time: total.min_time,
+        max_time: total.max_time,
+        p50: percentile(latency, total.max_time, 0.50),
+        p90: percentile(latency, total.max_time, 0.90),
+        p95: percentile(latency, total.max_time, 0.95),
+        p99: percentile(latency, total.max_time, 0.99),
+    })
+}
+
+/// forecast the daily requests of the next `days` days from up to a year
+/// of daily stats, with a weekly seasonality. `None` when the site has
+/// less than two weeks of history
+pub async fn forecast(
+    pool: &Pool<Sqlite>, site: i64, days: usize,
+) -> Result<Option<Vec<SiteForecast>>, sqlx::Error> {
+    const DAY: i64 = 86400;
+    let today = utils::now() - utils::now().rem_euclid(DAY);
+    let from = today - 365 * DAY;
+    let stats = list(pool, site, from, today, DAY).await?;
+
+    let Some(first) = stats.first().map(|s| s.timestamp) else {
+        return Ok(None);
+    };
+    let mut series = vec![0.0; ((today - first) / DAY) as usize];
+    for stat in stats.iter() {
+        series[((stat.timestamp - first) / DAY) as usize] = stat.total as f64;
+    }
+
+    let Some(model) = HoltWinters::fit(&series, 7) else { return Ok(None) };
+    let forecast = model
+        .forecast(days)
+        .into_iter()
+        .enumerate()
+        .map(|(i, p)| SiteForecast {
+            timestamp: today + i as i64 * DAY,
+            requests: p.value,
+            lower: p.lower,
+            upper: p.upper,
+        })
+        .collect();
+
+    Ok(Some(forecast))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::JsonStr;
+
+    /// 100 requests spread over the first four buckets: 0-5, 5-10, 10-25
+    /// and 25-50ms
+    fn buckets() -> Vec<i64> {
+        let mut latency = vec![0; Config::LATENCY_BUCKETS.len() + 1];
+        latency[..4].copy_from_slice(&[40, 30, 20, 10]);
+        latency
+    }
+
+    #[test]
+    fn percentiles() {
+        let latency = buckets();
+        assert_eq!(percentile(&latency, 50, 0.2), Some(2));
+        assert_eq!(percentile(&latency, 50, 0.5), Some(6));
+        assert_eq!(percentile(&latency, 50, 0.9), Some(25));
+        assert_eq!(percentile(&latency, 50, 0.95), Some(37));
+        assert_eq!(percentile(&[0; 12], 50, 0.5), None);
+
+        // the last bucket is bounded by the slowest request
+        let mut slow = vec![0; Config::LATENCY_BUCKETS.len() + 1];
+        slow[11] = 10;
+        assert_eq!(percentile(&slow, 30000, 0.5), Some(20000));
+    }
+
+    fn near(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn requests_below() {
+        let latency = buckets();
+        assert_eq!(below(&latency, 50, 10), Some(70.0));
+        assert!(near(
+            below(&latency, 50, 20).unwrap(),
+            70.0 + 20.0 * 10.0 / 15.0
+        ));
+        assert_eq!(below(&latency, 50, 0), Some(0.0));
+        assert_eq!(below(&latency, 50, 60000), Some(100.0));
+        assert_eq!(below(&[], 50, 10), None);
+    }
+
+    #[test]
+    fn apdex_score() {
+        let latency = buckets();
+        let a = apdex(&latency, 50, 0, 10);
+        assert_eq!(a.total, 100);
+        assert!(near(a.satisfied, 70.0));
+        assert!(near(a.tolerating, 26.0));
+        assert!(near(a.frustrated, 4.0));
+        assert!(near(a.score.unwrap(), 0.83));
+
+        // errors are frustrated whatever their latency
+        let a = apdex(&latency, 50, 10, 10);
+        assert!(near(a.satisfied, 63.0));
+        assert!(near(a.tolerating, 23.4));
+        assert!(near(a.frustrated, 13.6));
+        assert!(near(a.score.unwrap(), 0.747));
+
+        assert_eq!(apdex(&[], 50, 0, 10).score, None);
+    }
+
+    async fn site() -> Pool<Sqlite> {
+        let pool = utils::test_pool().await;
+        sqlx::query!("insert into sites(id, name) values(1, 'test')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool
+    }
+
+    fn window(total: i64) -> SiteStat {
+        let mut latency = vec![0; Config::LATENCY_BUCKETS.len() + 1];
+        latency[3] = total;
+        SiteStat {
+            site: 1,
+            total,
+            total_time: total * 40,
+            max_time: 45,
+            min_time: 30,
+            latency: JsonStr(latency),
+            ..Default::default()
+        }
+    }
+
+    #[actix_web::test]
+    async fn backfill() {
+        let pool = site().await;
+        let start = utils::now() - 30 * 86400;
+        let day = start - start.rem_euclid(86400);
+        let hour = start - start.rem_euclid(3600);
+        add(&pool, start, &window(10)).await.unwrap();
+        add(&pool, start + 60, &window(5)).await.unwrap();
+
+        // past the minutes, the window went to its hour and day
+        let hours = list(&pool, 1, day, day + 86400, 3600).await.unwrap();
+        assert_eq!(hours.len(), 1);
+        assert_eq!((hours[0].timestamp, hours[0].total), (hour, 15));
+        assert_eq!(hours[0].latency[3], 15);
+
+        // a rollup does not rebuild them from the missing minutes
+        rollup(&pool).await.unwrap();
+        let days = list(&pool, 1, day, day + 86400, 86400).await.unwrap();
+        assert_eq!(days.len(), 1);
+        assert_eq!((days[0].total, days[0].total_time), (15, 600));
+        assert_eq!(total(&pool, 1, day, day + 86400).await.unwrap().total, 15);
+
+        let routes = [SiteRoute {
+            site: 1,
+            route: "/users/:id".into(),
+            total: 15,
+            ..Default::default()
+        }];
+        routes_add(&pool, start, &routes).await.unwrap();
+        let listed = routes_list(&pool, 1, day, day + 86400).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].total, 15);
+
+        // older than every retention but the days
+        let old = utils::now() - 400 * 86400;
+        add(&pool, old, &window(7)).await.unwrap();
+        let day = old - old.rem_euclid(86400);
+        let days = list(&pool, 1, day, day + 86400, 86400).await.unwrap();
+        assert_eq!(days[0].total, 7);
+    }
+
+    #[actix_web::test]
+    async fn rollup_skips_pruned_sources() {
+        let pool = site().await;
+        let cutoff = config().stats_cutoff(60, utils::now()).unwrap();
+        let minute = cutoff + 60 - cutoff.rem_euclid(60);
+        let hour = minute - minute.rem_euclid(3600);
+        // the first minutes of the hour are gone, only its last one is new
+        if hour >= cutoff {
+            return;
+        }
+
+        let mut kept = window(100);
+        kept.step = 3600;
+        kept.timestamp = hour;
+        stat_set(&pool, &kept, true).await.unwrap();
+        add(&pool, minute, &window(1)).await.unwrap();
+        rollup(&pool).await.unwrap();
+
+        let stat = stat_get(&pool, 1, 3600, hour).await.unwrap();
+        assert_eq!(stat.total, 100);
+    }
+}
@@ -0,0 +1,348 @@
+use crate::config::Config;
+use crate::models::incident::{Incident, IncidentCause};
+use crate::models::site::SiteHealth;
+use crate::models::status::{
+    StatusDay, StatusIncident, StatusLevel, StatusNote, StatusPage,
+    StatusReport, StatusSite,
+};
+use crate::{incident, utils, AppState};
+
+fn level(
+    health: &SiteHealth, degraded: bool, probed: bool, open: &[&Incident],
+) -> StatusLevel {
+    let down = open.iter().any(|i| {
+        matches!(
+            i.cause,
+            IncidentCause::MissedPings | IncidentCause::ProbeFailure
+        )
+    });
+    if down || *health == SiteHealth::Down {
+        StatusLevel::Outage
+    } else if degraded || !open.is_empty() {
+        StatusLevel::Degraded
+    } else if *health == SiteHealth::Up || probed {
+        StatusLevel::Operational
+    } else {
+        StatusLevel::Unknown
+    }
+}
+
+/// the sites of a status page with their daily uptime over the last
+/// `Config::STATUS_DAYS` days and their open incidents
+pub async fn report(
+    state: &AppState, page: StatusPage,
+) -> Result<StatusReport, sqlx::Error> {
+    let now = utils::now();
+    let today = now - now.rem_euclid(86400);
+    let from = today - (Config::STATUS_DAYS - 1) * 86400;
+
+    let mut sites = Vec::with_capacity(page.sites.len());
+    for id in page.sites.iter() {
+        let site = state.sites.lock().await.get(id).cloned();
+        let Some(site) = site else { continue };
+
+        let incidents = sqlx::query_as! {
+            Incident,
+            "select * from incidents where site = ? and started < ? and
+            (ended = 0 or ended > ?) order by started",
+            site.id, now, from
+        }
+        .fetch_all(&state.sql)
+        .await?;
+
+        // the days before the site was added have no data, not full uptime
+        let days = (0..Config::STATUS_DAYS)
+            .map(|i| {
+                let day = from + i * 86400;
+                let start = day.max(site.timestamp);
+                let end = (day + 86400).min(now);
+                if start >= end {
+                    return StatusDay {
+                        timestamp: day,
+                        uptime: None,
+                        downtime_minutes: 0.0,
+                    };
+                }
+                let down = incident::downtime(&incidents, start, end);
+                let total = end - start;
+                StatusDay {
+                    timestamp: day,
+                    uptime: Some((total - down) as f64 / total as f64 * 100.0),
+                    downtime_minutes: down as f64 / 60.0,
+                }
+            })
+            .collect();
+        let since = from.max(site.timestamp).min(now);
+        let down = incident::downtime(&incidents, since, now);
+        let total = (now - since).max(1);
+
+        let open =
+            incidents.iter().filter(|i| i.ended == 0).collect::<Vec<_>>();
+        let probed = sqlx::query! {
+            "select id from probes where site = ? and enabled = true and
+            checked > 0 limit 1",
+            site.id
+        }
+        .fetch_optional(&state.sql)
+        .await?
+        .is_some();
+        let status = level(&site.health, site.degraded, probed, &open);
+
+        let mut active = Vec::with_capacity(open.len());
+        for incident in open {
+            let notes = sqlx::query_as! {
+                StatusNote,
+                "select timestamp, text from incidents_notes
+                where incident = ? order by id desc",
+                incident.id
+            }
+            .fetch_all(&state.sql)
+            .await?;
+
+            active.push(StatusIncident {
+                cause: incident.cause.clone(),
+                started: incident.started,
+                text: incident.text.clone(),
+                notes,
+            });
+        }
+
+        sites.push(StatusSite {
+            name: site.name,
+            status,
+            uptime: (total - down) as f64 / total as f64 * 100.0,
+            days,
+            incidents: active,
+        });
+    }
+
+    let status = [StatusLevel::Outage, StatusLevel::Degraded]
+        .into_iter()
+        .find(|l| sites.iter().any(|s| s.status == *l))
+        .or_else(|| {
+            let known = sites.iter().any(|s| s.status != StatusLevel::Unknown);
+            known.then_some(StatusLevel::Operational)
+        })
+        .unwrap_or(StatusLevel::Unknown);
+
+    Ok(StatusReport {
+        slug: page.slug,
+        title: page.title,
+        description: page.description,
+        status,
+        sites,
+        timestamp: now,
+    })
+}
+
+fn date(timestamp: i64, format: &str) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|d| d.format(format).to_string())
+        .unwrap_or_default()
+}
+
+fn label(level: &StatusLevel) -> (&'static str, &'static str) {
+    match level {
+        StatusLevel::Operational => ("operational", "Operational"),
+        StatusLevel::Degraded => ("degraded", "Degraded"),
+        StatusLevel::Outage => ("outage", "Outage"),
+        StatusLevel::Unknown => ("unknown", "Unknown"),
+    }
+}
+
+const STYLE: &str = "
+body { margin: 0 auto; max-width: 860px; padding: 24px;
+    font-family: system-ui, sans-serif; background: #040404; color: #f2f2f2 }
+h1 { margin: 0 0 8px } p { color: #aaa }
+.banner, .site { border: 1px solid #222; border-radius: 8px; padding: 16px;
+    margin: 16px 0 }
+.row { display: flex; justify-content: space-between; align-items: center }
+.operational { color: #00dc7d } .degraded { color: #ffd600 }
+.outage { color: #ec0f0f } .unknown { color: #888 }
+.bars { display: flex; gap: 2px; height: 32px; margin: 12px 0 4px }
+.bars span { flex: 1; border-radius: 2px; background: #00dc7d }
+.bars .minor { background: #ffd600 } .bars .major { background: #ec0f0f }
+.bars .none { background: #333 }
+.legend { display: flex; justify-content: space-between; color: #888;
+    font-size: 13px }
+.incident { border-left: 3px solid #ec0f0f; padding: 4px 12px; margin: 12px 0 }
+.note { color: #ccc; margin: 6px 0 } small { color: #888 }
+";
+
+/// the status report as a standalone page, refreshed every minute
+pub fn html(report: &StatusReport) -> String {
+    let (class, text) = label(&report.status);
+    let mut out = format!(
+        "<!doctype html><html><head><meta charset=\"utf-8\">\
+        <meta name=\"viewport\" content=\"width=device-width\">\
+        <meta http-equiv=\"refresh\" content=\"60\">\
+        <title>{title}</title><style>{STYLE}</style></head><body>\
+        <h1>{title}</h1><p>{description}</p>\
+        <div class=\"banner {class}\"><b>{text}</b></div>",
+        title = utils::escape(&report.title),
+        description = utils::escape(&report.description),
+    );
+
+    for site in report.sites.iter() {
+        let (class, text) = label(&site.status);
+        out += &format!(
+            "<div class=\"site\"><div class=\"row\"><b>{}</b>\
+            <span class=\"{class}\">{text}</span></div><div class=\"bars\">",
+            utils::escape(&site.name)
+        );
+        for day in site.days.iter() {
+            let Some(uptime) = day.uptime else {
+                out += &format!(
+                    "<span class=\"none\" title=\"{}: no data\"></span>",
+                    date(day.timestamp, "%Y-%m-%d")
+                );
+                continue;
+            };
+            let class = match uptime {
+                u if u >= 100.0 => "",
+                u if u >= 99.0 => "minor",
+                _ => "major",
+            };
+            out += &format!(
+                "<span class=\"{class}\" title=\"{}: {:.2}%, {:.0}m down\">\
+                </span>",
+                date(day.timestamp, "%Y-%m-%d"),
+                uptime,
+                day.downtime_minutes
+            );
+        }
+        out += &format!(
+            "</div><div class=\"legend\"><span>{} days ago</span>\
+            <span>{:.2}% uptime</span><span>today</span></div>",
+            site.days.len(),
+            site.uptime
+        );
+
+        for incident in site.incidents.iter() {
+            let cause = format!("{:?}", incident.cause);
+            out += &format!(
+                "<div class=\"incident\"><b>{}</b> <small>since {}</small>",
+                utils::escape(match incident.text.is_empty() {
+                    true => &cause,
+                    false => &incident.text,
+                }),
+                date(incident.started, "%Y-%m-%d %H:%M UTC")
+            );
+            for note in incident.notes.iter() {
+                out += &format!(
+                    "<div class=\"note\"><small>{}</small> {}</div>",
+                    date(note.timestamp, "%Y-%m-%d %H:%M UTC"),
+                    utils::escape(&note.text)
+                );
+            }
+            out += "</div>";
+        }
+        out += "</div>";
+    }
+
+    out += &format!(
+        "<small>updated {}</small></body></html>",
+        date(report.timestamp, "%Y-%m-%d %H:%M UTC")
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::site::Site;
+    use crate::models::JsonStr;
+
+    #[test]
+    fn levels() {
+        let (up, down) = (SiteHealth::Up, SiteHealth::Down);
+        let incident = |cause| Incident { cause, ..Default::default() };
+        let (missed, errors) = (
+            incident(IncidentCause::MissedPings),
+            incident(IncidentCause::ErrorRate),
+        );
+
+        assert_eq!(level(&up, false, false, &[]), StatusLevel::Operational);
+        assert_eq!(level(&down, false, true, &[]), StatusLevel::Outage);
+        assert_eq!(level(&up, false, false, &[&missed]), StatusLevel::Outage);
+        assert_eq!(level(&up, true, false, &[]), StatusLevel::Degraded);
+        assert_eq!(level(&up, false, false, &[&errors]), StatusLevel::Degraded);
+        // a site that never pinged is only known from its probes
+        let unknown = SiteHealth::Unknown;
+        assert_eq!(level(&unknown, false, false, &[]), StatusLevel::Unknown);
+        assert_eq!(level(&unknown, false, true, &[]), StatusLevel::Operational);
+    }
+
+    #[actix_web::test]
+    async fn days() {
+        let state = utils::test_state().await;
+        let now = utils::now();
+        let today = now - now.rem_euclid(86400);
+        let yesterday = today - 86400;
+        // added on the morning of the day before yesterday
+        let added = yesterday - 86400 + 6 * 3600;
+        sqlx::query!("insert into sites(id, name) values(1, 'test')")
+            .execute(&state.sql)
+            .await
+            .unwrap();
+        let site = Site {
+            id: 1,
+            name: "test".to_string(),
+            health: SiteHealth::Up,
+            timestamp: added,
+            ..Default::default()
+        };
+        state.sites.lock().await.insert(1, site);
+
+        // an hour down yesterday, overlapping incidents count once
+        let (start, end) = (yesterday + 12 * 3600, yesterday + 13 * 3600);
+        for (started, ended) in [(start, end - 1800), (start + 600, end)] {
+            sqlx::query! {
+                "insert into incidents(site, started, ended) values(1,?,?)",
+                started, ended
+            }
+            .execute(&state.sql)
+            .await
+            .unwrap();
+        }
+
+        let page = StatusPage {
+            slug: "main".to_string(),
+            title: "Main".to_string(),
+            sites: JsonStr(vec![1, 2]),
+            ..Default::default()
+        };
+        let operational = report(&state, page.clone()).await.unwrap();
+        assert_eq!(operational.status, StatusLevel::Operational);
+        // unknown sites are left out
+        assert_eq!(operational.sites.len(), 1);
+        let days = &operational.sites[0].days;
+        assert_eq!(days.len(), Config::STATUS_DAYS as usize);
+        assert_eq!(days[days.len() - 1].timestamp, today);
+        assert!(days
+            .windows(2)
+            .all(|d| d[1].timestamp - d[0].timestamp == 86400));
+
+        let day = |ago: usize| &days[days.len() - 1 - ago];
+        assert_eq!(day(3).uptime, None);
+        assert_eq!(day(2).uptime, Some(100.0));
+        assert_eq!(day(1).downtime_minutes, 60.0);
+        assert_eq!(day(1).uptime, Some(23.0 / 24.0 * 100.0));
+        assert!(html(&operational).contains("no data"));
+
+        let (cause, started) = (IncidentCause::ErrorRate, now - 60);
+        sqlx::query! {
+            "insert into incidents(site, cause, started, text) values(1,?,?,?)",
+            cause, started, "<5xx>"
+        }
+        .execute(&state.sql)
+        .await
+        .unwrap();
+        let degraded = report(&state, page).await.unwrap();
+        assert_eq!(degraded.status, StatusLevel::Degraded);
+        assert_eq!(degraded.sites[0].incidents.len(), 1);
+        let html = html(&degraded);
+        assert!(html.contains("banner degraded"));
+        assert!(html.contains("&lt;5xx&gt;"));
+    }
+}
@@ -0,0 +1,68 @@
+use std::os::{
+    linux::net::SocketAddrExt,
+    unix::net::{SocketAddr, UnixDatagram},
+};
+use std::time::Duration;
+
+use actix_web::web::Data;
+
+use crate::{utils, AppState};
+
+/// send a state string (`READY=1`, `STATUS=...`, `WATCHDOG=1`) to systemd.
+/// does nothing when web was not started by a `Type=notify` unit
+pub fn notify(state: &str) {
+    let Ok(path) = std::env::var("NOTIFY_SOCKET") else { return };
+
+    let addr = match path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name),
+        None => SocketAddr::from_pathname(&path),
+    };
+
+    let result = UnixDatagram::unbound().and_then(|sock| {
+        addr.and_then(|a| sock.send_to_addr(state.as_bytes(), &a))
+    });
+    if let Err(e) = result {
+        log::error!("could not notify systemd: {e}");
+    }
+}
+
+/// report the status to systemd and ping the watchdog at half of
+/// `WatchdogSec`. the ping is skipped when the database or the sites lock
+/// do not answer in time, so systemd restarts a stuck service
+pub async fn watchdog(state: Data<AppState>) {
+    let watchdog = std::env::var("WATCHDOG_USEC")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(|v| Duration::from_micros(v / 2));
+    let period = watchdog.unwrap_or(Duration::MAX).min(Duration::from_secs(10));
+    let mut interval = tokio::time::interval(period);
+
+    loop {
+        interval.tick().await;
+
+        let check = async {
+            sqlx::query!("select 1 as one").fetch_one(&state.sql).await.ok()?;
+            let sites = state.sites.lock().await;
+            let latest = sites.values().map(|s| s.latest_dump_timestamp).max();
+            Some((sites.len(), latest.unwrap_or_default()))
+        };
+
+        let Ok(Some((count, latest))) =
+            tokio::time::timeout(Duration::from_secs(5), check).await
+        else {
+            log::error!("watchdog check failed");
+            continue;
+        };
+
+        let latest = match latest {
+            0 => "never".to_string(),
+            t => format!("{}s ago", utils::now() - t),
+        };
+        let status = format!("sites {count}, last dump {latest}");
+        if watchdog.is_some() {
+            notify(&format!("WATCHDOG=1\nSTATUS={status}"));
+        } else {
+            notify(&format!("STATUS={status}"));
+        }
+    }
+}
@@ -24,6 +24,22 @@ pub fn get_random_string(charset: &[u8], len: usize) -> String {
     (0..len).map(|_| charset[rng.gen_range(0..charset.len())] as char).collect()
 }
 
+/// escape text for html and svg
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
 pub async fn send_message(text: &str) {
     if cfg!(debug_assertions) {
         log::info!("send_message: {text}");
@@ -91,3 +107,33 @@ impl CutOff for Option<String> {
         }
     }
 }
+
+/// an in memory database with every migration, for the tests
+#[cfg(test)]
+pub async fn test_pool() -> sqlx::SqlitePool {
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        // every connection to :memory: is a database of its own
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect("sqlite::memory:")
+        .await
+        .expect("test database");
+    sqlx::migrate!().run(&pool).await.expect("test migrations");
+    pool
+}
+
+/// the app state over a `test_pool`, with no sites loaded
+#[cfg(test)]
+pub async fn test_state() -> crate::AppState {
+    use std::collections::{HashMap, HashSet};
+    use tokio::sync::Mutex;
+
+    crate::AppState {
+        sql: test_pool().await,
+        sites: Mutex::new(HashMap::new()),
+        nonces: Mutex::new(HashMap::new()),
+        probes: Mutex::new(HashSet::new()),
+        status: Mutex::new(HashMap::new()),
+    }
+}